@@ -1,7 +1,11 @@
-use bellperson::{Index, LinearCombination, Variable};
+use bellperson::groth16::{create_random_proof, generate_random_parameters};
+use bellperson::util_cs::test_cs::TestConstraintSystem;
+use bellperson::{Circuit, ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use ff::{Field, ScalarEngine};
 use paired::bls12_381::Bls12;
+use paired::Engine;
+use rand::thread_rng;
 
 fn lc_benchmark(c: &mut Criterion) {
     c.bench_function("LinearCombination::add((Fr, Variable))", |b| {
@@ -35,5 +39,101 @@ fn lc_benchmark(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, lc_benchmark);
+/// A circuit made of many small constraints, similar in shape to what the
+/// boolean and hashing gadgets produce: each constraint has 1-3 terms per
+/// linear combination.
+#[derive(Clone)]
+struct SmallTermsCircuit<E: Engine> {
+    x: Option<E::Fr>,
+    num_constraints: usize,
+}
+
+impl<E: Engine> Circuit<E> for SmallTermsCircuit<E> {
+    fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let mut x_val = self.x;
+        let mut x = cs.alloc(|| "x", || x_val.ok_or(SynthesisError::AssignmentMissing))?;
+
+        for i in 0..self.num_constraints {
+            // y = (x + 1) * x
+            let y_val = x_val.map(|mut v| {
+                let mut tmp = v;
+                tmp.add_assign(&E::Fr::one());
+                v.mul_assign(&tmp);
+                v
+            });
+            let y = cs.alloc(
+                || format!("y {}", i),
+                || y_val.ok_or(SynthesisError::AssignmentMissing),
+            )?;
+
+            cs.enforce(
+                || format!("y = (x + 1) * x {}", i),
+                |lc| lc + x + CS::one(),
+                |lc| lc + x,
+                |lc| lc + y,
+            );
+
+            x = y;
+            x_val = y_val;
+        }
+
+        let out = cs.alloc_input(|| "out", || x_val.ok_or(SynthesisError::AssignmentMissing))?;
+        cs.enforce(
+            || "out = x",
+            |lc| lc + x,
+            |lc| lc + CS::one(),
+            |lc| lc + out,
+        );
+
+        Ok(())
+    }
+}
+
+fn synthesis_benchmark(c: &mut Criterion) {
+    let circuit = SmallTermsCircuit::<Bls12> {
+        x: Some(<Bls12 as ScalarEngine>::Fr::one()),
+        num_constraints: 1 << 12,
+    };
+
+    c.bench_function("synthesize small-term circuit", |b| {
+        b.iter(|| {
+            let mut cs = TestConstraintSystem::<Bls12>::new();
+            circuit.clone().synthesize(&mut cs).unwrap();
+            black_box(cs);
+        });
+    });
+}
+
+fn proving_benchmark(c: &mut Criterion) {
+    let rng = &mut thread_rng();
+    let num_constraints = 1 << 12;
+
+    let params = generate_random_parameters::<Bls12, _, _>(
+        SmallTermsCircuit::<Bls12> {
+            x: None,
+            num_constraints,
+        },
+        rng,
+    )
+    .unwrap();
+
+    let circuit = SmallTermsCircuit::<Bls12> {
+        x: Some(<Bls12 as ScalarEngine>::Fr::one()),
+        num_constraints,
+    };
+
+    let mut group = c.benchmark_group("proving");
+    group.sample_size(10);
+    group.bench_function("prove small-term circuit", |b| {
+        b.iter(|| black_box(create_random_proof(circuit.clone(), &params, rng).unwrap()));
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    lc_benchmark,
+    synthesis_benchmark,
+    proving_benchmark
+);
 criterion_main!(benches);
@@ -0,0 +1,430 @@
+//! Digests of the shape of a circuit.
+//!
+//! A `CircuitDigest` identifies the constraint matrices of a circuit together
+//! with its number of inputs, auxiliary variables and constraints, but not any
+//! witness values. It is used to tie parameters to the circuit they were
+//! generated for.
+//!
+//! The digest is computed while synthesizing, by evaluating the constraint
+//! matrices as a polynomial at points derived from fixed labels: every
+//! coefficient in column `var` of constraint `j` in matrix `A`, `B` or `C` is
+//! weighted by `alpha^j * rho_M * beta^i` (for `Index::Aux(i)`) or
+//! `gamma^i` (for `Index::Input(i)`). Unlike a hash over the constraints in
+//! order, such a sum can be shifted when merging constraint systems, so the
+//! digest does not depend on whether a circuit was synthesized in parallel.
+//! The final digest is a BLAKE2s hash of the counts and the evaluations.
+//!
+//! The digest detects accidental changes to a circuit; it is not meant to be
+//! resistant against deliberately crafted collisions.
+
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use blake2s_simd::{Params as Blake2sParams, State as Blake2sState};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use ff::{Field, PrimeField, PrimeFieldRepr, ScalarEngine};
+
+use crate::{Index, LinearCombination};
+
+const DIGEST_PERSONALIZATION: &[u8; 8] = b"BP_Shape";
+const POINT_PERSONALIZATION: &[u8; 8] = b"BP_Point";
+
+// Powers are looked up as `high[i >> WINDOW] * low[i & (2^WINDOW - 1)]`.
+const WINDOW: usize = 10;
+
+/// The digest of the shape of a circuit.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CircuitDigest(pub [u8; 32]);
+
+impl fmt::Display for CircuitDigest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for byte in &self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for CircuitDigest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CircuitDigest({})", self)
+    }
+}
+
+/// Powers of a field element, with a small table of consecutive powers and
+/// a growing table of strides.
+#[derive(Clone)]
+struct Powers<E: ScalarEngine> {
+    low: Vec<E::Fr>,
+    high: Vec<E::Fr>,
+    stride: E::Fr,
+}
+
+impl<E: ScalarEngine> Powers<E> {
+    fn new(base: E::Fr) -> Self {
+        let mut low = Vec::with_capacity(1 << WINDOW);
+        let mut power = E::Fr::one();
+        for _ in 0..(1 << WINDOW) {
+            low.push(power);
+            power.mul_assign(&base);
+        }
+
+        Powers {
+            low,
+            high: vec![E::Fr::one()],
+            stride: power,
+        }
+    }
+
+    fn get(&mut self, i: usize) -> E::Fr {
+        let high = i >> WINDOW;
+        while self.high.len() <= high {
+            let mut next = *self.high.last().unwrap();
+            next.mul_assign(&self.stride);
+            self.high.push(next);
+        }
+
+        let mut power = self.high[high];
+        power.mul_assign(&self.low[i & ((1 << WINDOW) - 1)]);
+        power
+    }
+}
+
+/// Derives a field element from `label`.
+fn point<E: ScalarEngine>(label: &[u8]) -> E::Fr {
+    let mut state = Blake2sParams::new()
+        .hash_length(32)
+        .personal(POINT_PERSONALIZATION)
+        .to_state();
+    state.update(label);
+
+    hash_to_scalar(&state)
+}
+
+/// Derives a field element from the data hashed into `state`, by hashing it
+/// with a counter until there are enough bytes.
+pub(crate) fn hash_to_scalar<F: PrimeField>(state: &Blake2sState) -> F {
+    let mut repr = F::Repr::default();
+    let len = repr.as_ref().len() * 8;
+
+    let mut bytes = Vec::with_capacity(len + 32);
+    let mut counter = 0u32;
+    while bytes.len() < len {
+        let hash = state.clone().update(&counter.to_le_bytes()).finalize();
+        bytes.extend_from_slice(hash.as_bytes());
+        counter += 1;
+    }
+    repr.read_le(&bytes[..len])
+        .expect("enough bytes were hashed");
+
+    // Keep fewer bits than the modulus has, so the value is always in range.
+    let mut remaining = F::NUM_BITS as usize - 1;
+    for limb in repr.as_mut() {
+        if remaining >= 64 {
+            remaining -= 64;
+        } else {
+            *limb &= (1u64 << remaining) - 1;
+            remaining = 0;
+        }
+    }
+
+    F::from_repr(repr).expect("value is below the modulus")
+}
+
+/// Computes the `CircuitDigest` of a constraint system while it is being
+/// synthesized. Constraint systems feed every allocation and constraint into
+/// it, and merge the hashers of extended constraint systems.
+#[derive(Clone)]
+pub struct ShapeHasher<E: ScalarEngine> {
+    num_inputs: usize,
+    num_aux: usize,
+    num_constraints: usize,
+
+    alpha: E::Fr,
+    rho_b: E::Fr,
+    rho_c: E::Fr,
+    input_powers: Powers<E>,
+    aux_powers: Powers<E>,
+
+    // alpha^num_constraints
+    constraint_power: E::Fr,
+
+    // The evaluation, split by the kind of variable, so that it can be
+    // shifted when extending.
+    one: E::Fr,
+    inputs: E::Fr,
+    aux: E::Fr,
+}
+
+impl<E: ScalarEngine> Default for ShapeHasher<E> {
+    fn default() -> Self {
+        ShapeHasher {
+            num_inputs: 0,
+            num_aux: 0,
+            num_constraints: 0,
+            alpha: point::<E>(b"constraint"),
+            rho_b: point::<E>(b"b"),
+            rho_c: point::<E>(b"c"),
+            input_powers: Powers::new(point::<E>(b"input")),
+            aux_powers: Powers::new(point::<E>(b"aux")),
+            constraint_power: E::Fr::one(),
+            one: E::Fr::zero(),
+            inputs: E::Fr::zero(),
+            aux: E::Fr::zero(),
+        }
+    }
+}
+
+impl<E: ScalarEngine> ShapeHasher<E> {
+    pub fn new() -> Self {
+        ShapeHasher::default()
+    }
+
+    pub fn alloc_input(&mut self) {
+        self.num_inputs += 1;
+    }
+
+    pub fn alloc(&mut self) {
+        self.num_aux += 1;
+    }
+
+    pub fn enforce(
+        &mut self,
+        a: &LinearCombination<E>,
+        b: &LinearCombination<E>,
+        c: &LinearCombination<E>,
+    ) {
+        let mut weight = self.constraint_power;
+        self.add_lc(a, &weight);
+        weight.mul_assign(&self.rho_b);
+        self.add_lc(b, &weight);
+        let mut weight = self.constraint_power;
+        weight.mul_assign(&self.rho_c);
+        self.add_lc(c, &weight);
+
+        self.constraint_power.mul_assign(&self.alpha);
+        self.num_constraints += 1;
+    }
+
+    fn add_lc(&mut self, lc: &LinearCombination<E>, weight: &E::Fr) {
+        let mut one = E::Fr::zero();
+        let mut inputs = E::Fr::zero();
+        let mut aux = E::Fr::zero();
+
+        for (var, coeff) in lc.iter() {
+            match var.get_unchecked() {
+                Index::Input(0) => one.add_assign(coeff),
+                Index::Input(i) => {
+                    let mut tmp = self.input_powers.get(i);
+                    tmp.mul_assign(coeff);
+                    inputs.add_assign(&tmp);
+                }
+                Index::Aux(i) => {
+                    let mut tmp = self.aux_powers.get(i);
+                    tmp.mul_assign(coeff);
+                    aux.add_assign(&tmp);
+                }
+            }
+        }
+
+        one.mul_assign(weight);
+        self.one.add_assign(&one);
+        inputs.mul_assign(weight);
+        self.inputs.add_assign(&inputs);
+        aux.mul_assign(weight);
+        self.aux.add_assign(&aux);
+    }
+
+    /// Merges the hasher of a constraint system which is appended to ours,
+    /// following the renumbering of `ConstraintSystem::extend`: the first
+    /// input of `other` is its temporary "one" variable, which is skipped.
+    pub fn extend(&mut self, other: &ShapeHasher<E>) {
+        let input_shift = self.input_powers.get(self.num_inputs - 1);
+        let aux_shift = self.aux_powers.get(self.num_aux);
+
+        let mut one = other.one;
+        one.mul_assign(&self.constraint_power);
+        self.one.add_assign(&one);
+
+        let mut inputs = other.inputs;
+        inputs.mul_assign(&input_shift);
+        inputs.mul_assign(&self.constraint_power);
+        self.inputs.add_assign(&inputs);
+
+        let mut aux = other.aux;
+        aux.mul_assign(&aux_shift);
+        aux.mul_assign(&self.constraint_power);
+        self.aux.add_assign(&aux);
+
+        self.constraint_power.mul_assign(&other.constraint_power);
+        self.num_inputs += other.num_inputs - 1;
+        self.num_aux += other.num_aux;
+        self.num_constraints += other.num_constraints;
+    }
+
+    pub fn num_inputs(&self) -> usize {
+        self.num_inputs
+    }
+
+    pub fn num_aux(&self) -> usize {
+        self.num_aux
+    }
+
+    pub fn num_constraints(&self) -> usize {
+        self.num_constraints
+    }
+
+    pub fn finish(&self) -> CircuitDigest {
+        let mut state = Blake2sParams::new()
+            .hash_length(32)
+            .personal(DIGEST_PERSONALIZATION)
+            .to_state();
+
+        for count in &[self.num_inputs, self.num_aux, self.num_constraints] {
+            state.update(&(*count as u64).to_le_bytes());
+        }
+        for value in &[self.one, self.inputs, self.aux] {
+            let mut bytes = vec![];
+            value
+                .into_repr()
+                .write_le(&mut bytes)
+                .expect("writing to a vector does not fail");
+            state.update(&bytes);
+        }
+
+        let mut digest = [0u8; 32];
+        digest.copy_from_slice(state.finalize().as_bytes());
+        CircuitDigest(digest)
+    }
+
+    /// Serializes the state of the hasher, to resume it with `read`.
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_u64::<BigEndian>(self.num_inputs as u64)?;
+        writer.write_u64::<BigEndian>(self.num_aux as u64)?;
+        writer.write_u64::<BigEndian>(self.num_constraints as u64)?;
+        for value in &[self.constraint_power, self.one, self.inputs, self.aux] {
+            value.into_repr().write_be(&mut writer)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut hasher = ShapeHasher::new();
+        hasher.num_inputs = reader.read_u64::<BigEndian>()? as usize;
+        hasher.num_aux = reader.read_u64::<BigEndian>()? as usize;
+        hasher.num_constraints = reader.read_u64::<BigEndian>()? as usize;
+        for value in &mut [
+            &mut hasher.constraint_power,
+            &mut hasher.one,
+            &mut hasher.inputs,
+            &mut hasher.aux,
+        ] {
+            let mut repr = <E::Fr as PrimeField>::Repr::default();
+            repr.read_be(&mut reader)?;
+            **value = E::Fr::from_repr(repr)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        }
+
+        Ok(hasher)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Variable;
+    use paired::bls12_381::{Bls12, Fr};
+
+    fn var(index: Index) -> Variable {
+        Variable::new_unchecked(index)
+    }
+
+    fn lc(terms: &[(Index, u64)]) -> LinearCombination<Bls12> {
+        terms.iter().fold(LinearCombination::zero(), |lc, &(i, c)| {
+            lc + (Fr::from_str(&c.to_string()).unwrap(), var(i))
+        })
+    }
+
+    #[test]
+    fn test_powers() {
+        let base = Fr::from_str("3").unwrap();
+        let mut powers = Powers::<Bls12>::new(base);
+        for &i in &[0usize, 1, 1023, 1024, 5000, 3000] {
+            assert_eq!(powers.get(i), base.pow(&[i as u64]));
+        }
+    }
+
+    #[test]
+    fn test_digest() {
+        let one = Index::Input(0);
+        let x = Index::Input(1);
+        let y = Index::Aux(0);
+
+        let digest = |constraints: &[(&[(Index, u64)], &[(Index, u64)], &[(Index, u64)])]| {
+            let mut hasher = ShapeHasher::<Bls12>::new();
+            hasher.alloc_input();
+            hasher.alloc_input();
+            hasher.alloc();
+            for (a, b, c) in constraints {
+                hasher.enforce(&lc(a), &lc(b), &lc(c));
+            }
+            hasher.finish()
+        };
+
+        let base = digest(&[(&[(y, 1)], &[(y, 1)], &[(x, 1)])]);
+        assert_eq!(base, digest(&[(&[(y, 1)], &[(y, 1)], &[(x, 1)])]));
+        // Zero terms do not change the matrices.
+        assert_eq!(base, digest(&[(&[(y, 1), (one, 0)], &[(y, 1)], &[(x, 1)])]));
+
+        assert_ne!(base, digest(&[(&[(y, 2)], &[(y, 1)], &[(x, 1)])]));
+        assert_ne!(base, digest(&[(&[(y, 1)], &[(x, 1)], &[(y, 1)])]));
+        assert_ne!(base, digest(&[(&[(y, 1)], &[(one, 1)], &[(x, 1)])]));
+        assert_ne!(
+            base,
+            digest(&[(&[(y, 1)], &[(y, 1)], &[(x, 1)]), (&[], &[], &[])])
+        );
+    }
+
+    #[test]
+    fn test_extend() {
+        // Two copies of x * x = y, with x an input, once sequentially and
+        // once through extend.
+        let square = |hasher: &mut ShapeHasher<Bls12>, input: usize, aux: usize| {
+            hasher.alloc_input();
+            hasher.alloc();
+            hasher.enforce(
+                &lc(&[(Index::Input(input), 1)]),
+                &lc(&[(Index::Input(input), 1)]),
+                &lc(&[(Index::Aux(aux), 1), (Index::Input(0), 1)]),
+            );
+        };
+
+        let mut sequential = ShapeHasher::<Bls12>::new();
+        sequential.alloc_input();
+        square(&mut sequential, 1, 0);
+        square(&mut sequential, 2, 1);
+
+        let mut extended = ShapeHasher::<Bls12>::new();
+        extended.alloc_input();
+        square(&mut extended, 1, 0);
+        let mut child = ShapeHasher::<Bls12>::new();
+        child.alloc_input();
+        square(&mut child, 1, 0);
+        extended.extend(&child);
+
+        assert_eq!(extended.num_inputs(), 3);
+        assert_eq!(extended.num_aux(), 2);
+        assert_eq!(extended.num_constraints(), 2);
+        assert_eq!(extended.finish(), sequential.finish());
+
+        let mut buf = vec![];
+        extended.write(&mut buf).unwrap();
+        let mut resumed = ShapeHasher::<Bls12>::read(&buf[..]).unwrap();
+        assert_eq!(resumed.finish(), sequential.finish());
+        square(&mut resumed, 3, 2);
+        square(&mut sequential, 3, 2);
+        assert_eq!(resumed.finish(), sequential.finish());
+    }
+}
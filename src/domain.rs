@@ -11,17 +11,66 @@
 //! [`EvaluationDomain`]: crate::domain::EvaluationDomain
 //! [Groth16]: https://eprint.iacr.org/2016/260
 
-use ff::{Field, PrimeField, ScalarEngine};
+use ff::{Field, PrimeField, PrimeFieldRepr, ScalarEngine};
 use groupy::CurveProjective;
-use paired::Engine;
 
-use super::multicore::Worker;
+use super::multicore::{CancellationToken, Worker};
 use super::SynthesisError;
 
 use crate::gpu;
 
 use log::{info, warn};
 
+/// The number of elements after which a sub-FFT of `parallel_fft` checks
+/// whether it was cancelled while shuffling them.
+const CANCELLATION_CHECK_INTERVAL: usize = 1 << 10;
+
+/// The roots of unity which the constraints of a QAP are assigned to. In a
+/// domain of size `2^k`, the `i`th constraint is assigned to `w^i`, where `w`
+/// is the `2^k`th root of unity derived from `root_of_unity`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QapDomain {
+    /// The roots of unity derived from `PrimeField::root_of_unity`, which
+    /// parameters generated by bellperson use.
+    Bellperson,
+    /// The roots of unity derived from the smallest quadratic non-residue of
+    /// the field, which parameters generated by snarkjs use.
+    Snarkjs,
+}
+
+impl QapDomain {
+    /// The primitive `2^S`th root of unity the roots of the domain are
+    /// derived from.
+    pub fn root_of_unity<F: PrimeField>(self) -> F {
+        match self {
+            QapDomain::Bellperson => F::root_of_unity(),
+            QapDomain::Snarkjs => {
+                // The odd part t of the modulus minus one.
+                let mut t = F::char();
+                t.sub_noborrow(&F::Repr::from(1));
+                t.shr(F::S);
+
+                let mut minus_one = F::one();
+                minus_one.negate();
+
+                // n is a non-residue iff n^((r - 1) / 2) = (n^t)^(2^(S - 1)) = -1.
+                let mut n = F::one();
+                loop {
+                    n.add_assign(&F::one());
+                    let root = n.pow(t);
+                    let mut x = root;
+                    for _ in 1..F::S {
+                        x.square();
+                    }
+                    if x == minus_one {
+                        return root;
+                    }
+                }
+            }
+        }
+    }
+}
+
 pub struct EvaluationDomain<E: ScalarEngine, G: Group<E>> {
     coeffs: Vec<G>,
     exp: u32,
@@ -43,12 +92,20 @@ impl<E: ScalarEngine, G: Group<E>> AsMut<[G]> for EvaluationDomain<E, G> {
     }
 }
 
-impl<E: Engine, G: Group<E>> EvaluationDomain<E, G> {
+impl<E: ScalarEngine, G: Group<E>> EvaluationDomain<E, G> {
     pub fn into_coeffs(self) -> Vec<G> {
         self.coeffs
     }
 
-    pub fn from_coeffs(mut coeffs: Vec<G>) -> Result<EvaluationDomain<E, G>, SynthesisError> {
+    pub fn from_coeffs(coeffs: Vec<G>) -> Result<EvaluationDomain<E, G>, SynthesisError> {
+        Self::from_coeffs_in(coeffs, QapDomain::Bellperson)
+    }
+
+    /// Like `from_coeffs`, but with the roots of unity of `domain`.
+    pub fn from_coeffs_in(
+        mut coeffs: Vec<G>,
+        domain: QapDomain,
+    ) -> Result<EvaluationDomain<E, G>, SynthesisError> {
         // Compute the size of our evaluation domain
         let mut m = 1;
         let mut exp = 0;
@@ -63,7 +120,7 @@ impl<E: Engine, G: Group<E>> EvaluationDomain<E, G> {
             }
         }
         // Compute omega, the 2^exp primitive root of unity
-        let mut omega = E::Fr::root_of_unity();
+        let mut omega = domain.root_of_unity::<E::Fr>();
         for _ in exp..E::Fr::S {
             omega.square();
         }
@@ -88,7 +145,7 @@ impl<E: Engine, G: Group<E>> EvaluationDomain<E, G> {
         &mut self,
         worker: &Worker,
         kern: &mut Option<gpu::LockedFFTKernel<E>>,
-    ) -> gpu::GPUResult<()> {
+    ) -> Result<(), SynthesisError> {
         best_fft(kern, &mut self.coeffs, worker, &self.omega, self.exp)?;
         Ok(())
     }
@@ -97,7 +154,7 @@ impl<E: Engine, G: Group<E>> EvaluationDomain<E, G> {
         &mut self,
         worker: &Worker,
         kern: &mut Option<gpu::LockedFFTKernel<E>>,
-    ) -> gpu::GPUResult<()> {
+    ) -> Result<(), SynthesisError> {
         best_fft(kern, &mut self.coeffs, worker, &self.omegainv, self.exp)?;
 
         worker.scope(self.coeffs.len(), |scope, chunk| {
@@ -133,7 +190,7 @@ impl<E: Engine, G: Group<E>> EvaluationDomain<E, G> {
         &mut self,
         worker: &Worker,
         kern: &mut Option<gpu::LockedFFTKernel<E>>,
-    ) -> gpu::GPUResult<()> {
+    ) -> Result<(), SynthesisError> {
         self.distribute_powers(worker, E::Fr::multiplicative_generator());
         self.fft(worker, kern)?;
         Ok(())
@@ -143,7 +200,7 @@ impl<E: Engine, G: Group<E>> EvaluationDomain<E, G> {
         &mut self,
         worker: &Worker,
         kern: &mut Option<gpu::LockedFFTKernel<E>>,
-    ) -> gpu::GPUResult<()> {
+    ) -> Result<(), SynthesisError> {
         let geninv = self.geninv;
         self.ifft(worker, kern)?;
         self.distribute_powers(worker, geninv);
@@ -287,16 +344,20 @@ impl<E: ScalarEngine> Group<E> for Scalar<E> {
     }
 }
 
-fn best_fft<E: Engine, T: Group<E>>(
+/// Fails with `SynthesisError::Cancelled`, leaving `a` unspecified, if the
+/// worker's cancellation token is cancelled before or while it runs.
+fn best_fft<E: ScalarEngine, T: Group<E>>(
     kern: &mut Option<gpu::LockedFFTKernel<E>>,
     a: &mut [T],
     worker: &Worker,
     omega: &E::Fr,
     log_n: u32,
-) -> gpu::GPUResult<()> {
+) -> Result<(), SynthesisError> {
+    worker.cancellation().check()?;
+
     if let Some(ref mut kern) = kern {
         if kern
-            .with(|k: &mut gpu::FFTKernel<E>| gpu_fft(k, a, omega, log_n))
+            .with(|k: &mut dyn gpu::FftKernelOps<E>| gpu_fft(k, a, omega, log_n))
             .is_ok()
         {
             return Ok(());
@@ -305,16 +366,16 @@ fn best_fft<E: Engine, T: Group<E>>(
 
     let log_cpus = worker.log_num_cpus();
     if log_n <= log_cpus {
-        serial_fft(a, omega, log_n);
+        cancellable_serial_fft(a, omega, log_n, worker.cancellation());
     } else {
         parallel_fft(a, worker, omega, log_n, log_cpus);
     }
 
-    Ok(())
+    worker.cancellation().check()
 }
 
-pub fn gpu_fft<E: Engine, T: Group<E>>(
-    kern: &mut gpu::FFTKernel<E>,
+pub fn gpu_fft<E: ScalarEngine, T: Group<E>>(
+    kern: &mut dyn gpu::FftKernelOps<E>,
     a: &mut [T],
     omega: &E::Fr,
     log_n: u32,
@@ -332,6 +393,17 @@ pub fn gpu_fft<E: Engine, T: Group<E>>(
 }
 
 pub fn serial_fft<E: ScalarEngine, T: Group<E>>(a: &mut [T], omega: &E::Fr, log_n: u32) {
+    cancellable_serial_fft(a, omega, log_n, &CancellationToken::new());
+}
+
+/// Like `serial_fft`, but stops between rounds once `cancel` is cancelled,
+/// leaving `a` unspecified.
+fn cancellable_serial_fft<E: ScalarEngine, T: Group<E>>(
+    a: &mut [T],
+    omega: &E::Fr,
+    log_n: u32,
+    cancel: &CancellationToken,
+) {
     fn bitreverse(mut n: u32, l: u32) -> u32 {
         let mut r = 0;
         for _ in 0..l {
@@ -353,6 +425,10 @@ pub fn serial_fft<E: ScalarEngine, T: Group<E>>(a: &mut [T], omega: &E::Fr, log_
 
     let mut m = 1;
     for _ in 0..log_n {
+        if cancel.is_cancelled() {
+            return;
+        }
+
         let w_m = omega.pow(&[u64::from(n / (2 * m))]);
 
         let mut k = 0;
@@ -391,6 +467,7 @@ fn parallel_fft<E: ScalarEngine, T: Group<E>>(
 
     worker.scope(0, |scope, _| {
         let a = &*a;
+        let cancel = worker.cancellation();
 
         for (j, tmp) in tmp.iter_mut().enumerate() {
             scope.spawn(move |_scope| {
@@ -400,6 +477,10 @@ fn parallel_fft<E: ScalarEngine, T: Group<E>>(
 
                 let mut elt = E::Fr::one();
                 for (i, tmp) in tmp.iter_mut().enumerate() {
+                    if i % CANCELLATION_CHECK_INTERVAL == 0 && cancel.is_cancelled() {
+                        return;
+                    }
+
                     for s in 0..num_cpus {
                         let idx = (i + (s << log_new_n)) % (1 << log_n);
                         let mut t = a[idx];
@@ -411,7 +492,7 @@ fn parallel_fft<E: ScalarEngine, T: Group<E>>(
                 }
 
                 // Perform sub-FFT
-                serial_fft(tmp, &new_omega, log_new_n);
+                cancellable_serial_fft(tmp, &new_omega, log_new_n, cancel);
             });
         }
     });
@@ -559,9 +640,10 @@ fn parallel_fft_consistency() {
     test_consistency::<Bls12, _>(rng);
 }
 
+#[cfg(feature = "paired")]
 pub fn create_fft_kernel<E>(_log_d: usize, priority: bool) -> Option<gpu::FFTKernel<E>>
 where
-    E: Engine,
+    E: paired::Engine,
 {
     match gpu::FFTKernel::create(priority) {
         Ok(k) => {
@@ -630,3 +712,33 @@ mod tests {
         }
     }
 }
+
+#[cfg(feature = "groth16")]
+#[test]
+fn qap_domain_roots_of_unity() {
+    use paired::bls12_381::Fr;
+
+    assert_eq!(
+        QapDomain::Bellperson.root_of_unity::<Fr>(),
+        Fr::root_of_unity()
+    );
+
+    // snarkjs derives its roots of unity from 5^t.
+    let root = QapDomain::Snarkjs.root_of_unity::<Fr>();
+    assert_eq!(
+        root,
+        Fr::from_str("937917089079007706106976984802249742464848817460758522850752807661925904159")
+            .unwrap()
+    );
+
+    // Both are primitive 2^S th roots of unity.
+    for &root in &[Fr::root_of_unity(), root] {
+        let mut x = root;
+        for _ in 1..Fr::S {
+            x.square();
+        }
+        assert_ne!(x, Fr::one());
+        x.square();
+        assert_eq!(x, Fr::one());
+    }
+}
@@ -1,7 +1,9 @@
 use fs2::FileExt;
 use log::{debug, info, warn};
+use std::any::Any;
 use std::fs::File;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 const GPU_LOCK_NAME: &str = "bellman.gpu.lock";
 const PRIORITY_LOCK_NAME: &str = "bellman.priority.lock";
@@ -68,38 +70,70 @@ impl Drop for PriorityLock {
 use super::error::{GPUError, GPUResult};
 use super::fft::FFTKernel;
 use super::multiexp::MultiexpKernel;
+use super::{FftKernelOps, MultiexpKernelOps};
 use crate::domain::create_fft_kernel;
+use crate::multicore::Worker;
 use crate::multiexp::create_multiexp_kernel;
+use ff::{PrimeField, ScalarEngine};
 use paired::Engine;
 
+/// Builds the FFT kernel behind `LockedFFTKernel`.
+fn create_fft<E: Engine>(log_d: usize, priority: bool) -> Option<Box<dyn FftKernelOps<E>>> {
+    create_fft_kernel::<E>(log_d, priority).map(|k| Box::new(k) as Box<dyn FftKernelOps<E>>)
+}
+
+/// Builds the multiexp kernel behind `LockedMultiexpKernel`.
+fn create_multiexp<E: Engine>(
+    log_d: usize,
+    priority: bool,
+) -> Option<Box<dyn MultiexpKernelOps<E>>> {
+    create_multiexp_kernel::<E>(log_d, priority)
+        .map(|k| Box::new(k) as Box<dyn MultiexpKernelOps<E>>)
+}
+
 macro_rules! locked_kernel {
-    ($class:ident, $kern:ident, $func:ident, $name:expr) => {
+    ($class:ident, $ops:ident, $func:ident, $name:expr) => {
+        /// A kernel which is only built once it is first used, and freed
+        /// while a process with priority needs the GPU. It can be held for
+        /// any `ScalarEngine`, but only built for a `paired::Engine`.
         pub struct $class<E>
         where
-            E: Engine,
+            E: ScalarEngine,
         {
             log_d: usize,
             priority: bool,
-            kernel: Option<$kern<E>>,
+            create: fn(usize, bool) -> Option<Box<dyn $ops<E>>>,
+            kernel: Option<Box<dyn $ops<E>>>,
+            runs: usize,
         }
 
         impl<E> $class<E>
         where
-            E: Engine,
+            E: ScalarEngine,
         {
-            pub fn new(log_d: usize, priority: bool) -> $class<E> {
+            pub fn new(log_d: usize, priority: bool) -> $class<E>
+            where
+                E: Engine,
+            {
                 $class::<E> {
                     log_d,
                     priority,
+                    create: $func::<E>,
                     kernel: None,
+                    runs: 0,
                 }
             }
 
+            /// The number of computations which have run on the GPU.
+            pub fn runs(&self) -> usize {
+                self.runs
+            }
+
             fn init(&mut self) {
                 if self.kernel.is_none() {
                     PriorityLock::wait(self.priority);
                     info!("GPU is available for {}!", $name);
-                    self.kernel = $func::<E>(self.log_d, self.priority);
+                    self.kernel = (self.create)(self.log_d, self.priority);
                 }
             }
 
@@ -114,7 +148,7 @@ macro_rules! locked_kernel {
 
             pub fn with<F, R>(&mut self, mut f: F) -> GPUResult<R>
             where
-                F: FnMut(&mut $kern<E>) -> GPUResult<R>,
+                F: FnMut(&mut dyn $ops<E>) -> GPUResult<R>,
             {
                 if std::env::var("BELLMAN_NO_GPU").is_ok() {
                     return Err(GPUError::GPUDisabled);
@@ -124,7 +158,7 @@ macro_rules! locked_kernel {
 
                 loop {
                     if let Some(ref mut k) = self.kernel {
-                        match f(k) {
+                        match f(&mut **k) {
                             Err(GPUError::GPUTaken) => {
                                 self.free();
                                 self.init();
@@ -133,7 +167,10 @@ macro_rules! locked_kernel {
                                 warn!("GPU {} failed! Falling back to CPU... Error: {}", $name, e);
                                 return Err(e);
                             }
-                            Ok(v) => return Ok(v),
+                            Ok(v) => {
+                                self.runs += 1;
+                                return Ok(v);
+                            }
                         }
                     } else {
                         return Err(GPUError::KernelUninitialized);
@@ -144,10 +181,39 @@ macro_rules! locked_kernel {
     };
 }
 
-locked_kernel!(LockedFFTKernel, FFTKernel, create_fft_kernel, "FFT");
+locked_kernel!(LockedFFTKernel, FftKernelOps, create_fft, "FFT");
 locked_kernel!(
     LockedMultiexpKernel,
-    MultiexpKernel,
-    create_multiexp_kernel,
+    MultiexpKernelOps,
+    create_multiexp,
     "Multiexp"
 );
+
+impl<E: Engine> FftKernelOps<E> for FFTKernel<E> {
+    fn radix_fft(&mut self, a: &mut [E::Fr], omega: &E::Fr, log_n: u32) -> GPUResult<()> {
+        FFTKernel::radix_fft(self, a, omega, log_n)
+    }
+}
+
+impl<E: Engine> MultiexpKernelOps<E> for MultiexpKernel<E> {
+    fn multiexp(
+        &mut self,
+        pool: &Worker,
+        bases: &dyn Any,
+        exps: Arc<Vec<<E::Fr as PrimeField>::Repr>>,
+        skip: usize,
+        n: usize,
+    ) -> GPUResult<Box<dyn Any>> {
+        if let Some(bases) = bases.downcast_ref::<Arc<Vec<E::G1Affine>>>() {
+            let result =
+                MultiexpKernel::multiexp::<E::G1Affine>(self, pool, bases.clone(), exps, skip, n)?;
+            Ok(Box::new(result))
+        } else if let Some(bases) = bases.downcast_ref::<Arc<Vec<E::G2Affine>>>() {
+            let result =
+                MultiexpKernel::multiexp::<E::G2Affine>(self, pool, bases.clone(), exps, skip, n)?;
+            Ok(Box::new(result))
+        } else {
+            Err(GPUError::Simple("Only E::G1 and E::G2 are supported!"))
+        }
+    }
+}
@@ -2,6 +2,52 @@ mod error;
 
 pub use self::error::*;
 
+use std::any::Any;
+use std::sync::Arc;
+
+use ff::{PrimeField, ScalarEngine};
+use groupy::CurveAffine;
+
+use crate::multicore::Worker;
+
+/// The FFT of a GPU kernel, over the scalar field of `E`. The CPU paths which
+/// may hand their FFTs to a kernel only require a `ScalarEngine` through it,
+/// while the kernels themselves are only built for a `paired::Engine`, with
+/// the `gpu` feature.
+pub trait FftKernelOps<E: ScalarEngine> {
+    fn radix_fft(&mut self, a: &mut [E::Fr], omega: &E::Fr, log_n: u32) -> GPUResult<()>;
+}
+
+/// The multiexp of a GPU kernel, see `FftKernelOps`. The bases are an
+/// `Arc<Vec<G>>` and the result a `G::Projective`, for one of the groups of
+/// the engine; use `kernel_multiexp` to call it.
+pub trait MultiexpKernelOps<E: ScalarEngine> {
+    fn multiexp(
+        &mut self,
+        pool: &Worker,
+        bases: &dyn Any,
+        exps: Arc<Vec<<E::Fr as PrimeField>::Repr>>,
+        skip: usize,
+        n: usize,
+    ) -> GPUResult<Box<dyn Any>>;
+}
+
+/// Computes the multiexp of `n` of the `bases` from the `skip`th one on with
+/// `kern`, which fails for the groups the kernel is not built for.
+pub fn kernel_multiexp<G: CurveAffine>(
+    kern: &mut dyn MultiexpKernelOps<G::Engine>,
+    pool: &Worker,
+    bases: Arc<Vec<G>>,
+    exps: Arc<Vec<<<G::Engine as ScalarEngine>::Fr as PrimeField>::Repr>>,
+    skip: usize,
+    n: usize,
+) -> GPUResult<G::Projective> {
+    kern.multiexp(pool, &bases, exps, skip, n)?
+        .downcast::<G::Projective>()
+        .map(|result| *result)
+        .map_err(|_| GPUError::Simple("Only E::G1 and E::G2 are supported!"))
+}
+
 #[cfg(feature = "gpu")]
 mod locks;
 
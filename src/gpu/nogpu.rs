@@ -1,4 +1,5 @@
 use super::error::{GPUError, GPUResult};
+use super::{FftKernelOps, MultiexpKernelOps};
 use crate::multicore::Worker;
 use ff::{PrimeField, ScalarEngine};
 use groupy::CurveAffine;
@@ -51,23 +52,29 @@ where
     }
 }
 
-use paired::Engine;
-
 macro_rules! locked_kernel {
-    ($class:ident) => {
+    ($class:ident, $ops:ident) => {
         pub struct $class<E>(PhantomData<E>);
 
         impl<E> $class<E>
         where
-            E: Engine,
+            E: ScalarEngine,
         {
-            pub fn new(_: usize, _: bool) -> $class<E> {
+            #[cfg(feature = "paired")]
+            pub fn new(_: usize, _: bool) -> $class<E>
+            where
+                E: paired::Engine,
+            {
                 $class::<E>(PhantomData)
             }
 
-            pub fn with<F, R, K>(&mut self, _: F) -> GPUResult<R>
+            pub fn runs(&self) -> usize {
+                0
+            }
+
+            pub fn with<F, R>(&mut self, _: F) -> GPUResult<R>
             where
-                F: FnMut(&mut K) -> GPUResult<R>,
+                F: FnMut(&mut dyn $ops<E>) -> GPUResult<R>,
             {
                 return Err(GPUError::GPUDisabled);
             }
@@ -75,5 +82,5 @@ macro_rules! locked_kernel {
     };
 }
 
-locked_kernel!(LockedFFTKernel);
-locked_kernel!(LockedMultiexpKernel);
+locked_kernel!(LockedFFTKernel, FftKernelOps);
+locked_kernel!(LockedMultiexpKernel, MultiexpKernelOps);
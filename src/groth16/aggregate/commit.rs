@@ -0,0 +1,96 @@
+use std::sync::Arc;
+
+use ff::{Field, PrimeField};
+use groupy::CurveAffine;
+use paired::{Engine, PairingCurveAffine};
+use rayon::prelude::*;
+
+use super::srs::CommitmentKey;
+use crate::multicore::Worker;
+use crate::multiexp::{multiexp, FullDensity};
+
+/// A commitment in the target group to one or two vectors, with one element
+/// per key of a `CommitmentKey`.
+#[derive(Clone, Debug)]
+pub struct PairCommitment<E: Engine> {
+    pub t: E::Fqk,
+    pub u: E::Fqk,
+}
+
+impl<E: Engine> PartialEq for PairCommitment<E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.t == other.t && self.u == other.u
+    }
+}
+
+impl<E: Engine> PairCommitment<E> {
+    /// Multiplies this commitment by `other^x`, as when folding the
+    /// committed vectors.
+    pub(super) fn mul_pow(&mut self, other: &Self, x: &E::Fr) {
+        let x = x.into_repr();
+        self.t.mul_assign(&other.t.pow(&x));
+        self.u.mul_assign(&other.u.pow(&x));
+    }
+}
+
+/// Computes `prod_i e(left_i, right_i)`.
+pub(super) fn pairing_product<E: Engine>(left: &[E::G1Affine], right: &[E::G2Affine]) -> E::Fqk {
+    assert_eq!(left.len(), right.len());
+
+    let ml = left
+        .par_iter()
+        .zip(right.par_iter())
+        .map(|(l, r)| {
+            let (l, r) = (l.prepare(), r.prepare());
+            E::miller_loop(&[(&l, &r)])
+        })
+        .reduce(E::Fqk::one, |mut acc, ml| {
+            acc.mul_assign(&ml);
+            acc
+        });
+
+    E::final_exponentiation(&ml).expect("miller loop result is never zero")
+}
+
+/// Commits to `a` under `vkey` and to `b` under `wkey` at once.
+pub(super) fn commit_ab<E: Engine>(
+    vkey: &CommitmentKey<E::G2Affine>,
+    wkey: &CommitmentKey<E::G1Affine>,
+    a: &[E::G1Affine],
+    b: &[E::G2Affine],
+) -> PairCommitment<E> {
+    PairCommitment {
+        t: pairing_product::<E>(&[a, &wkey.a].concat(), &[&vkey.a, b].concat()),
+        u: pairing_product::<E>(&[a, &wkey.b].concat(), &[&vkey.b, b].concat()),
+    }
+}
+
+/// Commits to `c` under `vkey`.
+pub(super) fn commit_c<E: Engine>(
+    vkey: &CommitmentKey<E::G2Affine>,
+    c: &[E::G1Affine],
+) -> PairCommitment<E> {
+    PairCommitment {
+        t: pairing_product::<E>(c, &vkey.a),
+        u: pairing_product::<E>(c, &vkey.b),
+    }
+}
+
+/// Computes `sum_i scalars_i * bases_i` on the CPU.
+pub(super) fn multiexponentiation<G>(bases: &[G], scalars: &[G::Scalar]) -> G::Projective
+where
+    G: CurveAffine,
+{
+    assert_eq!(bases.len(), scalars.len());
+
+    let exps = scalars.iter().map(|s| s.into_repr()).collect::<Vec<_>>();
+    multiexp(
+        &Worker::new(),
+        (Arc::new(bases.to_vec()), 0),
+        FullDensity,
+        Arc::new(exps),
+        &mut None,
+    )
+    .wait()
+    .expect("multiexp without a GPU kernel does not fail")
+}
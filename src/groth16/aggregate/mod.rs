@@ -0,0 +1,419 @@
+//! Aggregation of Groth16 proofs, following SnarkPack.
+//!
+//! `aggregate_proofs` turns `n` proofs for the same verifying key into a
+//! single `AggregateProof` whose size, like the cost of
+//! `verify_aggregate_proof`, is logarithmic in `n`.
+//!
+//! The aggregator commits to the `A`, `B` and `C` elements of the proofs with
+//! pairing-based commitments, and derives a random `r` from them. The Groth16
+//! verification equations combined with the powers of `r` only depend on
+//! `prod_i e(A_i, B_i)^(r^i)` and on `agg_c = sum_i r^i * C_i`, which is part
+//! of the aggregate proof. A generalized inner product argument (GIPA) then
+//! shows these values are consistent with the commitments, by halving the
+//! committed vectors in each round. Finally, KZG openings show that the
+//! commitment keys the GIPA ends up with were derived from the structured
+//! reference string.
+//!
+//! The verifying key and the SRS are bound to the challenges, along with
+//! the public inputs and every message of the prover.
+//!
+//! The number of proofs must be exactly the size the SRS was specialized
+//! for, which is a power of two, and aggregation fails otherwise. Proofs can
+//! be padded with copies of the last proof and its public inputs.
+
+use std::io::{self, Read, Write};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use ff::{Field, PrimeField, PrimeFieldRepr};
+use groupy::{CurveAffine, EncodedPoint};
+use paired::bls12_381::{Bls12, Fq, Fq12, Fq2, Fq6, FqRepr};
+use paired::Engine;
+
+mod commit;
+mod prover;
+mod srs;
+mod transcript;
+mod verifier;
+
+pub use self::commit::PairCommitment;
+pub use self::prover::*;
+pub use self::srs::{setup_fake_srs, GenericSRS, ProverSRS, VerifierSRS};
+pub use self::verifier::*;
+
+/// Engines whose target group has a canonical byte encoding, on which the
+/// serialization of aggregate proofs and their transcripts rely.
+pub trait GtEncoding: Engine {
+    fn write_gt<W: Write>(element: &Self::Fqk, writer: W) -> io::Result<()>;
+
+    /// Reads an element of the field the target group lives in, without
+    /// checking it is in the target group.
+    fn read_fqk<R: Read>(reader: R) -> io::Result<Self::Fqk>;
+}
+
+impl GtEncoding for Bls12 {
+    fn write_gt<W: Write>(element: &Fq12, mut writer: W) -> io::Result<()> {
+        for fq6 in &[element.c0, element.c1] {
+            for fq2 in &[fq6.c0, fq6.c1, fq6.c2] {
+                fq2.c0.into_repr().write_be(&mut writer)?;
+                fq2.c1.into_repr().write_be(&mut writer)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_fqk<R: Read>(mut reader: R) -> io::Result<Fq12> {
+        Ok(Fq12 {
+            c0: read_fq6(&mut reader)?,
+            c1: read_fq6(&mut reader)?,
+        })
+    }
+}
+
+/// The messages of the prover in one round of the GIPA, for the halves `L`
+/// and `R` of the vectors and keys.
+#[derive(Clone, Debug)]
+pub struct GipaRound<E: Engine> {
+    /// Commitment to `A_R` under `vkey_L` and to `B_L` under `wkey_R`.
+    pub com_ab_l: PairCommitment<E>,
+    /// Commitment to `A_L` under `vkey_R` and to `B_R` under `wkey_L`.
+    pub com_ab_r: PairCommitment<E>,
+    pub z_ab_l: E::Fqk,
+    pub z_ab_r: E::Fqk,
+    /// Commitment to `C_R` under `vkey_L`.
+    pub com_c_l: PairCommitment<E>,
+    /// Commitment to `C_L` under `vkey_R`.
+    pub com_c_r: PairCommitment<E>,
+    pub z_c_l: E::G1Affine,
+    pub z_c_r: E::G1Affine,
+}
+
+impl<E: Engine> PartialEq for GipaRound<E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.com_ab_l == other.com_ab_l
+            && self.com_ab_r == other.com_ab_r
+            && self.z_ab_l == other.z_ab_l
+            && self.z_ab_r == other.z_ab_r
+            && self.com_c_l == other.com_c_l
+            && self.com_c_r == other.com_c_r
+            && self.z_c_l == other.z_c_l
+            && self.z_c_r == other.z_c_r
+    }
+}
+
+/// A proof that committed vectors `A`, `B` and `C` have the claimed
+/// `prod_i e(A_i, B_i)` and `sum_i C_i`.
+#[derive(Clone, Debug)]
+pub struct GipaProof<E: Engine> {
+    pub rounds: Vec<GipaRound<E>>,
+    pub final_a: E::G1Affine,
+    pub final_b: E::G2Affine,
+    pub final_c: E::G1Affine,
+    pub final_vkey: (E::G2Affine, E::G2Affine),
+    pub final_wkey: (E::G1Affine, E::G1Affine),
+}
+
+impl<E: Engine> PartialEq for GipaProof<E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.rounds == other.rounds
+            && self.final_a == other.final_a
+            && self.final_b == other.final_b
+            && self.final_c == other.final_c
+            && self.final_vkey == other.final_vkey
+            && self.final_wkey == other.final_wkey
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AggregateProof<E: Engine> {
+    pub com_ab: PairCommitment<E>,
+    pub com_c: PairCommitment<E>,
+    pub agg_c: E::G1Affine,
+    pub gipa: GipaProof<E>,
+    pub vkey_opening: (E::G1Affine, E::G1Affine),
+    pub wkey_opening: (E::G2Affine, E::G2Affine),
+}
+
+impl<E: Engine> PartialEq for AggregateProof<E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.com_ab == other.com_ab
+            && self.com_c == other.com_c
+            && self.agg_c == other.agg_c
+            && self.gipa == other.gipa
+            && self.vkey_opening == other.vkey_opening
+            && self.wkey_opening == other.wkey_opening
+    }
+}
+
+impl<E: GtEncoding> AggregateProof<E> {
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write_commitment(&self.com_ab, &mut writer)?;
+        write_commitment(&self.com_c, &mut writer)?;
+        write_point(&self.agg_c, &mut writer)?;
+        write_point(&self.vkey_opening.0, &mut writer)?;
+        write_point(&self.vkey_opening.1, &mut writer)?;
+        write_point(&self.wkey_opening.0, &mut writer)?;
+        write_point(&self.wkey_opening.1, &mut writer)?;
+
+        let gipa = &self.gipa;
+        writer.write_u32::<BigEndian>(gipa.rounds.len() as u32)?;
+        for round in &gipa.rounds {
+            write_commitment(&round.com_ab_l, &mut writer)?;
+            write_commitment(&round.com_ab_r, &mut writer)?;
+            E::write_gt(&round.z_ab_l, &mut writer)?;
+            E::write_gt(&round.z_ab_r, &mut writer)?;
+            write_commitment(&round.com_c_l, &mut writer)?;
+            write_commitment(&round.com_c_r, &mut writer)?;
+            write_point(&round.z_c_l, &mut writer)?;
+            write_point(&round.z_c_r, &mut writer)?;
+        }
+        write_point(&gipa.final_a, &mut writer)?;
+        write_point(&gipa.final_b, &mut writer)?;
+        write_point(&gipa.final_c, &mut writer)?;
+        write_point(&gipa.final_vkey.0, &mut writer)?;
+        write_point(&gipa.final_vkey.1, &mut writer)?;
+        write_point(&gipa.final_wkey.0, &mut writer)?;
+        write_point(&gipa.final_wkey.1, &mut writer)?;
+
+        Ok(())
+    }
+
+    /// Reads an aggregate proof, checking that its elements of the target
+    /// group are in the subgroup of order `r`.
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let com_ab = read_commitment(&mut reader)?;
+        let com_c = read_commitment(&mut reader)?;
+        let agg_c = read_point(&mut reader)?;
+        let vkey_opening = (read_point(&mut reader)?, read_point(&mut reader)?);
+        let wkey_opening = (read_point(&mut reader)?, read_point(&mut reader)?);
+
+        let num_rounds = reader.read_u32::<BigEndian>()?;
+        // A round halves the number of proofs, which fits in a usize.
+        if num_rounds as usize >= std::mem::size_of::<usize>() * 8 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "too many rounds",
+            ));
+        }
+        let mut rounds = vec![];
+        for _ in 0..num_rounds {
+            rounds.push(GipaRound {
+                com_ab_l: read_commitment(&mut reader)?,
+                com_ab_r: read_commitment(&mut reader)?,
+                z_ab_l: read_gt::<E, _>(&mut reader)?,
+                z_ab_r: read_gt::<E, _>(&mut reader)?,
+                com_c_l: read_commitment(&mut reader)?,
+                com_c_r: read_commitment(&mut reader)?,
+                z_c_l: read_point(&mut reader)?,
+                z_c_r: read_point(&mut reader)?,
+            });
+        }
+        let gipa = GipaProof {
+            rounds,
+            final_a: read_point(&mut reader)?,
+            final_b: read_point(&mut reader)?,
+            final_c: read_point(&mut reader)?,
+            final_vkey: (read_point(&mut reader)?, read_point(&mut reader)?),
+            final_wkey: (read_point(&mut reader)?, read_point(&mut reader)?),
+        };
+
+        Ok(AggregateProof {
+            com_ab,
+            com_c,
+            agg_c,
+            gipa,
+            vkey_opening,
+            wkey_opening,
+        })
+    }
+}
+
+fn write_point<G: CurveAffine, W: Write>(point: &G, mut writer: W) -> io::Result<()> {
+    writer.write_all(point.into_compressed().as_ref())
+}
+
+fn read_point<G: CurveAffine, R: Read>(mut reader: R) -> io::Result<G> {
+    let mut repr = G::Compressed::empty();
+    reader.read_exact(repr.as_mut())?;
+    repr.into_affine()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn read_fq<R: Read>(mut reader: R) -> io::Result<Fq> {
+    let mut repr = FqRepr::default();
+    repr.read_be(&mut reader)?;
+    Fq::from_repr(repr).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn read_fq2<R: Read>(mut reader: R) -> io::Result<Fq2> {
+    Ok(Fq2 {
+        c0: read_fq(&mut reader)?,
+        c1: read_fq(&mut reader)?,
+    })
+}
+
+fn read_fq6<R: Read>(mut reader: R) -> io::Result<Fq6> {
+    Ok(Fq6 {
+        c0: read_fq2(&mut reader)?,
+        c1: read_fq2(&mut reader)?,
+        c2: read_fq2(&mut reader)?,
+    })
+}
+
+fn read_gt<E: GtEncoding, R: Read>(reader: R) -> io::Result<E::Fqk> {
+    let element = E::read_fqk(reader)?;
+
+    if element.pow(E::Fr::char()) != E::Fqk::one() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "element is not in the target group",
+        ));
+    }
+
+    Ok(element)
+}
+
+fn write_commitment<E: GtEncoding, W: Write>(
+    commitment: &PairCommitment<E>,
+    mut writer: W,
+) -> io::Result<()> {
+    E::write_gt(&commitment.t, &mut writer)?;
+    E::write_gt(&commitment.u, &mut writer)
+}
+
+fn read_commitment<E: GtEncoding, R: Read>(mut reader: R) -> io::Result<PairCommitment<E>> {
+    Ok(PairCommitment {
+        t: read_gt::<E, _>(&mut reader)?,
+        u: read_gt::<E, _>(&mut reader)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::groth16::{create_random_proof, generate_random_parameters, Proof};
+    use crate::{Circuit, ConstraintSystem, SynthesisError};
+    use paired::bls12_381::Fr;
+    use rand_core::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    /// Proves knowledge of a square root of the public input.
+    #[derive(Clone)]
+    struct Square {
+        x: Option<Fr>,
+    }
+
+    impl Circuit<Bls12> for Square {
+        fn synthesize<CS: ConstraintSystem<Bls12>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let x_value = self.x;
+            let x = cs.alloc(|| "x", || x_value.ok_or(SynthesisError::AssignmentMissing))?;
+            let y = cs.alloc_input(
+                || "y",
+                || {
+                    let mut y = x_value.ok_or(SynthesisError::AssignmentMissing)?;
+                    y.square();
+                    Ok(y)
+                },
+            )?;
+            cs.enforce(|| "x * x = y", |lc| lc + x, |lc| lc + x, |lc| lc + y);
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_aggregate_proofs() {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        let params = generate_random_parameters(Square { x: None }, rng).unwrap();
+        let vk = &params.vk;
+
+        let srs = setup_fake_srs::<Bls12, _>(rng, 8);
+        let (prover_srs, verifier_srs) = srs.specialize(4).unwrap();
+        for &n in &[0, 1, 3, 16] {
+            assert!(match srs.specialize(n) {
+                Err(SynthesisError::UnsupportedAggregationSize { size, max_size: 8 }) => size == n,
+                _ => false,
+            });
+        }
+
+        let mut proofs = vec![];
+        let mut inputs = vec![];
+        for i in 1..=4 {
+            let x = Fr::from_str(&i.to_string()).unwrap();
+            let mut y = x;
+            y.square();
+            proofs.push(create_random_proof(Square { x: Some(x) }, &params, rng).unwrap());
+            inputs.push(vec![y]);
+        }
+
+        let aggregate = aggregate_proofs(&prover_srs, vk, &proofs, &inputs).unwrap();
+        assert_eq!(aggregate.gipa.rounds.len(), 2);
+        assert!(verify_aggregate_proof(&verifier_srs, vk, &inputs, &aggregate).unwrap());
+
+        // The public inputs are bound to their proofs.
+        let mut swapped = inputs.clone();
+        swapped.swap(0, 1);
+        assert!(!verify_aggregate_proof(&verifier_srs, vk, &swapped, &aggregate).unwrap());
+
+        // So are the verifying key and the SRS.
+        let other_params = generate_random_parameters(Square { x: None }, rng).unwrap();
+        assert!(
+            !verify_aggregate_proof(&verifier_srs, &other_params.vk, &inputs, &aggregate).unwrap()
+        );
+        let (_, other_verifier_srs) = setup_fake_srs::<Bls12, _>(rng, 4).specialize(4).unwrap();
+        assert!(!verify_aggregate_proof(&other_verifier_srs, vk, &inputs, &aggregate).unwrap());
+
+        let mut tampered = aggregate.clone();
+        tampered.agg_c = proofs[0].c;
+        assert!(!verify_aggregate_proof(&verifier_srs, vk, &inputs, &tampered).unwrap());
+
+        let mut tampered = aggregate.clone();
+        tampered.gipa.rounds.pop();
+        assert!(!verify_aggregate_proof(&verifier_srs, vk, &inputs, &tampered).unwrap());
+
+        // An invalid proof can't be hidden in the aggregate.
+        let mut invalid = proofs.clone();
+        invalid[3] = Proof {
+            a: proofs[3].a,
+            b: proofs[3].b,
+            c: proofs[2].c,
+        };
+        let aggregate_invalid = aggregate_proofs(&prover_srs, vk, &invalid, &inputs).unwrap();
+        assert!(!verify_aggregate_proof(&verifier_srs, vk, &inputs, &aggregate_invalid).unwrap());
+
+        assert!(
+            match aggregate_proofs(&prover_srs, vk, &proofs[..2], &inputs[..2]) {
+                Err(SynthesisError::AggregationSizeMismatch {
+                    expected: 4,
+                    actual: 2,
+                }) => true,
+                _ => false,
+            }
+        );
+
+        // Serialization.
+        let mut buf = vec![];
+        aggregate.write(&mut buf).unwrap();
+        let gt_len = 12 * 48;
+        let round_len = 10 * gt_len + 2 * 48;
+        let fixed_len = 4 * gt_len + 3 * 48 + 2 * 96 + 4 + 4 * 48 + 3 * 96;
+        assert_eq!(buf.len(), fixed_len + 2 * round_len);
+        let read = AggregateProof::<Bls12>::read(&buf[..]).unwrap();
+        assert!(read == aggregate);
+        assert!(AggregateProof::<Bls12>::read(&buf[..buf.len() - 1]).is_err());
+
+        let mut srs_buf = vec![];
+        srs.write(&mut srs_buf).unwrap();
+        let srs_read = GenericSRS::<Bls12>::read(&srs_buf[..]).unwrap();
+        assert_eq!(srs_read.g_alpha_powers, srs.g_alpha_powers);
+        assert_eq!(srs_read.h_beta_powers, srs.h_beta_powers);
+    }
+}
@@ -0,0 +1,184 @@
+use ff::Field;
+use groupy::{CurveAffine, CurveProjective};
+use rayon::prelude::*;
+
+use super::commit::{commit_ab, commit_c, multiexponentiation, pairing_product};
+use super::srs::{
+    fold, kzg_quotient, powers, vkey_poly_coeffs, wkey_poly_coeffs, CommitmentKey, ProverSRS,
+};
+use super::transcript::Transcript;
+use super::{AggregateProof, GipaProof, GipaRound, GtEncoding};
+use crate::groth16::{Proof, VerifyingKey};
+use crate::SynthesisError;
+
+/// Aggregates proofs for the verifying key `vk` into a single proof of size
+/// logarithmic in their number, which must be the size `srs` was specialized
+/// for. The public inputs of every proof are bound to the aggregate proof.
+pub fn aggregate_proofs<E: GtEncoding>(
+    srs: &ProverSRS<E>,
+    vk: &VerifyingKey<E>,
+    proofs: &[Proof<E>],
+    public_inputs: &[Vec<E::Fr>],
+) -> Result<AggregateProof<E>, SynthesisError> {
+    if proofs.len() != srs.n {
+        return Err(SynthesisError::AggregationSizeMismatch {
+            expected: srs.n,
+            actual: proofs.len(),
+        });
+    }
+    if public_inputs.len() != srs.n {
+        return Err(SynthesisError::AggregationSizeMismatch {
+            expected: srs.n,
+            actual: public_inputs.len(),
+        });
+    }
+
+    let a = proofs.iter().map(|proof| proof.a).collect::<Vec<_>>();
+    let b = proofs.iter().map(|proof| proof.b).collect::<Vec<_>>();
+    let c = proofs.iter().map(|proof| proof.c).collect::<Vec<_>>();
+
+    let vkey = srs.vkey();
+    let wkey = srs.wkey();
+    let com_ab = commit_ab(&vkey, &wkey, &a, &b);
+    let com_c = commit_c(&vkey, &c);
+
+    let mut transcript = Transcript::new(&srs.verifier_srs(), vk, public_inputs);
+    transcript.append_commitment(&com_ab);
+    transcript.append_commitment(&com_c);
+    let r = transcript.challenge();
+    let r_inv = r.inverse().expect("challenges are non-zero");
+
+    // The verifier checks the proofs combined with the powers of r. Scaling A
+    // and C by r^i and the key committing to them by r^-i leaves the
+    // commitments unchanged.
+    let r_powers = powers(&r, srs.n);
+    let agg_c = multiexponentiation(&c, &r_powers).into_affine();
+    let scale = |points: &[E::G1Affine]| {
+        points
+            .par_iter()
+            .zip(r_powers.par_iter())
+            .map(|(p, r)| p.mul(*r).into_affine())
+            .collect::<Vec<_>>()
+    };
+    let a_r = scale(&a);
+    let c_r = scale(&c);
+    let vkey_r = vkey.scale_powers(&r_inv);
+
+    // The inner products the GIPA proves, which the verifier derives from
+    // agg_c. The one of the C elements is agg_c itself.
+    let z_ab = pairing_product::<E>(&a_r, &b);
+    transcript.append_g1(&agg_c);
+    transcript.append_gt(&z_ab);
+
+    let (gipa, challenges) = prove_gipa(&mut transcript, a_r, b, c_r, vkey_r, wkey);
+
+    transcript.append_final(&gipa);
+    let z = transcript.challenge();
+
+    let challenges_inv = challenges
+        .iter()
+        .map(|x| x.inverse().expect("challenges are non-zero"))
+        .collect::<Vec<_>>();
+
+    // The final vkey is committed to in G2, so it is opened in G1, and the
+    // other way around for the final wkey.
+    let vkey_quotient = kzg_quotient(&vkey_poly_coeffs(&challenges_inv, &r_inv), &z);
+    let vkey_opening = (
+        multiexponentiation(&srs.g_alpha_powers[..vkey_quotient.len()], &vkey_quotient)
+            .into_affine(),
+        multiexponentiation(&srs.g_beta_powers[..vkey_quotient.len()], &vkey_quotient)
+            .into_affine(),
+    );
+    let wkey_quotient = kzg_quotient(&wkey_poly_coeffs(&challenges, srs.n), &z);
+    let wkey_opening = (
+        multiexponentiation(&srs.h_alpha_powers[..wkey_quotient.len()], &wkey_quotient)
+            .into_affine(),
+        multiexponentiation(&srs.h_beta_powers[..wkey_quotient.len()], &wkey_quotient)
+            .into_affine(),
+    );
+
+    Ok(AggregateProof {
+        com_ab,
+        com_c,
+        agg_c,
+        gipa,
+        vkey_opening,
+        wkey_opening,
+    })
+}
+
+/// Proves, by halving the vectors in each round, that the commitments to
+/// `a`, `b` and `c` under `vkey` and `wkey` are consistent with
+/// `prod_i e(a_i, b_i)` and `sum_i c_i`. Returns the proof and the challenges
+/// of the rounds.
+fn prove_gipa<E: GtEncoding>(
+    transcript: &mut Transcript<E>,
+    mut a: Vec<E::G1Affine>,
+    mut b: Vec<E::G2Affine>,
+    mut c: Vec<E::G1Affine>,
+    mut vkey: CommitmentKey<E::G2Affine>,
+    mut wkey: CommitmentKey<E::G1Affine>,
+) -> (GipaProof<E>, Vec<E::Fr>) {
+    let mut rounds = vec![];
+    let mut challenges = vec![];
+
+    // The vector paired with c in its inner product starts out as all ones,
+    // and folding keeps its elements equal, so a single scalar tracks it.
+    let mut c_scalar = E::Fr::one();
+
+    while a.len() > 1 {
+        let m = a.len() / 2;
+        let (a_l, a_r) = a.split_at(m);
+        let (b_l, b_r) = b.split_at(m);
+        let (c_l, c_r) = c.split_at(m);
+        let (vkey_l, vkey_r) = vkey.split(m);
+        let (wkey_l, wkey_r) = wkey.split(m);
+
+        let sum_c = |c: &[E::G1Affine]| {
+            let mut sum = c.iter().fold(E::G1::zero(), |mut sum, c| {
+                sum.add_assign_mixed(c);
+                sum
+            });
+            sum.mul_assign(c_scalar);
+            sum.into_affine()
+        };
+
+        let round = GipaRound {
+            com_ab_l: commit_ab(&vkey_l, &wkey_r, a_r, b_l),
+            com_ab_r: commit_ab(&vkey_r, &wkey_l, a_l, b_r),
+            z_ab_l: pairing_product::<E>(a_r, b_l),
+            z_ab_r: pairing_product::<E>(a_l, b_r),
+            com_c_l: commit_c(&vkey_l, c_r),
+            com_c_r: commit_c(&vkey_r, c_l),
+            z_c_l: sum_c(c_r),
+            z_c_r: sum_c(c_l),
+        };
+        transcript.append_round(&round);
+        rounds.push(round);
+
+        let x = transcript.challenge();
+        let x_inv = x.inverse().expect("challenges are non-zero");
+        challenges.push(x);
+
+        a = fold(a_l, a_r, &x);
+        b = fold(b_l, b_r, &x_inv);
+        c = fold(c_l, c_r, &x);
+        vkey = CommitmentKey::fold(&vkey_l, &vkey_r, &x_inv);
+        wkey = CommitmentKey::fold(&wkey_l, &wkey_r, &x);
+
+        let mut scale = x_inv;
+        scale.add_assign(&E::Fr::one());
+        c_scalar.mul_assign(&scale);
+    }
+
+    let gipa = GipaProof {
+        rounds,
+        final_a: a[0],
+        final_b: b[0],
+        final_c: c[0],
+        final_vkey: (vkey.a[0], vkey.b[0]),
+        final_wkey: (wkey.a[0], wkey.b[0]),
+    };
+
+    (gipa, challenges)
+}
@@ -0,0 +1,384 @@
+use std::io::{self, Read, Write};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use ff::{Field, PrimeField};
+use groupy::{CurveAffine, CurveProjective, EncodedPoint};
+use paired::Engine;
+use rand_core::RngCore;
+use rayon::prelude::*;
+
+use crate::SynthesisError;
+
+/// A structured reference string holding the powers `g^(alpha^i)`,
+/// `g^(beta^i)`, `h^(alpha^i)` and `h^(beta^i)` of two secrets `alpha` and
+/// `beta`, which are unrelated to those of a Groth16 verifying key. An SRS
+/// with `2 * n` powers can aggregate up to `n` proofs.
+///
+/// The powers of alpha and beta can be taken from two independent powers of
+/// tau ceremonies.
+#[derive(Clone, Debug)]
+pub struct GenericSRS<E: Engine> {
+    pub g_alpha_powers: Vec<E::G1Affine>,
+    pub g_beta_powers: Vec<E::G1Affine>,
+    pub h_alpha_powers: Vec<E::G2Affine>,
+    pub h_beta_powers: Vec<E::G2Affine>,
+}
+
+/// The part of the SRS needed to aggregate exactly `n` proofs.
+#[derive(Clone, Debug)]
+pub struct ProverSRS<E: Engine> {
+    pub n: usize,
+    pub g_alpha_powers: Vec<E::G1Affine>,
+    pub g_beta_powers: Vec<E::G1Affine>,
+    pub h_alpha_powers: Vec<E::G2Affine>,
+    pub h_beta_powers: Vec<E::G2Affine>,
+}
+
+/// The part of the SRS needed to verify an aggregate of exactly `n` proofs.
+#[derive(Clone, Debug)]
+pub struct VerifierSRS<E: Engine> {
+    pub n: usize,
+    pub g: E::G1Affine,
+    pub h: E::G2Affine,
+    pub g_alpha: E::G1Affine,
+    pub g_beta: E::G1Affine,
+    pub h_alpha: E::G2Affine,
+    pub h_beta: E::G2Affine,
+}
+
+/// A pair of commitment keys, with one vector per secret of the SRS.
+#[derive(Clone, Debug)]
+pub(super) struct CommitmentKey<G: CurveAffine> {
+    pub(super) a: Vec<G>,
+    pub(super) b: Vec<G>,
+}
+
+/// Generates an SRS for aggregating up to `size` proofs from secrets drawn
+/// from `rng`. This is only suitable for testing, since whoever knows the
+/// secrets can forge aggregate proofs.
+pub fn setup_fake_srs<E: Engine, R: RngCore>(rng: &mut R, size: usize) -> GenericSRS<E> {
+    let alpha = E::Fr::random(rng);
+    let beta = E::Fr::random(rng);
+    let alpha_powers = powers(&alpha, 2 * size);
+    let beta_powers = powers(&beta, 2 * size);
+
+    GenericSRS {
+        g_alpha_powers: mul_generator(&alpha_powers),
+        g_beta_powers: mul_generator(&beta_powers),
+        h_alpha_powers: mul_generator(&alpha_powers),
+        h_beta_powers: mul_generator(&beta_powers),
+    }
+}
+
+fn mul_generator<G: CurveAffine>(scalars: &[G::Scalar]) -> Vec<G> {
+    scalars
+        .par_iter()
+        .map(|s| G::one().mul(*s).into_affine())
+        .collect()
+}
+
+/// Returns `[1, s, s^2, ..., s^(n - 1)]`.
+pub(super) fn powers<F: Field>(s: &F, n: usize) -> Vec<F> {
+    let mut powers = Vec::with_capacity(n);
+    let mut cur = F::one();
+    for _ in 0..n {
+        powers.push(cur);
+        cur.mul_assign(s);
+    }
+    powers
+}
+
+impl<E: Engine> GenericSRS<E> {
+    /// The maximum number of proofs this SRS can aggregate.
+    pub fn max_size(&self) -> usize {
+        self.g_alpha_powers.len() / 2
+    }
+
+    /// Extracts the keys for aggregating exactly `n` proofs, where `n` must
+    /// be a power of two of at least two, and at most `max_size`.
+    pub fn specialize(&self, n: usize) -> Result<(ProverSRS<E>, VerifierSRS<E>), SynthesisError> {
+        if n < 2 || !n.is_power_of_two() || n > self.max_size() {
+            return Err(SynthesisError::UnsupportedAggregationSize {
+                size: n,
+                max_size: self.max_size(),
+            });
+        }
+
+        let prover = ProverSRS {
+            n,
+            g_alpha_powers: self.g_alpha_powers[..2 * n].to_vec(),
+            g_beta_powers: self.g_beta_powers[..2 * n].to_vec(),
+            h_alpha_powers: self.h_alpha_powers[..2 * n].to_vec(),
+            h_beta_powers: self.h_beta_powers[..2 * n].to_vec(),
+        };
+        let verifier = prover.verifier_srs();
+
+        Ok((prover, verifier))
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_u32::<BigEndian>(self.g_alpha_powers.len() as u32)?;
+        for g in self.g_alpha_powers.iter().chain(&self.g_beta_powers) {
+            writer.write_all(g.into_uncompressed().as_ref())?;
+        }
+        for h in self.h_alpha_powers.iter().chain(&self.h_beta_powers) {
+            writer.write_all(h.into_uncompressed().as_ref())?;
+        }
+
+        Ok(())
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let len = reader.read_u32::<BigEndian>()? as usize;
+        if len < 2 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "SRS must hold at least two powers",
+            ));
+        }
+
+        Ok(GenericSRS {
+            g_alpha_powers: read_points(&mut reader, len)?,
+            g_beta_powers: read_points(&mut reader, len)?,
+            h_alpha_powers: read_points(&mut reader, len)?,
+            h_beta_powers: read_points(&mut reader, len)?,
+        })
+    }
+}
+
+fn read_points<G: CurveAffine, R: Read>(mut reader: R, len: usize) -> io::Result<Vec<G>> {
+    let mut repr = G::Uncompressed::empty();
+    (0..len)
+        .map(|_| {
+            reader.read_exact(repr.as_mut())?;
+            repr.into_affine()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+                .and_then(|e| {
+                    if e.is_zero() {
+                        Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "point at infinity",
+                        ))
+                    } else {
+                        Ok(e)
+                    }
+                })
+        })
+        .collect()
+}
+
+impl<E: Engine> ProverSRS<E> {
+    /// The part of this SRS the verifier needs.
+    pub fn verifier_srs(&self) -> VerifierSRS<E> {
+        VerifierSRS {
+            n: self.n,
+            g: self.g_alpha_powers[0],
+            h: self.h_alpha_powers[0],
+            g_alpha: self.g_alpha_powers[1],
+            g_beta: self.g_beta_powers[1],
+            h_alpha: self.h_alpha_powers[1],
+            h_beta: self.h_beta_powers[1],
+        }
+    }
+
+    /// The key committing to the `A` and `C` elements of the proofs, made of
+    /// the first `n` powers in G2.
+    pub(super) fn vkey(&self) -> CommitmentKey<E::G2Affine> {
+        CommitmentKey {
+            a: self.h_alpha_powers[..self.n].to_vec(),
+            b: self.h_beta_powers[..self.n].to_vec(),
+        }
+    }
+
+    /// The key committing to the `B` elements of the proofs, made of the
+    /// last `n` powers in G1.
+    pub(super) fn wkey(&self) -> CommitmentKey<E::G1Affine> {
+        CommitmentKey {
+            a: self.g_alpha_powers[self.n..].to_vec(),
+            b: self.g_beta_powers[self.n..].to_vec(),
+        }
+    }
+}
+
+impl<G: CurveAffine> CommitmentKey<G> {
+    pub(super) fn len(&self) -> usize {
+        self.a.len()
+    }
+
+    pub(super) fn split(&self, at: usize) -> (Self, Self) {
+        let left = CommitmentKey {
+            a: self.a[..at].to_vec(),
+            b: self.b[..at].to_vec(),
+        };
+        let right = CommitmentKey {
+            a: self.a[at..].to_vec(),
+            b: self.b[at..].to_vec(),
+        };
+        (left, right)
+    }
+
+    /// Computes `left + x * right` element-wise.
+    pub(super) fn fold(left: &Self, right: &Self, x: &G::Scalar) -> Self {
+        CommitmentKey {
+            a: fold(&left.a, &right.a, x),
+            b: fold(&left.b, &right.b, x),
+        }
+    }
+
+    /// Multiplies the `i`-th element of both vectors by `s^i`.
+    pub(super) fn scale_powers(&self, s: &G::Scalar) -> Self {
+        let powers = powers(s, self.len());
+        let scale = |points: &[G]| {
+            points
+                .par_iter()
+                .zip(powers.par_iter())
+                .map(|(p, s)| p.mul(*s).into_affine())
+                .collect()
+        };
+
+        CommitmentKey {
+            a: scale(&self.a),
+            b: scale(&self.b),
+        }
+    }
+}
+
+/// Computes `left + x * right` element-wise.
+pub(super) fn fold<G: CurveAffine>(left: &[G], right: &[G], x: &G::Scalar) -> Vec<G> {
+    left.par_iter()
+        .zip(right.par_iter())
+        .map(|(l, r)| {
+            let mut folded = r.mul(*x);
+            folded.add_assign_mixed(l);
+            folded.into_affine()
+        })
+        .collect()
+}
+
+// Folding a key of length `n` with the challenges `x_1, ..., x_k` of the
+// GIPA rounds, where round `j` folds the element at `i + m_j` into the one at
+// `i` for `m_j = n / 2^j`, leaves `sum_i c_i * key_i` with
+// `sum_i c_i * X^i = prod_j (1 + x_j * X^m_j)`. As the keys are powers of the
+// secrets, the final keys are commitments to such polynomials, which the
+// prover opens with KZG to show they were folded correctly.
+
+/// The coefficients of `prod_j (1 + factors[j] * X^m_j)`.
+pub(super) fn product_poly_coeffs<F: Field>(factors: &[F]) -> Vec<F> {
+    let mut coeffs = vec![F::one()];
+    for factor in factors.iter().rev() {
+        let shifted = coeffs
+            .iter()
+            .map(|c| {
+                let mut c = *c;
+                c.mul_assign(factor);
+                c
+            })
+            .collect::<Vec<_>>();
+        coeffs.extend(shifted);
+    }
+    coeffs
+}
+
+/// Evaluates `prod_j (1 + factors[j] * X^m_j)` at `z`.
+pub(super) fn product_poly_eval<F: Field>(factors: &[F], z: &F) -> F {
+    let mut acc = F::one();
+    let mut z_power = *z;
+    for factor in factors.iter().rev() {
+        let mut term = z_power;
+        term.mul_assign(factor);
+        term.add_assign(&F::one());
+        acc.mul_assign(&term);
+        z_power.square();
+    }
+    acc
+}
+
+/// Divides `f(X) - f(z)` by `X - z`, for `f` given by its coefficients.
+pub(super) fn kzg_quotient<F: Field>(coeffs: &[F], z: &F) -> Vec<F> {
+    let mut quotient = vec![F::zero(); coeffs.len() - 1];
+    let mut acc = F::zero();
+    for i in (1..coeffs.len()).rev() {
+        acc.mul_assign(z);
+        acc.add_assign(&coeffs[i]);
+        quotient[i - 1] = acc;
+    }
+    quotient
+}
+
+/// The exponents of the final key `vkey`, which is scaled by `r^-i` before
+/// folding with the inverses of the challenges.
+pub(super) fn vkey_poly_coeffs<F: PrimeField>(challenges_inv: &[F], r_inv: &F) -> Vec<F> {
+    let mut coeffs = product_poly_coeffs(challenges_inv);
+    let r_powers = powers(r_inv, coeffs.len());
+    for (c, r) in coeffs.iter_mut().zip(&r_powers) {
+        c.mul_assign(r);
+    }
+    coeffs
+}
+
+pub(super) fn vkey_poly_eval<F: PrimeField>(challenges_inv: &[F], r_inv: &F, z: &F) -> F {
+    let mut z_r = *z;
+    z_r.mul_assign(r_inv);
+    product_poly_eval(challenges_inv, &z_r)
+}
+
+/// The exponents of the final key `wkey`, which starts at the `n`-th power
+/// and is folded with the challenges.
+pub(super) fn wkey_poly_coeffs<F: PrimeField>(challenges: &[F], n: usize) -> Vec<F> {
+    let mut coeffs = vec![F::zero(); n];
+    coeffs.extend(product_poly_coeffs(challenges));
+    coeffs
+}
+
+pub(super) fn wkey_poly_eval<F: PrimeField>(challenges: &[F], n: usize, z: &F) -> F {
+    let mut acc = z.pow(&[n as u64]);
+    acc.mul_assign(&product_poly_eval(challenges, z));
+    acc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use paired::bls12_381::Fr;
+    use rand_core::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    fn eval<F: Field>(coeffs: &[F], z: &F) -> F {
+        let mut acc = F::zero();
+        for c in coeffs.iter().rev() {
+            acc.mul_assign(z);
+            acc.add_assign(c);
+        }
+        acc
+    }
+
+    #[test]
+    fn test_key_polynomials() {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+        let challenges = (0..3).map(|_| Fr::random(rng)).collect::<Vec<_>>();
+        let r_inv = Fr::random(rng);
+        let z = Fr::random(rng);
+
+        let vkey = vkey_poly_coeffs(&challenges, &r_inv);
+        assert_eq!(vkey.len(), 8);
+        assert_eq!(eval(&vkey, &z), vkey_poly_eval(&challenges, &r_inv, &z));
+
+        let wkey = wkey_poly_coeffs(&challenges, 8);
+        assert_eq!(wkey.len(), 16);
+        assert_eq!(eval(&wkey, &z), wkey_poly_eval(&challenges, 8, &z));
+
+        // (X - z) * q(X) = f(X) - f(z)
+        let quotient = kzg_quotient(&wkey, &z);
+        let x = Fr::random(rng);
+        let mut lhs = x;
+        lhs.sub_assign(&z);
+        lhs.mul_assign(&eval(&quotient, &x));
+        let mut rhs = eval(&wkey, &x);
+        rhs.sub_assign(&eval(&wkey, &z));
+        assert_eq!(lhs, rhs);
+    }
+}
@@ -0,0 +1,134 @@
+use std::marker::PhantomData;
+
+use blake2s_simd::{Params as Blake2sParams, State as Blake2sState};
+use ff::{Field, PrimeField, PrimeFieldRepr};
+use groupy::CurveAffine;
+
+use super::srs::VerifierSRS;
+use super::{GipaProof, GipaRound, GtEncoding, PairCommitment};
+use crate::digest::hash_to_scalar;
+use crate::groth16::VerifyingKey;
+
+const TRANSCRIPT_PERSONALIZATION: &[u8; 8] = b"BP_Aggr_";
+
+/// A Fiat-Shamir transcript, from which the prover and the verifier derive
+/// the same challenges.
+pub(super) struct Transcript<E: GtEncoding> {
+    state: Blake2sState,
+    _marker: PhantomData<E>,
+}
+
+impl<E: GtEncoding> Transcript<E> {
+    /// Starts a transcript for the aggregate of proofs for `public_inputs`
+    /// under `vk`, using the commitment keys of `srs`.
+    pub(super) fn new(
+        srs: &VerifierSRS<E>,
+        vk: &VerifyingKey<E>,
+        public_inputs: &[Vec<E::Fr>],
+    ) -> Self {
+        let mut transcript = Transcript {
+            state: Blake2sParams::new()
+                .hash_length(32)
+                .personal(TRANSCRIPT_PERSONALIZATION)
+                .to_state(),
+            _marker: PhantomData,
+        };
+
+        // The commitment keys are made of powers of the secrets of the SRS,
+        // which the openings of the final keys check against these elements.
+        transcript.append_len(srs.n);
+        transcript.append_g1(&srs.g);
+        transcript.append_g2(&srs.h);
+        transcript.append_g1(&srs.g_alpha);
+        transcript.append_g1(&srs.g_beta);
+        transcript.append_g2(&srs.h_alpha);
+        transcript.append_g2(&srs.h_beta);
+
+        transcript.append_g1(&vk.alpha_g1);
+        transcript.append_g1(&vk.beta_g1);
+        transcript.append_g2(&vk.beta_g2);
+        transcript.append_g2(&vk.gamma_g2);
+        transcript.append_g1(&vk.delta_g1);
+        transcript.append_g2(&vk.delta_g2);
+        transcript.append_len(vk.ic.len());
+        for ic in &vk.ic {
+            transcript.append_g1(ic);
+        }
+
+        transcript.append_len(public_inputs.len());
+        for inputs in public_inputs {
+            transcript.append_len(inputs.len());
+            for input in inputs {
+                transcript.append_scalar(input);
+            }
+        }
+
+        transcript
+    }
+
+    fn append_len(&mut self, len: usize) {
+        self.state.update(&(len as u64).to_le_bytes());
+    }
+
+    fn append_scalar(&mut self, scalar: &E::Fr) {
+        let mut bytes = vec![];
+        scalar
+            .into_repr()
+            .write_le(&mut bytes)
+            .expect("writing to a vector does not fail");
+        self.state.update(&bytes);
+    }
+
+    pub(super) fn append_g1(&mut self, point: &E::G1Affine) {
+        self.state.update(point.into_uncompressed().as_ref());
+    }
+
+    pub(super) fn append_g2(&mut self, point: &E::G2Affine) {
+        self.state.update(point.into_uncompressed().as_ref());
+    }
+
+    pub(super) fn append_gt(&mut self, element: &E::Fqk) {
+        let mut bytes = vec![];
+        E::write_gt(element, &mut bytes).expect("writing to a vector does not fail");
+        self.state.update(&bytes);
+    }
+
+    pub(super) fn append_commitment(&mut self, commitment: &PairCommitment<E>) {
+        self.append_gt(&commitment.t);
+        self.append_gt(&commitment.u);
+    }
+
+    pub(super) fn append_round(&mut self, round: &GipaRound<E>) {
+        self.append_commitment(&round.com_ab_l);
+        self.append_commitment(&round.com_ab_r);
+        self.append_gt(&round.z_ab_l);
+        self.append_gt(&round.z_ab_r);
+        self.append_commitment(&round.com_c_l);
+        self.append_commitment(&round.com_c_r);
+        self.append_g1(&round.z_c_l);
+        self.append_g1(&round.z_c_r);
+    }
+
+    /// Appends the final values and keys of a GIPA proof, but not its rounds.
+    pub(super) fn append_final(&mut self, gipa: &GipaProof<E>) {
+        self.append_g1(&gipa.final_a);
+        self.append_g2(&gipa.final_b);
+        self.append_g1(&gipa.final_c);
+        self.append_g2(&gipa.final_vkey.0);
+        self.append_g2(&gipa.final_vkey.1);
+        self.append_g1(&gipa.final_wkey.0);
+        self.append_g1(&gipa.final_wkey.1);
+    }
+
+    /// Derives a non-zero challenge from everything appended so far, and
+    /// appends it to the transcript.
+    pub(super) fn challenge(&mut self) -> E::Fr {
+        loop {
+            let challenge: E::Fr = hash_to_scalar(&self.state);
+            self.append_scalar(&challenge);
+            if !challenge.is_zero() {
+                return challenge;
+            }
+        }
+    }
+}
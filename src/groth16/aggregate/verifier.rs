@@ -0,0 +1,231 @@
+use ff::{Field, PrimeField};
+use groupy::{CurveAffine, CurveProjective};
+use paired::{Engine, PairingCurveAffine};
+
+use super::commit::{commit_ab, commit_c, multiexponentiation, pairing_product};
+use super::srs::{powers, vkey_poly_eval, wkey_poly_eval, CommitmentKey, VerifierSRS};
+use super::transcript::Transcript;
+use super::{AggregateProof, GtEncoding};
+use crate::groth16::{prepare_verifying_key, VerifyingKey};
+use crate::SynthesisError;
+
+/// Verifies an aggregate of proofs for `public_inputs` under the verifying
+/// key `vk`.
+pub fn verify_aggregate_proof<E: GtEncoding>(
+    srs: &VerifierSRS<E>,
+    vk: &VerifyingKey<E>,
+    public_inputs: &[Vec<E::Fr>],
+    proof: &AggregateProof<E>,
+) -> Result<bool, SynthesisError> {
+    let pvk = prepare_verifying_key(vk);
+    if public_inputs.len() != srs.n {
+        return Err(SynthesisError::AggregationSizeMismatch {
+            expected: srs.n,
+            actual: public_inputs.len(),
+        });
+    }
+    for inputs in public_inputs {
+        if inputs.len() + 1 != pvk.ic.len() {
+            return Err(SynthesisError::MalformedVerifyingKey);
+        }
+    }
+    if proof.gipa.rounds.len() != srs.n.trailing_zeros() as usize {
+        return Ok(false);
+    }
+
+    let mut transcript = Transcript::new(srs, vk, public_inputs);
+    transcript.append_commitment(&proof.com_ab);
+    transcript.append_commitment(&proof.com_c);
+    let r = transcript.challenge();
+    let r_inv = r.inverse().expect("challenges are non-zero");
+
+    // Combining the verification equations of the proofs with the powers of r
+    // gives the inner product of the A and B elements scaled by r^i, in
+    // terms of the sum of the C elements scaled by r^i:
+    // prod_i e(A_i, B_i)^(r^i) = e(alpha, beta)^(sum_i r^i)
+    //     * e(sum_i r^i * IC(inputs_i), gamma) * e(agg_c, delta)
+    let r_powers = powers(&r, srs.n);
+    let mut sum_r = E::Fr::zero();
+    for r in &r_powers {
+        sum_r.add_assign(r);
+    }
+
+    let mut acc_ic = pvk.ic[0].mul(sum_r);
+    if pvk.ic.len() > 1 {
+        let ic_scalars = (0..pvk.ic.len() - 1)
+            .map(|j| {
+                let mut scalar = E::Fr::zero();
+                for (r, inputs) in r_powers.iter().zip(public_inputs) {
+                    let mut tmp = *r;
+                    tmp.mul_assign(&inputs[j]);
+                    scalar.add_assign(&tmp);
+                }
+                scalar
+            })
+            .collect::<Vec<_>>();
+        acc_ic.add_assign(&multiexponentiation(&pvk.ic[1..], &ic_scalars));
+    }
+
+    // The verifying key holds -gamma and -delta, so negate the other side.
+    acc_ic.negate();
+    let mut neg_agg_c = proof.agg_c.into_projective();
+    neg_agg_c.negate();
+    let acc_ic = acc_ic.into_affine().prepare();
+    let neg_agg_c = neg_agg_c.into_affine().prepare();
+    let mut z_ab = E::final_exponentiation(&E::miller_loop(&[
+        (&acc_ic, &pvk.neg_gamma_g2),
+        (&neg_agg_c, &pvk.neg_delta_g2),
+    ]))
+    .expect("miller loop result is never zero");
+    z_ab.mul_assign(&pvk.alpha_g1_beta_g2.pow(&sum_r.into_repr()));
+    transcript.append_g1(&proof.agg_c);
+    transcript.append_gt(&z_ab);
+
+    // Replay the rounds of the GIPA proof on the claimed commitments and
+    // inner products.
+    let mut com_ab = proof.com_ab.clone();
+    let mut com_c = proof.com_c.clone();
+    let mut z_c = proof.agg_c.into_projective();
+    let mut challenges = vec![];
+    let mut challenges_inv = vec![];
+    let mut c_scalar = E::Fr::one();
+    for round in &proof.gipa.rounds {
+        transcript.append_round(round);
+        let x = transcript.challenge();
+        let x_inv = x.inverse().expect("challenges are non-zero");
+
+        com_ab.mul_pow(&round.com_ab_l, &x);
+        com_ab.mul_pow(&round.com_ab_r, &x_inv);
+        z_ab.mul_assign(&round.z_ab_l.pow(&x.into_repr()));
+        z_ab.mul_assign(&round.z_ab_r.pow(&x_inv.into_repr()));
+        com_c.mul_pow(&round.com_c_l, &x);
+        com_c.mul_pow(&round.com_c_r, &x_inv);
+        z_c.add_assign(&round.z_c_l.mul(x));
+        z_c.add_assign(&round.z_c_r.mul(x_inv));
+
+        let mut scale = x_inv;
+        scale.add_assign(&E::Fr::one());
+        c_scalar.mul_assign(&scale);
+
+        challenges.push(x);
+        challenges_inv.push(x_inv);
+    }
+
+    let gipa = &proof.gipa;
+    let final_vkey = CommitmentKey {
+        a: vec![gipa.final_vkey.0],
+        b: vec![gipa.final_vkey.1],
+    };
+    let final_wkey = CommitmentKey {
+        a: vec![gipa.final_wkey.0],
+        b: vec![gipa.final_wkey.1],
+    };
+
+    if com_ab != commit_ab(&final_vkey, &final_wkey, &[gipa.final_a], &[gipa.final_b])
+        || z_ab != pairing_product::<E>(&[gipa.final_a], &[gipa.final_b])
+        || com_c != commit_c(&final_vkey, &[gipa.final_c])
+        || z_c != gipa.final_c.mul(c_scalar)
+    {
+        return Ok(false);
+    }
+
+    // Check the KZG openings of the final keys at a random point.
+    transcript.append_final(gipa);
+    let z = transcript.challenge();
+
+    let vkey_eval = vkey_poly_eval(&challenges_inv, &r_inv, &z);
+    let wkey_eval = wkey_poly_eval(&challenges, srs.n, &z);
+
+    Ok(check_g2_opening(
+        srs,
+        &gipa.final_vkey.0,
+        &proof.vkey_opening.0,
+        &srs.h_alpha,
+        &z,
+        &vkey_eval,
+    ) && check_g2_opening(
+        srs,
+        &gipa.final_vkey.1,
+        &proof.vkey_opening.1,
+        &srs.h_beta,
+        &z,
+        &vkey_eval,
+    ) && check_g1_opening(
+        srs,
+        &gipa.final_wkey.0,
+        &proof.wkey_opening.0,
+        &srs.g_alpha,
+        &z,
+        &wkey_eval,
+    ) && check_g1_opening(
+        srs,
+        &gipa.final_wkey.1,
+        &proof.wkey_opening.1,
+        &srs.g_beta,
+        &z,
+        &wkey_eval,
+    ))
+}
+
+/// Checks that `opening` shows the polynomial committed to by `commitment`
+/// in G2 evaluates to `eval` at `z`, i.e. that
+/// `e(opening, h^secret / h^z) = e(g, commitment / h^eval)`.
+fn check_g2_opening<E: Engine>(
+    srs: &VerifierSRS<E>,
+    commitment: &E::G2Affine,
+    opening: &E::G1Affine,
+    h_secret: &E::G2Affine,
+    z: &E::Fr,
+    eval: &E::Fr,
+) -> bool {
+    let mut h_secret_z = h_secret.into_projective();
+    h_secret_z.sub_assign(&srs.h.mul(*z));
+
+    let mut commitment_eval = commitment.into_projective();
+    commitment_eval.sub_assign(&srs.h.mul(*eval));
+
+    let mut neg_g = srs.g;
+    neg_g.negate();
+
+    let opening = opening.prepare();
+    let h_secret_z = h_secret_z.into_affine().prepare();
+    let neg_g = neg_g.prepare();
+    let commitment_eval = commitment_eval.into_affine().prepare();
+
+    E::final_exponentiation(&E::miller_loop(&[
+        (&opening, &h_secret_z),
+        (&neg_g, &commitment_eval),
+    ]))
+    .expect("miller loop result is never zero")
+        == E::Fqk::one()
+}
+
+/// Checks that `opening` shows the polynomial committed to by `commitment`
+/// in G1 evaluates to `eval` at `z`, i.e. that
+/// `e(g^secret / g^z, opening) = e(commitment / g^eval, h)`.
+fn check_g1_opening<E: Engine>(
+    srs: &VerifierSRS<E>,
+    commitment: &E::G1Affine,
+    opening: &E::G2Affine,
+    g_secret: &E::G1Affine,
+    z: &E::Fr,
+    eval: &E::Fr,
+) -> bool {
+    let mut g_secret_z = g_secret.into_projective();
+    g_secret_z.sub_assign(&srs.g.mul(*z));
+
+    let mut neg_commitment_eval = srs.g.mul(*eval);
+    neg_commitment_eval.sub_assign(&commitment.into_projective());
+
+    let g_secret_z = g_secret_z.into_affine().prepare();
+    let opening = opening.prepare();
+    let neg_commitment_eval = neg_commitment_eval.into_affine().prepare();
+    let h = srs.h.prepare();
+
+    E::final_exponentiation(&E::miller_loop(&[
+        (&g_secret_z, &opening),
+        (&neg_commitment_eval, &h),
+    ]))
+    .expect("miller loop result is never zero")
+        == E::Fqk::one()
+}
@@ -0,0 +1,219 @@
+//! Provers which run in the background and return their proofs as `std`
+//! futures, so that they can be awaited on an async executor such as tokio or
+//! async-std without blocking its worker threads.
+//!
+//! Proving itself is blocking and saturates the CPU pools of `bellperson`, so
+//! each call runs on a thread of its own and only the completion is signalled
+//! to the executor. The circuits and parameters are moved to that thread,
+//! which is why they must be `'static`: share parameters between calls through
+//! an `Arc<Parameters<E>>` or an `Arc<MappedParameters<E>>`. As every call
+//! spawns a thread, limiting the number of proofs in flight is up to the
+//! caller. Dropping the future of a proof cancels it.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::thread;
+
+use ff::Field;
+use paired::Engine;
+use rand_core::RngCore;
+
+use super::{create_proof_batch_with_options, ParameterSource, Proof, ProverOptions};
+use crate::multicore::{CancellationToken, WorkerFuture};
+use crate::{Circuit, SynthesisError};
+
+/// The result of a prover running in the background, see `WorkerFuture`.
+///
+/// Dropping the future before it resolved cancels the cancellation token of
+/// the prover, which then gives up at its next check of the token.
+pub struct ProverFuture<T> {
+    future: WorkerFuture<Result<T, SynthesisError>>,
+    cancel: CancelOnDrop,
+}
+
+impl<T: Send + 'static> ProverFuture<T> {
+    /// Blocks the current thread until the prover is done.
+    pub fn wait(self) -> Result<T, SynthesisError> {
+        let ProverFuture { future, mut cancel } = self;
+        let result = future.wait();
+        cancel.disarm();
+        result
+    }
+}
+
+impl<T> Future for ProverFuture<T> {
+    type Output = Result<T, SynthesisError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let poll = Pin::new(&mut self.future).poll(cx);
+        if poll.is_ready() {
+            self.cancel.disarm();
+        }
+        poll
+    }
+}
+
+/// Cancels its token when dropped, unless it was disarmed.
+struct CancelOnDrop(Option<CancellationToken>);
+
+impl CancelOnDrop {
+    fn disarm(&mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if let Some(cancel) = self.0.take() {
+            cancel.cancel();
+        }
+    }
+}
+
+pub fn create_proof_async<E, C, P>(
+    circuit: C,
+    params: P,
+    r: E::Fr,
+    s: E::Fr,
+) -> ProverFuture<Proof<E>>
+where
+    E: Engine,
+    C: Circuit<E> + Send + 'static,
+    P: ParameterSource<E> + 'static,
+{
+    let options = ProverOptions::default();
+    spawn_prover(options.cancel.clone(), move || {
+        let proofs = create_proof_batch_with_options::<E, C, P>(
+            vec![circuit],
+            params,
+            vec![r],
+            vec![s],
+            &options,
+        )?;
+        Ok(proofs.into_iter().next().unwrap())
+    })
+}
+
+pub fn create_random_proof_async<E, C, R, P>(
+    circuit: C,
+    params: P,
+    rng: &mut R,
+) -> ProverFuture<Proof<E>>
+where
+    E: Engine,
+    C: Circuit<E> + Send + 'static,
+    R: RngCore,
+    P: ParameterSource<E> + 'static,
+{
+    let r = E::Fr::random(rng);
+    let s = E::Fr::random(rng);
+
+    create_proof_async::<E, C, P>(circuit, params, r, s)
+}
+
+pub fn create_proof_batch_async<E, C, P>(
+    circuits: Vec<C>,
+    params: P,
+    r: Vec<E::Fr>,
+    s: Vec<E::Fr>,
+) -> ProverFuture<Vec<Proof<E>>>
+where
+    E: Engine,
+    C: Circuit<E> + Send + 'static,
+    P: ParameterSource<E> + 'static,
+{
+    create_proof_batch_priority_async::<E, C, P>(circuits, params, r, s, false)
+}
+
+pub fn create_random_proof_batch_async<E, C, R, P>(
+    circuits: Vec<C>,
+    params: P,
+    rng: &mut R,
+) -> ProverFuture<Vec<Proof<E>>>
+where
+    E: Engine,
+    C: Circuit<E> + Send + 'static,
+    R: RngCore,
+    P: ParameterSource<E> + 'static,
+{
+    // The randomness is drawn before returning, so that the rng is not moved
+    // to the prover thread.
+    let r_s = (0..circuits.len()).map(|_| E::Fr::random(rng)).collect();
+    let s_s = (0..circuits.len()).map(|_| E::Fr::random(rng)).collect();
+
+    create_proof_batch_priority_async::<E, C, P>(circuits, params, r_s, s_s, false)
+}
+
+pub fn create_proof_batch_priority_async<E, C, P>(
+    circuits: Vec<C>,
+    params: P,
+    r_s: Vec<E::Fr>,
+    s_s: Vec<E::Fr>,
+    priority: bool,
+) -> ProverFuture<Vec<Proof<E>>>
+where
+    E: Engine,
+    C: Circuit<E> + Send + 'static,
+    P: ParameterSource<E> + 'static,
+{
+    create_proof_batch_with_options_async::<E, C, P>(
+        circuits,
+        params,
+        r_s,
+        s_s,
+        ProverOptions {
+            priority,
+            ..ProverOptions::default()
+        },
+    )
+}
+
+/// Like `create_proof_batch_with_options`, in the background. The token of
+/// `options` is cancelled if the future is dropped before it resolved, and
+/// the observer is notified from the prover thread.
+pub fn create_proof_batch_with_options_async<E, C, P>(
+    circuits: Vec<C>,
+    params: P,
+    r_s: Vec<E::Fr>,
+    s_s: Vec<E::Fr>,
+    options: ProverOptions<'static>,
+) -> ProverFuture<Vec<Proof<E>>>
+where
+    E: Engine,
+    C: Circuit<E> + Send + 'static,
+    P: ParameterSource<E> + 'static,
+{
+    spawn_prover(options.cancel.clone(), move || {
+        create_proof_batch_with_options::<E, C, P>(circuits, params, r_s, s_s, &options)
+    })
+}
+
+/// Runs `prove` on a new thread rather than on one of the CPU pools, as the
+/// prover blocks while waiting for the computations it starts on those pools.
+/// `prove` must give up once `cancel` is cancelled, which happens when the
+/// returned future is dropped early. If the thread can not be spawned, the
+/// future resolves to the error.
+fn spawn_prover<T, F>(cancel: CancellationToken, prove: F) -> ProverFuture<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, SynthesisError> + Send + 'static,
+{
+    let mut spawn_result = Ok(());
+    let future = WorkerFuture::spawn(prove, |job| {
+        spawn_result = thread::Builder::new()
+            .name("bellperson-prover".into())
+            .spawn(job)
+            .map(drop);
+    });
+
+    let future = match spawn_result {
+        Ok(()) => future,
+        Err(e) => WorkerFuture::ready(Err(SynthesisError::IoError(e))),
+    };
+
+    ProverFuture {
+        future,
+        cancel: CancelOnDrop(Some(cancel)),
+    }
+}
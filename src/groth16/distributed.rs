@@ -0,0 +1,568 @@
+//! Proving with the multiexps spread over several machines.
+//!
+//! [`create_distributed_proof_batch`] synthesizes the circuits and computes
+//! the FFTs locally, then splits the multiexps of the H, L, A and B queries
+//! into [`MultiexpJob`]s of at most a given number of exponents. Jobs can be
+//! serialized and computed with [`compute_multiexp_job`] by any process
+//! holding the same parameters. Once the result of every job has been added
+//! back, [`DistributedProof::finish`] assembles the proofs locally.
+
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+
+use bit_vec::BitVec;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use ff::{Field, PrimeField, PrimeFieldRepr};
+use groupy::{CurveAffine, CurveProjective, EncodedPoint};
+use paired::Engine;
+use rand_core::RngCore;
+
+use super::prover::{assemble_proof, compute_h, synthesize_batch};
+use super::{ParameterSource, Proof, VerifyingKey};
+use crate::gpu::{LockedFFTKernel, LockedMultiexpKernel};
+use crate::multicore::{CancellationToken, Worker};
+use crate::multiexp::{multiexp, DensityTracker, FullDensity, QuerySource, SourceBuilder};
+use crate::{Circuit, SynthesisError};
+
+/// A query of the parameters which the prover computes a multiexp of.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MultiexpQuery {
+    H,
+    L,
+    AInputs,
+    AAux,
+    BG1Inputs,
+    BG1Aux,
+    BG2Inputs,
+    BG2Aux,
+}
+
+const QUERIES: [MultiexpQuery; 8] = [
+    MultiexpQuery::H,
+    MultiexpQuery::L,
+    MultiexpQuery::AInputs,
+    MultiexpQuery::AAux,
+    MultiexpQuery::BG1Inputs,
+    MultiexpQuery::BG1Aux,
+    MultiexpQuery::BG2Inputs,
+    MultiexpQuery::BG2Aux,
+];
+
+impl MultiexpQuery {
+    fn from_tag(tag: u8) -> io::Result<Self> {
+        QUERIES
+            .get(tag as usize)
+            .copied()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "unknown multiexp query"))
+    }
+
+    fn is_g2(self) -> bool {
+        self == MultiexpQuery::BG2Inputs || self == MultiexpQuery::BG2Aux
+    }
+}
+
+/// A part of one of the multiexps of a proof: the multiexp of a range of the
+/// bases of a query with the corresponding exponents.
+#[derive(Clone)]
+pub struct MultiexpJob<E: Engine> {
+    /// The position of the job in `DistributedProof::jobs`, which identifies
+    /// its result.
+    pub id: usize,
+    pub query: MultiexpQuery,
+    /// The sizes the query is requested with from the `ParameterSource`.
+    pub query_size: (usize, usize),
+    /// The position of the first base of the job in the query.
+    pub base_offset: usize,
+    /// Which of the exponents have a base in the query, if not all of them.
+    pub density: Option<BitVec>,
+    pub exponents: Vec<<E::Fr as PrimeField>::Repr>,
+}
+
+/// Writes `value`, which jobs and results store as a `u32`.
+fn write_u32<W: Write>(writer: &mut W, value: usize) -> io::Result<()> {
+    let value = u32::try_from(value).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the multiexp job is too large to be serialized",
+        )
+    })?;
+    writer.write_u32::<BigEndian>(value)
+}
+
+impl<E: Engine> MultiexpJob<E> {
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write_u32(&mut writer, self.id)?;
+        writer.write_u8(self.query as u8)?;
+        write_u32(&mut writer, self.query_size.0)?;
+        write_u32(&mut writer, self.query_size.1)?;
+        write_u32(&mut writer, self.base_offset)?;
+        write_u32(&mut writer, self.exponents.len())?;
+
+        match self.density {
+            Some(ref density) => {
+                writer.write_u8(1)?;
+                writer.write_all(&density.to_bytes())?;
+            }
+            None => writer.write_u8(0)?,
+        }
+
+        for exponent in &self.exponents {
+            exponent.write_be(&mut writer)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let id = reader.read_u32::<BigEndian>()? as usize;
+        let query = MultiexpQuery::from_tag(reader.read_u8()?)?;
+        let query_size = (
+            reader.read_u32::<BigEndian>()? as usize,
+            reader.read_u32::<BigEndian>()? as usize,
+        );
+        let base_offset = reader.read_u32::<BigEndian>()? as usize;
+        let len = reader.read_u32::<BigEndian>()? as usize;
+
+        let density = match reader.read_u8()? {
+            0 => None,
+            1 => {
+                // The length is untrusted, so the bytes are only allocated as
+                // they are read.
+                let num_bytes = (len + 7) / 8;
+                let mut bytes = vec![];
+                (&mut reader)
+                    .take(num_bytes as u64)
+                    .read_to_end(&mut bytes)?;
+                if bytes.len() != num_bytes {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "truncated density",
+                    ));
+                }
+                let mut density = BitVec::from_bytes(&bytes);
+                density.truncate(len);
+                Some(density)
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "invalid density flag",
+                ))
+            }
+        };
+
+        let mut exponents = Vec::new();
+        for _ in 0..len {
+            let mut repr = <E::Fr as PrimeField>::Repr::default();
+            repr.read_be(&mut reader)?;
+            // Only accept canonical exponents.
+            E::Fr::from_repr(repr).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            exponents.push(repr);
+        }
+
+        Ok(MultiexpJob {
+            id,
+            query,
+            query_size,
+            base_offset,
+            density,
+            exponents,
+        })
+    }
+}
+
+/// The result of a multiexp, in the group of the bases of its query.
+#[derive(Clone, Debug)]
+pub enum MultiexpOutput<E: Engine> {
+    G1(E::G1),
+    G2(E::G2),
+}
+
+/// The result of the `MultiexpJob` with the same id.
+#[derive(Clone, Debug)]
+pub struct MultiexpResult<E: Engine> {
+    pub id: usize,
+    pub output: MultiexpOutput<E>,
+}
+
+impl<E: Engine> MultiexpResult<E> {
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write_u32(&mut writer, self.id)?;
+        match self.output {
+            MultiexpOutput::G1(ref p) => {
+                writer.write_u8(1)?;
+                writer.write_all(p.into_affine().into_uncompressed().as_ref())?;
+            }
+            MultiexpOutput::G2(ref p) => {
+                writer.write_u8(2)?;
+                writer.write_all(p.into_affine().into_uncompressed().as_ref())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let id = reader.read_u32::<BigEndian>()? as usize;
+        let output = match reader.read_u8()? {
+            1 => {
+                let mut repr = <E::G1Affine as CurveAffine>::Uncompressed::empty();
+                reader.read_exact(repr.as_mut())?;
+                let p = repr
+                    .into_affine()
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                MultiexpOutput::G1(p.into_projective())
+            }
+            2 => {
+                let mut repr = <E::G2Affine as CurveAffine>::Uncompressed::empty();
+                reader.read_exact(repr.as_mut())?;
+                let p = repr
+                    .into_affine()
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                MultiexpOutput::G2(p.into_projective())
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "invalid multiexp group",
+                ))
+            }
+        };
+
+        Ok(MultiexpResult { id, output })
+    }
+}
+
+/// Computes `job` with the bases of `params`, which must be the parameters
+/// the job was created with. Each job fetches its whole query from `params`,
+/// so parameters which are kept in memory are preferable to mapped ones.
+pub fn compute_multiexp_job<E, P>(
+    job: &MultiexpJob<E>,
+    params: P,
+) -> Result<MultiexpResult<E>, SynthesisError>
+where
+    E: Engine,
+    P: ParameterSource<E>,
+{
+    let g1 =
+        |bases: P::G1Builder| job_multiexp::<E, E::G1Affine, _>(job, bases).map(MultiexpOutput::G1);
+    let g2 =
+        |bases: P::G2Builder| job_multiexp::<E, E::G2Affine, _>(job, bases).map(MultiexpOutput::G2);
+
+    let (n, m) = job.query_size;
+    let output = match job.query {
+        MultiexpQuery::H => g1(params.get_h(n)?)?,
+        MultiexpQuery::L => g1(params.get_l(n)?)?,
+        MultiexpQuery::AInputs => g1(params.get_a(n, m)?.0)?,
+        MultiexpQuery::AAux => g1(params.get_a(n, m)?.1)?,
+        MultiexpQuery::BG1Inputs => g1(params.get_b_g1(n, m)?.0)?,
+        MultiexpQuery::BG1Aux => g1(params.get_b_g1(n, m)?.1)?,
+        MultiexpQuery::BG2Inputs => g2(params.get_b_g2(n, m)?.0)?,
+        MultiexpQuery::BG2Aux => g2(params.get_b_g2(n, m)?.1)?,
+    };
+
+    Ok(MultiexpResult { id: job.id, output })
+}
+
+fn job_multiexp<E, G, S>(job: &MultiexpJob<E>, bases: S) -> Result<G::Projective, SynthesisError>
+where
+    E: Engine,
+    G: CurveAffine<Engine = E>,
+    S: SourceBuilder<G>,
+{
+    let table = bases.fixed_base_table().map(|(table, _)| table);
+    let (bases, skip) = bases.get();
+    let bases = QuerySource {
+        bases,
+        skip: skip + job.base_offset,
+        table,
+    };
+    let exponents = Arc::new(job.exponents.clone());
+
+    let mut log_d = 0;
+    while (1 << log_d) < exponents.len() {
+        log_d += 1;
+    }
+
+    let worker = Worker::new();
+    let mut kern = Some(LockedMultiexpKernel::<E>::new(log_d, false));
+    match job.density {
+        Some(ref density) => {
+            let density = Arc::new(DensityTracker {
+                bv: density.clone(),
+                total_density: density.iter().filter(|b| *b).count(),
+            });
+            multiexp(&worker, bases, density, exponents, &mut kern).wait()
+        }
+        None => multiexp(&worker, bases, FullDensity, exponents, &mut kern).wait(),
+    }
+}
+
+/// A batch of proofs whose multiexps are computed elsewhere.
+pub struct DistributedProof<E: Engine> {
+    proofs: Vec<PendingProof<E>>,
+    jobs: Vec<MultiexpJob<E>>,
+    /// The proof each job is a part of.
+    owners: Vec<usize>,
+    results: Vec<Option<MultiexpOutput<E>>>,
+}
+
+struct PendingProof<E: Engine> {
+    vk: VerifyingKey<E>,
+    r: E::Fr,
+    s: E::Fr,
+}
+
+impl<E: Engine> DistributedProof<E> {
+    pub fn jobs(&self) -> &[MultiexpJob<E>] {
+        &self.jobs
+    }
+
+    /// The jobs whose result has not been added yet.
+    pub fn pending_jobs(&self) -> impl Iterator<Item = &MultiexpJob<E>> {
+        self.jobs
+            .iter()
+            .zip(&self.results)
+            .filter(|(_, result)| result.is_none())
+            .map(|(job, _)| job)
+    }
+
+    /// Adds the result of one of the jobs. The result of a job which was
+    /// computed more than once replaces the previous one.
+    pub fn add_result(&mut self, result: MultiexpResult<E>) -> Result<(), SynthesisError> {
+        let job = self
+            .jobs
+            .get(result.id)
+            .ok_or(SynthesisError::UnexpectedJobResult(result.id))?;
+        let is_g2 = match result.output {
+            MultiexpOutput::G1(_) => false,
+            MultiexpOutput::G2(_) => true,
+        };
+        if is_g2 != job.query.is_g2() {
+            return Err(SynthesisError::UnexpectedJobResult(result.id));
+        }
+
+        self.results[result.id] = Some(result.output);
+        Ok(())
+    }
+
+    /// Assembles the proofs from the results of their jobs.
+    pub fn finish(self) -> Result<Vec<Proof<E>>, SynthesisError> {
+        let mut g1_answers = vec![[E::G1::zero(); 6]; self.proofs.len()];
+        let mut g2_answers = vec![[E::G2::zero(); 2]; self.proofs.len()];
+        for ((job, &owner), result) in self.jobs.iter().zip(&self.owners).zip(self.results) {
+            match result.ok_or(SynthesisError::MissingJobResult(job.id))? {
+                MultiexpOutput::G1(p) => g1_answers[owner][job.query as usize].add_assign(&p),
+                MultiexpOutput::G2(p) => g2_answers[owner]
+                    [job.query as usize - MultiexpQuery::BG2Inputs as usize]
+                    .add_assign(&p),
+            }
+        }
+
+        self.proofs
+            .iter()
+            .zip(g1_answers)
+            .zip(g2_answers)
+            .map(
+                |(
+                    (proof, [h, l, a_inputs, a_aux, b_g1_inputs, b_g1_aux]),
+                    [b_g2_inputs, b_g2_aux],
+                )| {
+                    assemble_proof(
+                        &proof.vk,
+                        proof.r,
+                        proof.s,
+                        h,
+                        l,
+                        (a_inputs, a_aux),
+                        (b_g1_inputs, b_g1_aux),
+                        (b_g2_inputs, b_g2_aux),
+                    )
+                },
+            )
+            .collect()
+    }
+
+    /// Splits the multiexp of `exponents` with the bases of `query` into jobs
+    /// of at most `chunk_size` exponents.
+    fn push_jobs(
+        &mut self,
+        owner: usize,
+        query: MultiexpQuery,
+        query_size: (usize, usize),
+        density: Option<&BitVec>,
+        exponents: &[<E::Fr as PrimeField>::Repr],
+        chunk_size: usize,
+    ) {
+        let mut base_offset = 0;
+        for (i, chunk) in exponents.chunks(chunk_size).enumerate() {
+            let density = density.map(|density| {
+                density
+                    .iter()
+                    .skip(i * chunk_size)
+                    .take(chunk.len())
+                    .collect::<BitVec>()
+            });
+            let num_bases = density
+                .as_ref()
+                .map_or(chunk.len(), |density| density.iter().filter(|b| *b).count());
+
+            self.jobs.push(MultiexpJob {
+                id: self.jobs.len(),
+                query,
+                query_size,
+                base_offset,
+                density,
+                exponents: chunk.to_vec(),
+            });
+            self.owners.push(owner);
+            self.results.push(None);
+
+            base_offset += num_bases;
+        }
+    }
+}
+
+pub fn create_random_distributed_proof_batch<E, C, R, P>(
+    circuits: Vec<C>,
+    params: P,
+    rng: &mut R,
+    chunk_size: usize,
+) -> Result<DistributedProof<E>, SynthesisError>
+where
+    E: Engine,
+    C: Circuit<E> + Send,
+    R: RngCore,
+    P: ParameterSource<E>,
+{
+    let r_s = (0..circuits.len()).map(|_| E::Fr::random(rng)).collect();
+    let s_s = (0..circuits.len()).map(|_| E::Fr::random(rng)).collect();
+
+    create_distributed_proof_batch::<E, C, P>(circuits, params, r_s, s_s, chunk_size)
+}
+
+/// Synthesizes `circuits` and computes their FFTs, and splits their multiexps
+/// into jobs of at most `chunk_size` exponents. The proofs are the same as
+/// the ones of `create_proof_batch` with the same randomizers. As with the
+/// default `ProverOptions`, only the sizes of the circuits are checked against
+/// the parameters, not their digests.
+pub fn create_distributed_proof_batch<E, C, P>(
+    circuits: Vec<C>,
+    params: P,
+    r_s: Vec<E::Fr>,
+    s_s: Vec<E::Fr>,
+    chunk_size: usize,
+) -> Result<DistributedProof<E>, SynthesisError>
+where
+    E: Engine,
+    C: Circuit<E> + Send,
+    P: ParameterSource<E>,
+{
+    if chunk_size == 0 {
+        return Err(SynthesisError::EmptyMultiexpJobs);
+    }
+
+    for randomness in &[&r_s, &s_s] {
+        if randomness.len() != circuits.len() {
+            return Err(SynthesisError::BatchSizeMismatch {
+                expected: circuits.len(),
+                actual: randomness.len(),
+            });
+        }
+    }
+
+    let provers = synthesize_batch(circuits, false, &CancellationToken::new())?;
+
+    let worker = Worker::new();
+    let mut distributed = DistributedProof {
+        proofs: Vec::with_capacity(provers.len()),
+        jobs: Vec::new(),
+        owners: Vec::new(),
+        results: Vec::new(),
+    };
+    for (owner, ((mut prover, r), s)) in provers.into_iter().zip(r_s).zip(s_s).enumerate() {
+        params.check_size(&prover.size())?;
+        let vk = params.get_vk(prover.input_assignment.len())?;
+
+        distributed.proofs.push(PendingProof {
+            vk: vk.clone(),
+            r,
+            s,
+        });
+
+        let mut log_d = 0;
+        while (1 << log_d) < prover.a.len() {
+            log_d += 1;
+        }
+        let mut fft_kern = Some(LockedFFTKernel::<E>::new(log_d, false));
+        let h = compute_h(&mut prover, false, vk.domain(), &worker, &mut fft_kern)?;
+        drop(fft_kern);
+
+        let input_assignment = prover
+            .input_assignment
+            .iter()
+            .map(|s| s.into_repr())
+            .collect::<Vec<_>>();
+        let aux_assignment = prover
+            .aux_assignment
+            .iter()
+            .map(|s| s.into_repr())
+            .collect::<Vec<_>>();
+
+        let a_size = (
+            input_assignment.len(),
+            prover.a_aux_density.get_total_density(),
+        );
+        let b_size = (
+            prover.b_input_density.get_total_density(),
+            prover.b_aux_density.get_total_density(),
+        );
+
+        let queries = [
+            (MultiexpQuery::H, (h.len(), 0), None, &h[..]),
+            (
+                MultiexpQuery::L,
+                (aux_assignment.len(), 0),
+                None,
+                &aux_assignment[..],
+            ),
+            (MultiexpQuery::AInputs, a_size, None, &input_assignment[..]),
+            (
+                MultiexpQuery::AAux,
+                a_size,
+                Some(&prover.a_aux_density.bv),
+                &aux_assignment[..],
+            ),
+            (
+                MultiexpQuery::BG1Inputs,
+                b_size,
+                Some(&prover.b_input_density.bv),
+                &input_assignment[..],
+            ),
+            (
+                MultiexpQuery::BG1Aux,
+                b_size,
+                Some(&prover.b_aux_density.bv),
+                &aux_assignment[..],
+            ),
+            (
+                MultiexpQuery::BG2Inputs,
+                b_size,
+                Some(&prover.b_input_density.bv),
+                &input_assignment[..],
+            ),
+            (
+                MultiexpQuery::BG2Aux,
+                b_size,
+                Some(&prover.b_aux_density.bv),
+                &aux_assignment[..],
+            ),
+        ];
+        for &(query, query_size, density, exponents) in &queries {
+            distributed.push_jobs(owner, query, query_size, density, exponents, chunk_size);
+        }
+    }
+
+    Ok(distributed)
+}
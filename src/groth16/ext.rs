@@ -1,5 +1,9 @@
-use super::{create_proof_batch_priority, create_random_proof_batch_priority};
-use super::{ParameterSource, Proof};
+use super::{
+    create_proof_batch_from_assignments_priority, create_proof_batch_priority,
+    create_proof_batch_with_shape_priority, create_random_proof_batch_from_assignments_priority,
+    create_random_proof_batch_priority, create_random_proof_batch_with_shape_priority,
+};
+use super::{CircuitShape, ParameterSource, Proof, ProvingAssignment};
 use crate::{Circuit, SynthesisError};
 use paired::Engine;
 use rand_core::RngCore;
@@ -115,3 +119,135 @@ where
 {
     create_random_proof_batch_priority::<E, C, R, P>(circuits, params, rng, true)
 }
+
+pub fn create_proof_with_shape<E, C, P: ParameterSource<E>>(
+    circuit: C,
+    shape: &CircuitShape<E>,
+    params: P,
+    r: E::Fr,
+    s: E::Fr,
+) -> Result<Proof<E>, SynthesisError>
+where
+    E: Engine,
+    C: Circuit<E> + Send,
+{
+    let proofs = create_proof_batch_with_shape_priority::<E, C, P>(
+        vec![circuit],
+        shape,
+        params,
+        vec![r],
+        vec![s],
+        false,
+    )?;
+    Ok(proofs.into_iter().next().unwrap())
+}
+
+pub fn create_random_proof_with_shape<E, C, R, P: ParameterSource<E>>(
+    circuit: C,
+    shape: &CircuitShape<E>,
+    params: P,
+    rng: &mut R,
+) -> Result<Proof<E>, SynthesisError>
+where
+    E: Engine,
+    C: Circuit<E> + Send,
+    R: RngCore,
+{
+    let proofs = create_random_proof_batch_with_shape_priority::<E, C, R, P>(
+        vec![circuit],
+        shape,
+        params,
+        rng,
+        false,
+    )?;
+    Ok(proofs.into_iter().next().unwrap())
+}
+
+pub fn create_proof_batch_with_shape<E, C, P: ParameterSource<E>>(
+    circuits: Vec<C>,
+    shape: &CircuitShape<E>,
+    params: P,
+    r: Vec<E::Fr>,
+    s: Vec<E::Fr>,
+) -> Result<Vec<Proof<E>>, SynthesisError>
+where
+    E: Engine,
+    C: Circuit<E> + Send,
+{
+    create_proof_batch_with_shape_priority::<E, C, P>(circuits, shape, params, r, s, false)
+}
+
+pub fn create_random_proof_batch_with_shape<E, C, R, P: ParameterSource<E>>(
+    circuits: Vec<C>,
+    shape: &CircuitShape<E>,
+    params: P,
+    rng: &mut R,
+) -> Result<Vec<Proof<E>>, SynthesisError>
+where
+    E: Engine,
+    C: Circuit<E> + Send,
+    R: RngCore,
+{
+    create_random_proof_batch_with_shape_priority::<E, C, R, P>(circuits, shape, params, rng, false)
+}
+
+pub fn create_proof_from_assignment<E, P: ParameterSource<E>>(
+    prover: ProvingAssignment<E>,
+    params: P,
+    r: E::Fr,
+    s: E::Fr,
+) -> Result<Proof<E>, SynthesisError>
+where
+    E: Engine,
+{
+    let proofs = create_proof_batch_from_assignments_priority::<E, P>(
+        vec![prover],
+        params,
+        vec![r],
+        vec![s],
+        false,
+    )?;
+    Ok(proofs.into_iter().next().unwrap())
+}
+
+pub fn create_random_proof_from_assignment<E, R, P: ParameterSource<E>>(
+    prover: ProvingAssignment<E>,
+    params: P,
+    rng: &mut R,
+) -> Result<Proof<E>, SynthesisError>
+where
+    E: Engine,
+    R: RngCore,
+{
+    let proofs = create_random_proof_batch_from_assignments_priority::<E, R, P>(
+        vec![prover],
+        params,
+        rng,
+        false,
+    )?;
+    Ok(proofs.into_iter().next().unwrap())
+}
+
+pub fn create_proof_batch_from_assignments<E, P: ParameterSource<E>>(
+    provers: Vec<ProvingAssignment<E>>,
+    params: P,
+    r: Vec<E::Fr>,
+    s: Vec<E::Fr>,
+) -> Result<Vec<Proof<E>>, SynthesisError>
+where
+    E: Engine,
+{
+    create_proof_batch_from_assignments_priority::<E, P>(provers, params, r, s, false)
+}
+
+pub fn create_random_proof_batch_from_assignments<E, R, P: ParameterSource<E>>(
+    provers: Vec<ProvingAssignment<E>>,
+    params: P,
+    rng: &mut R,
+) -> Result<Vec<Proof<E>>, SynthesisError>
+where
+    E: Engine,
+    R: RngCore,
+{
+    create_random_proof_batch_from_assignments_priority::<E, R, P>(provers, params, rng, false)
+}
@@ -8,9 +8,10 @@ use paired::Engine;
 
 use super::{Parameters, VerifyingKey};
 
+use crate::digest::ShapeHasher;
 use crate::{Circuit, ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
 
-use crate::domain::{EvaluationDomain, Scalar};
+use crate::domain::{EvaluationDomain, QapDomain, Scalar};
 
 use crate::multicore::Worker;
 
@@ -20,6 +21,21 @@ pub fn generate_random_parameters<E, C, R>(
     circuit: C,
     rng: &mut R,
 ) -> Result<Parameters<E>, SynthesisError>
+where
+    E: Engine,
+    C: Circuit<E>,
+    R: RngCore,
+{
+    generate_random_parameters_in(circuit, QapDomain::Bellperson, rng)
+}
+
+/// Like `generate_random_parameters`, but assigns the constraints of the
+/// circuit to the roots of unity of `domain`.
+pub fn generate_random_parameters_in<E, C, R>(
+    circuit: C,
+    domain: QapDomain,
+    rng: &mut R,
+) -> Result<Parameters<E>, SynthesisError>
 where
     E: Engine,
     C: Circuit<E>,
@@ -33,7 +49,7 @@ where
     let delta = E::Fr::random(rng);
     let tau = E::Fr::random(rng);
 
-    generate_parameters::<E, C>(circuit, g1, g2, alpha, beta, gamma, delta, tau)
+    generate_parameters_in::<E, C>(circuit, g1, g2, alpha, beta, gamma, delta, tau, domain)
 }
 
 /// This is our assembly structure that we'll use to synthesize the
@@ -48,6 +64,7 @@ struct KeypairAssembly<E: Engine> {
     at_aux: Vec<Vec<(E::Fr, usize)>>,
     bt_aux: Vec<Vec<(E::Fr, usize)>>,
     ct_aux: Vec<Vec<(E::Fr, usize)>>,
+    hasher: ShapeHasher<E>,
 }
 
 impl<E: Engine> ConstraintSystem<E> for KeypairAssembly<E> {
@@ -64,6 +81,7 @@ impl<E: Engine> ConstraintSystem<E> for KeypairAssembly<E> {
             at_aux: vec![],
             bt_aux: vec![],
             ct_aux: vec![],
+            hasher: ShapeHasher::new(),
         }
     }
 
@@ -88,6 +106,7 @@ impl<E: Engine> ConstraintSystem<E> for KeypairAssembly<E> {
 
         let index = self.num_aux;
         self.num_aux += 1;
+        self.hasher.alloc();
 
         self.at_aux.push(vec![]);
         self.bt_aux.push(vec![]);
@@ -107,6 +126,7 @@ impl<E: Engine> ConstraintSystem<E> for KeypairAssembly<E> {
 
         let index = self.num_inputs;
         self.num_inputs += 1;
+        self.hasher.alloc_input();
 
         self.at_inputs.push(vec![]);
         self.bt_inputs.push(vec![]);
@@ -137,20 +157,26 @@ impl<E: Engine> ConstraintSystem<E> for KeypairAssembly<E> {
             }
         }
 
+        let a = a(LinearCombination::zero());
+        let b = b(LinearCombination::zero());
+        let c = c(LinearCombination::zero());
+
+        self.hasher.enforce(&a, &b, &c);
+
         eval(
-            a(LinearCombination::zero()),
+            a,
             &mut self.at_inputs,
             &mut self.at_aux,
             self.num_constraints,
         );
         eval(
-            b(LinearCombination::zero()),
+            b,
             &mut self.bt_inputs,
             &mut self.bt_aux,
             self.num_constraints,
         );
         eval(
-            c(LinearCombination::zero()),
+            c,
             &mut self.ct_inputs,
             &mut self.ct_aux,
             self.num_constraints,
@@ -187,6 +213,37 @@ pub fn generate_parameters<E, C>(
     delta: E::Fr,
     tau: E::Fr,
 ) -> Result<Parameters<E>, SynthesisError>
+where
+    E: Engine,
+    C: Circuit<E>,
+{
+    generate_parameters_in::<E, C>(
+        circuit,
+        g1,
+        g2,
+        alpha,
+        beta,
+        gamma,
+        delta,
+        tau,
+        QapDomain::Bellperson,
+    )
+}
+
+/// Like `generate_parameters`, but assigns the constraints of the circuit to
+/// the roots of unity of `domain`.
+#[allow(clippy::too_many_arguments)]
+pub fn generate_parameters_in<E, C>(
+    circuit: C,
+    g1: E::G1,
+    g2: E::G2,
+    alpha: E::Fr,
+    beta: E::Fr,
+    gamma: E::Fr,
+    delta: E::Fr,
+    tau: E::Fr,
+    domain: QapDomain,
+) -> Result<Parameters<E>, SynthesisError>
 where
     E: Engine,
     C: Circuit<E>,
@@ -205,9 +262,11 @@ where
         assembly.enforce(|| "", |lc| lc + Variable(Index::Input(i)), |lc| lc, |lc| lc);
     }
 
+    let digest = assembly.hasher.finish();
+
     // Create bases for blind evaluation of polynomials at tau
     let powers_of_tau = vec![Scalar::<E>(E::Fr::zero()); assembly.num_constraints];
-    let mut powers_of_tau = EvaluationDomain::from_coeffs(powers_of_tau)?;
+    let mut powers_of_tau = EvaluationDomain::from_coeffs_in(powers_of_tau, domain)?;
 
     // Compute G1 window table
     let mut g1_wnaf = Wnaf::new();
@@ -462,31 +521,32 @@ where
         delta_g1: g1.mul(delta).into_affine(),
         delta_g2: g2.mul(delta).into_affine(),
         ic: ic.into_iter().map(|e| e.into_affine()).collect(),
+        digest: Some(digest),
+        domain,
     };
 
-    Ok(Parameters {
+    Ok(Parameters::new(
         vk,
-        h: Arc::new(h.into_iter().map(|e| e.into_affine()).collect()),
-        l: Arc::new(l.into_iter().map(|e| e.into_affine()).collect()),
-
+        Arc::new(h.into_iter().map(|e| e.into_affine()).collect()),
+        Arc::new(l.into_iter().map(|e| e.into_affine()).collect()),
         // Filter points at infinity away from A/B queries
-        a: Arc::new(
+        Arc::new(
             a.into_iter()
                 .filter(|e| !e.is_zero())
                 .map(|e| e.into_affine())
                 .collect(),
         ),
-        b_g1: Arc::new(
+        Arc::new(
             b_g1.into_iter()
                 .filter(|e| !e.is_zero())
                 .map(|e| e.into_affine())
                 .collect(),
         ),
-        b_g2: Arc::new(
+        Arc::new(
             b_g2.into_iter()
                 .filter(|e| !e.is_zero())
                 .map(|e| e.into_affine())
                 .collect(),
         ),
-    })
+    ))
 }
@@ -1,17 +1,19 @@
 use groupy::{CurveAffine, EncodedPoint};
 use paired::Engine;
 
+use crate::multiexp::QuerySource;
 use crate::SynthesisError;
 
 use memmap::Mmap;
 
 use std::fs::File;
-use std::io;
+use std::io::{self, Read, Write};
 use std::ops::Range;
 use std::path::PathBuf;
 use std::sync::Arc;
 
-use super::{ParameterSource, VerifyingKey};
+use super::params::{check_size, query_source};
+use super::{CircuitSize, ParameterSource, ParameterTables, VerifyingKey};
 
 pub struct MappedParameters<E: Engine> {
     /// The parameter file we're reading from.  
@@ -45,16 +47,118 @@ pub struct MappedParameters<E: Engine> {
     pub b_g2: Vec<Range<usize>>,
 
     pub checked: bool,
+
+    /// Fixed-base tables of the queries, if they were precomputed. Unlike the
+    /// queries, they are kept in memory. They are only set together with the
+    /// queries they were precomputed for, see `Parameters::precompute_tables`.
+    pub(super) tables: Option<ParameterTables<E>>,
+}
+
+/// The queries of mapped parameters, read into memory.
+pub(super) struct Queries<E: Engine> {
+    pub(super) h: Vec<E::G1Affine>,
+    pub(super) l: Vec<E::G1Affine>,
+    pub(super) a: Vec<E::G1Affine>,
+    pub(super) b_g1: Vec<E::G1Affine>,
+    pub(super) b_g2: Vec<E::G2Affine>,
+}
+
+impl<E: Engine> MappedParameters<E> {
+    /// The fixed-base tables of the queries, if they were precomputed with
+    /// `precompute_tables` or read with `read_tables`.
+    pub fn tables(&self) -> Option<&ParameterTables<E>> {
+        self.tables.as_ref()
+    }
+
+    /// Precomputes fixed-base tables of the queries, see
+    /// `Parameters::precompute_tables`.
+    pub fn precompute_tables(&mut self, window: u32) -> io::Result<()> {
+        let queries = self.read_queries()?;
+        self.tables = Some(ParameterTables::new(
+            &queries.h,
+            &queries.l,
+            &queries.a,
+            &queries.b_g1,
+            &queries.b_g2,
+            window,
+        ));
+
+        Ok(())
+    }
+
+    /// Writes the precomputed tables, to be stored alongside the parameters.
+    pub fn write_tables<W: Write>(&self, writer: W) -> io::Result<()> {
+        match self.tables {
+            Some(ref tables) => tables.write(writer),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no tables were precomputed",
+            )),
+        }
+    }
+
+    /// Reads tables written by `write_tables`, which must have been
+    /// precomputed for these parameters.
+    pub fn read_tables<R: Read>(&mut self, reader: R) -> io::Result<()> {
+        let tables = ParameterTables::read(reader, self.checked)?;
+        let queries = self.read_queries()?;
+        if !tables.are_tables_of(
+            &queries.h,
+            &queries.l,
+            &queries.a,
+            &queries.b_g1,
+            &queries.b_g2,
+        ) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "tables were precomputed for other parameters",
+            ));
+        }
+
+        self.tables = Some(tables);
+        Ok(())
+    }
+
+    pub(super) fn read_queries(&self) -> io::Result<Queries<E>> {
+        let read_g1s = |ranges: &[Range<usize>]| {
+            ranges
+                .iter()
+                .cloned()
+                .map(|range| read_g1::<E>(&self.params, range, self.checked))
+                .collect::<io::Result<Vec<_>>>()
+        };
+
+        Ok(Queries {
+            h: read_g1s(&self.h)?,
+            l: read_g1s(&self.l)?,
+            a: read_g1s(&self.a)?,
+            b_g1: read_g1s(&self.b_g1)?,
+            b_g2: self
+                .b_g2
+                .iter()
+                .cloned()
+                .map(|range| read_g2::<E>(&self.params, range, self.checked))
+                .collect::<io::Result<Vec<_>>>()?,
+        })
+    }
 }
 
 impl<'a, E: Engine> ParameterSource<E> for &'a MappedParameters<E> {
-    type G1Builder = (Arc<Vec<E::G1Affine>>, usize);
-    type G2Builder = (Arc<Vec<E::G2Affine>>, usize);
+    type G1Builder = QuerySource<E::G1Affine>;
+    type G2Builder = QuerySource<E::G2Affine>;
 
     fn get_vk(&self, _: usize) -> Result<&VerifyingKey<E>, SynthesisError> {
         Ok(&self.vk)
     }
 
+    fn has_digest(&self) -> bool {
+        self.vk.digest().is_some()
+    }
+
+    fn check_size(&self, size: &CircuitSize) -> Result<(), SynthesisError> {
+        check_size(&self.vk, self.h.len(), self.l.len(), size)
+    }
+
     fn get_h(&self, _num_h: usize) -> Result<Self::G1Builder, SynthesisError> {
         let builder = self
             .h
@@ -63,7 +167,8 @@ impl<'a, E: Engine> ParameterSource<E> for &'a MappedParameters<E> {
             .map(|h| read_g1::<E>(&self.params, h, self.checked))
             .collect::<Result<_, _>>()?;
 
-        Ok((Arc::new(builder), 0))
+        let table = self.tables.as_ref().map(|tables| &tables.h);
+        Ok(query_source(Arc::new(builder), 0, table))
     }
 
     fn get_l(&self, _num_l: usize) -> Result<Self::G1Builder, SynthesisError> {
@@ -74,7 +179,8 @@ impl<'a, E: Engine> ParameterSource<E> for &'a MappedParameters<E> {
             .map(|l| read_g1::<E>(&self.params, l, self.checked))
             .collect::<Result<_, _>>()?;
 
-        Ok((Arc::new(builder), 0))
+        let table = self.tables.as_ref().map(|tables| &tables.l);
+        Ok(query_source(Arc::new(builder), 0, table))
     }
 
     fn get_a(
@@ -90,8 +196,12 @@ impl<'a, E: Engine> ParameterSource<E> for &'a MappedParameters<E> {
             .collect::<Result<_, _>>()?;
 
         let builder: Arc<Vec<_>> = Arc::new(builder);
+        let table = self.tables.as_ref().map(|tables| &tables.a);
 
-        Ok(((builder.clone(), 0), (builder, num_inputs)))
+        Ok((
+            query_source(builder.clone(), 0, table),
+            query_source(builder, num_inputs, table),
+        ))
     }
 
     fn get_b_g1(
@@ -107,8 +217,12 @@ impl<'a, E: Engine> ParameterSource<E> for &'a MappedParameters<E> {
             .collect::<Result<_, _>>()?;
 
         let builder: Arc<Vec<_>> = Arc::new(builder);
+        let table = self.tables.as_ref().map(|tables| &tables.b_g1);
 
-        Ok(((builder.clone(), 0), (builder, num_inputs)))
+        Ok((
+            query_source(builder.clone(), 0, table),
+            query_source(builder, num_inputs, table),
+        ))
     }
 
     fn get_b_g2(
@@ -124,8 +238,63 @@ impl<'a, E: Engine> ParameterSource<E> for &'a MappedParameters<E> {
             .collect::<Result<_, _>>()?;
 
         let builder: Arc<Vec<_>> = Arc::new(builder);
+        let table = self.tables.as_ref().map(|tables| &tables.b_g2);
+
+        Ok((
+            query_source(builder.clone(), 0, table),
+            query_source(builder, num_inputs, table),
+        ))
+    }
+}
+
+/// Shared parameters, which can be moved into the provers which run in the
+/// background, such as `create_proof_async`.
+impl<E: Engine> ParameterSource<E> for Arc<MappedParameters<E>> {
+    type G1Builder = QuerySource<E::G1Affine>;
+    type G2Builder = QuerySource<E::G2Affine>;
 
-        Ok(((builder.clone(), 0), (builder, num_inputs)))
+    fn get_vk(&self, _: usize) -> Result<&VerifyingKey<E>, SynthesisError> {
+        Ok(&self.vk)
+    }
+
+    fn has_digest(&self) -> bool {
+        self.vk.digest().is_some()
+    }
+
+    fn check_size(&self, size: &CircuitSize) -> Result<(), SynthesisError> {
+        (&**self).check_size(size)
+    }
+
+    fn get_h(&self, num_h: usize) -> Result<Self::G1Builder, SynthesisError> {
+        (&**self).get_h(num_h)
+    }
+
+    fn get_l(&self, num_l: usize) -> Result<Self::G1Builder, SynthesisError> {
+        (&**self).get_l(num_l)
+    }
+
+    fn get_a(
+        &self,
+        num_inputs: usize,
+        num_aux: usize,
+    ) -> Result<(Self::G1Builder, Self::G1Builder), SynthesisError> {
+        (&**self).get_a(num_inputs, num_aux)
+    }
+
+    fn get_b_g1(
+        &self,
+        num_inputs: usize,
+        num_aux: usize,
+    ) -> Result<(Self::G1Builder, Self::G1Builder), SynthesisError> {
+        (&**self).get_b_g1(num_inputs, num_aux)
+    }
+
+    fn get_b_g2(
+        &self,
+        num_inputs: usize,
+        num_aux: usize,
+    ) -> Result<(Self::G2Builder, Self::G2Builder), SynthesisError> {
+        (&**self).get_b_g2(num_inputs, num_aux)
     }
 }
 
@@ -10,18 +10,37 @@ use std::io::{self, Read, Write};
 #[cfg(test)]
 mod tests;
 
+pub mod aggregate;
+
+mod async_prover;
+mod distributed;
 mod ext;
 mod generator;
 mod mapped_params;
+mod observer;
 mod params;
 mod prover;
+mod rerandomize;
+mod shape;
+#[cfg(feature = "snarkjs")]
+mod snarkjs;
+mod solidity;
 mod verifier;
 mod verifying_key;
+mod zkey;
 
+pub use self::async_prover::*;
+pub use self::distributed::*;
 pub use self::ext::*;
 pub use self::generator::*;
 pub use self::mapped_params::*;
+pub use self::observer::{PhaseReport, ProverDevice, ProverObserver, ProverPhase};
 pub use self::prover::*;
+pub use self::rerandomize::*;
+pub use self::shape::CircuitShape;
+#[cfg(feature = "snarkjs")]
+pub use self::snarkjs::*;
+pub use self::solidity::*;
 pub use self::verifier::*;
 pub use self::verifying_key::*;
 pub use params::*;
@@ -104,6 +123,7 @@ impl<E: Engine> Proof<E> {
 #[cfg(test)]
 mod test_with_bls12_381 {
     use super::*;
+    use crate::domain::QapDomain;
     use crate::{Circuit, ConstraintSystem, SynthesisError};
 
     use ff::Field;
@@ -151,15 +171,79 @@ mod test_with_bls12_381 {
             let mut v = vec![];
 
             params.write(&mut v).unwrap();
-            assert_eq!(v.len(), 2136);
+            // The header of the key is followed by the digest.
+            assert_eq!(v.len(), 12 + 32 + 2136);
 
             let de_params = Parameters::read(&v[..], true).unwrap();
             assert!(params == de_params);
+            assert!(de_params.vk.digest().is_some());
 
             let de_params = Parameters::read(&v[..], false).unwrap();
             assert!(params == de_params);
         }
 
+        {
+            // Parameters without a digest keep the original format.
+            let mut legacy = params.clone();
+            legacy.vk.digest = None;
+
+            let mut v = vec![];
+            legacy.write(&mut v).unwrap();
+            assert_eq!(v.len(), 2136);
+
+            let de_params = Parameters::read(&v[..], true).unwrap();
+            assert!(legacy == de_params);
+            assert!(params != de_params);
+        }
+
+        {
+            // The roots of unity of snarkjs are flagged in the header, which
+            // then has no digest.
+            let mut snarkjs = params.clone();
+            snarkjs.vk.digest = None;
+            snarkjs.vk.domain = QapDomain::Snarkjs;
+
+            let mut v = vec![];
+            snarkjs.write(&mut v).unwrap();
+            assert_eq!(v.len(), 12 + 2136);
+
+            let de_params = Parameters::read(&v[..], true).unwrap();
+            assert!(snarkjs == de_params);
+            assert_eq!(de_params.vk.domain(), QapDomain::Snarkjs);
+        }
+
+        {
+            let mut v = vec![];
+            params.vk.write(&mut v).unwrap();
+
+            // Keys of another version of the format are rejected.
+            let mut other_version = v.clone();
+            other_version[7] += 1;
+            assert!(VerifyingKey::<Bls12>::read(&other_version[..]).is_err());
+
+            // Mapped keys are read the same way, and must not be truncated.
+            let path = std::env::temp_dir().join(format!("bellperson-vk-{}", std::process::id()));
+            let map = |bytes: &[u8]| {
+                std::fs::write(&path, bytes).unwrap();
+                let file = std::fs::File::open(&path).unwrap();
+                unsafe { memmap::Mmap::map(&file) }.unwrap()
+            };
+
+            let mut offset = 0;
+            let vk = VerifyingKey::<Bls12>::read_mmap(&map(&v), &mut offset).unwrap();
+            assert!(vk == params.vk);
+            assert_eq!(offset, v.len());
+
+            for &len in &[20, v.len() - 1] {
+                let mut offset = 0;
+                match VerifyingKey::<Bls12>::read_mmap(&map(&v[..len]), &mut offset) {
+                    Err(e) => assert_eq!(e.kind(), std::io::ErrorKind::UnexpectedEof),
+                    Ok(_) => panic!("read a truncated key"),
+                }
+            }
+            std::fs::remove_file(&path).unwrap();
+        }
+
         let pvk = prepare_verifying_key::<Bls12>(&params.vk);
 
         for _ in 0..100 {
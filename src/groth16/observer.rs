@@ -0,0 +1,61 @@
+use std::time::Duration;
+
+/// A phase of proof generation, as reported to a `ProverObserver`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ProverPhase {
+    /// Synthesis of the circuits into their assignments.
+    Synthesis,
+    /// The FFTs over the evaluations of the A, B and C polynomials, which
+    /// compute the coefficients of H.
+    Fft,
+    /// The multiexp of the H query.
+    HMultiexp,
+    /// The multiexp of the L query.
+    LMultiexp,
+    /// The multiexps of the A and B queries.
+    AbMultiexp,
+}
+
+/// Where a phase of proof generation was computed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ProverDevice {
+    Cpu,
+    /// At least part of the phase ran on a GPU.
+    Gpu,
+}
+
+/// The report of a finished phase of proof generation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PhaseReport {
+    pub phase: ProverPhase,
+    /// The number of proofs the phase was run for.
+    pub num_proofs: usize,
+    /// The size of the phase for a single proof: the number of constraints
+    /// for synthesis (the largest one, if the circuits differ), the size of
+    /// the evaluation domain for the FFTs and the number of exponents for the
+    /// multiexps.
+    pub size: usize,
+    pub device: ProverDevice,
+    pub elapsed: Duration,
+}
+
+/// Hooks called around each phase of proof generation, for example to show
+/// progress or to record timings.
+///
+/// The phases run one after the other, except for the multiexps, which are
+/// all started before any of them is waited for. Each phase is reported once
+/// all of its work has finished, in the order the phases were started, and
+/// its elapsed time runs until its last job finished. A phase which fails is
+/// not reported as finished. A batch of circuits with different sizes is
+/// proven in groups of circuits with the same size, which report their phases
+/// one after the other.
+pub trait ProverObserver: Sync {
+    fn phase_started(&self, _phase: ProverPhase) {}
+
+    fn phase_finished(&self, _report: &PhaseReport) {}
+}
+
+/// The observer of the provers which do not take one.
+pub(super) struct NoObserver;
+
+impl ProverObserver for NoObserver {}
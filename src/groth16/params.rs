@@ -1,10 +1,11 @@
 use groupy::{CurveAffine, EncodedPoint};
 use paired::Engine;
 
-use crate::multiexp::SourceBuilder;
+use crate::multiexp::{FixedBaseTable, QuerySource, SourceBuilder};
 use crate::SynthesisError;
 
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use log::warn;
 use memmap::{Mmap, MmapOptions};
 use std::fs::File;
 use std::io::{self, Read, Write};
@@ -38,6 +39,12 @@ pub struct Parameters<E: Engine> {
     // infinity for the same reason as the "A" polynomials.
     pub b_g1: Arc<Vec<E::G1Affine>>,
     pub b_g2: Arc<Vec<E::G2Affine>>,
+
+    // Fixed-base tables of the queries, if they were precomputed. They are
+    // only set together with the queries they were precomputed for, but the
+    // queries can be replaced afterwards, so each table is checked against
+    // its query before it is used.
+    tables: Option<ParameterTables<E>>,
 }
 
 impl<E: Engine> PartialEq for Parameters<E> {
@@ -52,6 +59,32 @@ impl<E: Engine> PartialEq for Parameters<E> {
 }
 
 impl<E: Engine> Parameters<E> {
+    /// Parameters with the given verifying key and queries, without tables.
+    pub fn new(
+        vk: VerifyingKey<E>,
+        h: Arc<Vec<E::G1Affine>>,
+        l: Arc<Vec<E::G1Affine>>,
+        a: Arc<Vec<E::G1Affine>>,
+        b_g1: Arc<Vec<E::G1Affine>>,
+        b_g2: Arc<Vec<E::G2Affine>>,
+    ) -> Self {
+        Parameters {
+            vk,
+            h,
+            l,
+            a,
+            b_g1,
+            b_g2,
+            tables: None,
+        }
+    }
+
+    /// The fixed-base tables of the queries, if they were precomputed with
+    /// `precompute_tables` or read with `read_tables`.
+    pub fn tables(&self) -> Option<&ParameterTables<E>> {
+        self.tables.as_ref()
+    }
+
     pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
         self.vk.write(&mut writer)?;
 
@@ -83,6 +116,47 @@ impl<E: Engine> Parameters<E> {
         Ok(())
     }
 
+    /// Precomputes fixed-base tables of the queries for windows of `window`
+    /// bits, which the prover then uses for its multiexps on the CPU instead
+    /// of the bases. The tables take `ceil(NUM_BITS / window)` times the
+    /// memory of the queries.
+    ///
+    /// The tables are not updated when the queries are replaced: the prover
+    /// then ignores the table of a query if its number of bases, or its first
+    /// or last base, changed. The tables must be precomputed again after
+    /// changing the bases of a query in any other way.
+    pub fn precompute_tables(&mut self, window: u32) {
+        self.tables = Some(ParameterTables::new(
+            &self.h, &self.l, &self.a, &self.b_g1, &self.b_g2, window,
+        ));
+    }
+
+    /// Writes the precomputed tables, to be stored alongside the parameters.
+    pub fn write_tables<W: Write>(&self, writer: W) -> io::Result<()> {
+        match self.tables {
+            Some(ref tables) => tables.write(writer),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no tables were precomputed",
+            )),
+        }
+    }
+
+    /// Reads tables written by `write_tables`, which must have been
+    /// precomputed for these parameters.
+    pub fn read_tables<R: Read>(&mut self, reader: R, checked: bool) -> io::Result<()> {
+        let tables = ParameterTables::read(reader, checked)?;
+        if !tables.are_tables_of(&self.h, &self.l, &self.a, &self.b_g1, &self.b_g2) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "tables were precomputed for other parameters",
+            ));
+        }
+
+        self.tables = Some(tables);
+        Ok(())
+    }
+
     // Quickly iterates through the parameter file, recording all
     // parameter offsets and caches the verifying key (vk) for quick
     // access via reference.
@@ -150,6 +224,7 @@ impl<E: Engine> Parameters<E> {
             b_g1,
             b_g2,
             checked,
+            tables: None,
         })
     }
 
@@ -274,6 +349,7 @@ impl<E: Engine> Parameters<E> {
             a: Arc::new(a),
             b_g1: Arc::new(b_g1),
             b_g2: Arc::new(b_g2),
+            tables: None,
         })
     }
 
@@ -372,10 +448,132 @@ impl<E: Engine> Parameters<E> {
             a: Arc::new(a),
             b_g1: Arc::new(b_g1),
             b_g2: Arc::new(b_g2),
+            tables: None,
         })
     }
 }
 
+/// Fixed-base tables of the queries of a set of parameters, see
+/// `Parameters::precompute_tables`.
+#[derive(Clone)]
+pub struct ParameterTables<E: Engine> {
+    pub(super) h: Arc<FixedBaseTable<E::G1Affine>>,
+    pub(super) l: Arc<FixedBaseTable<E::G1Affine>>,
+    pub(super) a: Arc<FixedBaseTable<E::G1Affine>>,
+    pub(super) b_g1: Arc<FixedBaseTable<E::G1Affine>>,
+    pub(super) b_g2: Arc<FixedBaseTable<E::G2Affine>>,
+}
+
+impl<E: Engine> ParameterTables<E> {
+    /// Builds the tables of the queries for windows of `window` bits.
+    pub fn new(
+        h: &[E::G1Affine],
+        l: &[E::G1Affine],
+        a: &[E::G1Affine],
+        b_g1: &[E::G1Affine],
+        b_g2: &[E::G2Affine],
+        window: u32,
+    ) -> Self {
+        ParameterTables {
+            h: Arc::new(FixedBaseTable::new(h, window)),
+            l: Arc::new(FixedBaseTable::new(l, window)),
+            a: Arc::new(FixedBaseTable::new(a, window)),
+            b_g1: Arc::new(FixedBaseTable::new(b_g1, window)),
+            b_g2: Arc::new(FixedBaseTable::new(b_g2, window)),
+        }
+    }
+
+    /// Whether these are the tables of the given queries.
+    pub fn are_tables_of(
+        &self,
+        h: &[E::G1Affine],
+        l: &[E::G1Affine],
+        a: &[E::G1Affine],
+        b_g1: &[E::G1Affine],
+        b_g2: &[E::G2Affine],
+    ) -> bool {
+        self.h.is_table_of(h)
+            && self.l.is_table_of(l)
+            && self.a.is_table_of(a)
+            && self.b_g1.is_table_of(b_g1)
+            && self.b_g2.is_table_of(b_g2)
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        self.h.write(&mut writer)?;
+        self.l.write(&mut writer)?;
+        self.a.write(&mut writer)?;
+        self.b_g1.write(&mut writer)?;
+        self.b_g2.write(&mut writer)
+    }
+
+    pub fn read<R: Read>(mut reader: R, checked: bool) -> io::Result<Self> {
+        Ok(ParameterTables {
+            h: Arc::new(FixedBaseTable::read(&mut reader, checked)?),
+            l: Arc::new(FixedBaseTable::read(&mut reader, checked)?),
+            a: Arc::new(FixedBaseTable::read(&mut reader, checked)?),
+            b_g1: Arc::new(FixedBaseTable::read(&mut reader, checked)?),
+            b_g2: Arc::new(FixedBaseTable::read(&mut reader, checked)?),
+        })
+    }
+}
+
+/// The source of the bases of a query from the `skip`th one on, with the
+/// table of the query if one was precomputed. A table which does not match
+/// the bases, because the query was replaced after precomputing it, is not
+/// used.
+pub(super) fn query_source<G: CurveAffine>(
+    bases: Arc<Vec<G>>,
+    skip: usize,
+    table: Option<&Arc<FixedBaseTable<G>>>,
+) -> QuerySource<G> {
+    let table = table.filter(|table| {
+        let matches = table.matches(&bases);
+        if !matches {
+            warn!("Ignoring a fixed-base table which does not match its query");
+        }
+        matches
+    });
+
+    QuerySource {
+        bases,
+        skip,
+        table: table.cloned(),
+    }
+}
+
+/// The size of the circuits which are proven together in a batch. Circuits
+/// of different sizes are proven in separate groups, each with the
+/// parameters for its size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CircuitSize {
+    /// The size of the evaluation domain of the constraints.
+    pub domain_size: usize,
+    /// The number of inputs, including the one input.
+    pub num_inputs: usize,
+    pub num_aux: usize,
+}
+
+/// Fails with `SynthesisError::ParameterSizeMismatch` if parameters with the
+/// verifying key `vk`, and `num_h` and `num_l` bases in the H and L queries,
+/// are not for circuits of `size`.
+pub(super) fn check_size<E: Engine>(
+    vk: &VerifyingKey<E>,
+    num_h: usize,
+    num_l: usize,
+    size: &CircuitSize,
+) -> Result<(), SynthesisError> {
+    if vk.ic.len() != size.num_inputs || num_l != size.num_aux || num_h + 1 != size.domain_size {
+        return Err(SynthesisError::ParameterSizeMismatch {
+            domain_size: size.domain_size,
+            num_inputs: size.num_inputs,
+            num_aux: size.num_aux,
+        });
+    }
+
+    Ok(())
+}
+
 pub trait ParameterSource<E: Engine>: Send + Sync {
     type G1Builder: SourceBuilder<E::G1Affine>;
     type G2Builder: SourceBuilder<E::G2Affine>;
@@ -398,22 +596,47 @@ pub trait ParameterSource<E: Engine>: Send + Sync {
         num_inputs: usize,
         num_aux: usize,
     ) -> Result<(Self::G2Builder, Self::G2Builder), SynthesisError>;
+
+    /// Whether the verifying keys carry the digest of the circuit they were
+    /// generated for. When asked to check them with
+    /// `ProverOptions::check_digest`, the prover only computes the digests of
+    /// the circuits it proves if this is the case.
+    fn has_digest(&self) -> bool {
+        true
+    }
+
+    /// Checks that these parameters are for circuits of `size`, before the
+    /// prover fetches anything from them for a group of circuits of that
+    /// size. Sources which can not tell accept every size.
+    fn check_size(&self, _size: &CircuitSize) -> Result<(), SynthesisError> {
+        Ok(())
+    }
 }
 
 impl<'a, E: Engine> ParameterSource<E> for &'a Parameters<E> {
-    type G1Builder = (Arc<Vec<E::G1Affine>>, usize);
-    type G2Builder = (Arc<Vec<E::G2Affine>>, usize);
+    type G1Builder = QuerySource<E::G1Affine>;
+    type G2Builder = QuerySource<E::G2Affine>;
 
     fn get_vk(&self, _: usize) -> Result<&VerifyingKey<E>, SynthesisError> {
         Ok(&self.vk)
     }
 
+    fn has_digest(&self) -> bool {
+        self.vk.digest().is_some()
+    }
+
+    fn check_size(&self, size: &CircuitSize) -> Result<(), SynthesisError> {
+        check_size(&self.vk, self.h.len(), self.l.len(), size)
+    }
+
     fn get_h(&self, _: usize) -> Result<Self::G1Builder, SynthesisError> {
-        Ok((self.h.clone(), 0))
+        let table = self.tables.as_ref().map(|tables| &tables.h);
+        Ok(query_source(self.h.clone(), 0, table))
     }
 
     fn get_l(&self, _: usize) -> Result<Self::G1Builder, SynthesisError> {
-        Ok((self.l.clone(), 0))
+        let table = self.tables.as_ref().map(|tables| &tables.l);
+        Ok(query_source(self.l.clone(), 0, table))
     }
 
     fn get_a(
@@ -421,7 +644,11 @@ impl<'a, E: Engine> ParameterSource<E> for &'a Parameters<E> {
         num_inputs: usize,
         _: usize,
     ) -> Result<(Self::G1Builder, Self::G1Builder), SynthesisError> {
-        Ok(((self.a.clone(), 0), (self.a.clone(), num_inputs)))
+        let table = self.tables.as_ref().map(|tables| &tables.a);
+        Ok((
+            query_source(self.a.clone(), 0, table),
+            query_source(self.a.clone(), num_inputs, table),
+        ))
     }
 
     fn get_b_g1(
@@ -429,7 +656,11 @@ impl<'a, E: Engine> ParameterSource<E> for &'a Parameters<E> {
         num_inputs: usize,
         _: usize,
     ) -> Result<(Self::G1Builder, Self::G1Builder), SynthesisError> {
-        Ok(((self.b_g1.clone(), 0), (self.b_g1.clone(), num_inputs)))
+        let table = self.tables.as_ref().map(|tables| &tables.b_g1);
+        Ok((
+            query_source(self.b_g1.clone(), 0, table),
+            query_source(self.b_g1.clone(), num_inputs, table),
+        ))
     }
 
     fn get_b_g2(
@@ -437,6 +668,61 @@ impl<'a, E: Engine> ParameterSource<E> for &'a Parameters<E> {
         num_inputs: usize,
         _: usize,
     ) -> Result<(Self::G2Builder, Self::G2Builder), SynthesisError> {
-        Ok(((self.b_g2.clone(), 0), (self.b_g2.clone(), num_inputs)))
+        let table = self.tables.as_ref().map(|tables| &tables.b_g2);
+        Ok((
+            query_source(self.b_g2.clone(), 0, table),
+            query_source(self.b_g2.clone(), num_inputs, table),
+        ))
+    }
+}
+
+/// Shared parameters, which can be moved into the provers which run in the
+/// background, such as `create_proof_async`.
+impl<E: Engine> ParameterSource<E> for Arc<Parameters<E>> {
+    type G1Builder = QuerySource<E::G1Affine>;
+    type G2Builder = QuerySource<E::G2Affine>;
+
+    fn get_vk(&self, _: usize) -> Result<&VerifyingKey<E>, SynthesisError> {
+        Ok(&self.vk)
+    }
+
+    fn has_digest(&self) -> bool {
+        self.vk.digest().is_some()
+    }
+
+    fn check_size(&self, size: &CircuitSize) -> Result<(), SynthesisError> {
+        (&**self).check_size(size)
+    }
+
+    fn get_h(&self, num_h: usize) -> Result<Self::G1Builder, SynthesisError> {
+        (&**self).get_h(num_h)
+    }
+
+    fn get_l(&self, num_l: usize) -> Result<Self::G1Builder, SynthesisError> {
+        (&**self).get_l(num_l)
+    }
+
+    fn get_a(
+        &self,
+        num_inputs: usize,
+        num_aux: usize,
+    ) -> Result<(Self::G1Builder, Self::G1Builder), SynthesisError> {
+        (&**self).get_a(num_inputs, num_aux)
+    }
+
+    fn get_b_g1(
+        &self,
+        num_inputs: usize,
+        num_aux: usize,
+    ) -> Result<(Self::G1Builder, Self::G1Builder), SynthesisError> {
+        (&**self).get_b_g1(num_inputs, num_aux)
+    }
+
+    fn get_b_g2(
+        &self,
+        num_inputs: usize,
+        num_aux: usize,
+    ) -> Result<(Self::G2Builder, Self::G2Builder), SynthesisError> {
+        (&**self).get_b_g2(num_inputs, num_aux)
     }
 }
@@ -1,21 +1,32 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use ff::{Field, PrimeField};
-use futures::Future;
+use bit_vec::BitVec;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use ff::{Field, PrimeField, PrimeFieldRepr};
 use groupy::{CurveAffine, CurveProjective};
 use paired::Engine;
 use rand_core::RngCore;
 use rayon::prelude::*;
 
-use super::{ParameterSource, Proof};
-use crate::domain::{EvaluationDomain, Scalar};
+use super::observer::NoObserver;
+use super::{
+    prepare_verifying_key, verify_proof, CircuitShape, CircuitSize, ParameterSource, PhaseReport,
+    Proof, ProverDevice, ProverObserver, ProverPhase, VerifyingKey,
+};
+use crate::digest::{CircuitDigest, ShapeHasher};
+use crate::domain::{EvaluationDomain, QapDomain, Scalar};
 use crate::gpu::{LockedFFTKernel, LockedMultiexpKernel};
-use crate::multicore::{Worker, THREAD_POOL};
+use crate::multicore::{CancellationToken, Worker, WorkerFuture, THREAD_POOL};
 use crate::multiexp::{multiexp, DensityTracker, FullDensity};
+use crate::util_cs::witness_cs::WitnessCS;
 use crate::{
     Circuit, ConstraintSystem, Index, LinearCombination, SynthesisError, Variable, BELLMAN_VERSION,
 };
-use log::info;
+use log::{debug, info, warn};
 
 #[cfg(feature = "gpu")]
 use crate::gpu::PriorityLock;
@@ -29,7 +40,7 @@ fn eval<E: Engine>(
 ) -> E::Fr {
     let mut acc = E::Fr::zero();
 
-    for (&index, &coeff) in lc.0.iter() {
+    for (&index, &coeff) in lc.iter() {
         let mut tmp;
 
         match index {
@@ -58,7 +69,19 @@ fn eval<E: Engine>(
     acc
 }
 
-struct ProvingAssignment<E: Engine> {
+/// The bytes every checkpoint written by `ProvingAssignment::write` starts with.
+const CHECKPOINT_MAGIC: [u8; 4] = *b"BPCK";
+
+/// The version of the checkpoint format written by `ProvingAssignment::write`.
+/// Version 1 always carried the state of the digest, which is optional since
+/// version 2.
+const CHECKPOINT_VERSION: u32 = 2;
+
+/// The synthesized witness of a circuit, with the evaluations of its
+/// constraints. This is everything the prover needs besides the parameters,
+/// so it can be written to disk after synthesis and proven later.
+#[derive(Clone)]
+pub struct ProvingAssignment<E: Engine> {
     // Density of queries
     a_aux_density: DensityTracker,
     b_input_density: DensityTracker,
@@ -72,9 +95,220 @@ struct ProvingAssignment<E: Engine> {
     // Assignments of variables
     input_assignment: Vec<E::Fr>,
     aux_assignment: Vec<E::Fr>,
+
+    // Only present if the digest of the circuit is computed.
+    hasher: Option<ShapeHasher<E>>,
 }
 use std::fmt;
 
+impl<E: Engine> ProvingAssignment<E> {
+    /// Synthesizes `circuit`, including the input constraints added by the prover,
+    /// and computes its digest.
+    pub fn synthesize<C: Circuit<E>>(circuit: C) -> Result<Self, SynthesisError> {
+        Self::synthesize_with_digest(circuit, true)
+    }
+
+    /// Like `synthesize`, but only computes the digest of the circuit if
+    /// `with_digest` is set, as hashing every constraint slows down
+    /// synthesis.
+    pub(super) fn synthesize_with_digest<C: Circuit<E>>(
+        circuit: C,
+        with_digest: bool,
+    ) -> Result<Self, SynthesisError> {
+        let mut prover = ProvingAssignment::new();
+        if with_digest {
+            prover.hasher = Some(ShapeHasher::new());
+        }
+
+        prover.alloc_input(|| "", || Ok(E::Fr::one()))?;
+
+        circuit.synthesize(&mut prover)?;
+
+        for i in 0..prover.input_assignment.len() {
+            prover.enforce(|| "", |lc| lc + Variable(Index::Input(i)), |lc| lc, |lc| lc);
+        }
+
+        Ok(prover)
+    }
+
+    pub fn num_constraints(&self) -> usize {
+        self.a.len()
+    }
+
+    /// The number of public inputs, including the "one" input.
+    pub fn num_inputs(&self) -> usize {
+        self.input_assignment.len()
+    }
+
+    /// The size of the circuit, which selects the parameters it is proven
+    /// with.
+    pub fn size(&self) -> CircuitSize {
+        CircuitSize {
+            domain_size: self.a.len().next_power_of_two(),
+            num_inputs: self.num_inputs(),
+            num_aux: self.aux_assignment.len(),
+        }
+    }
+
+    /// The digest of the synthesized constraint system, which must match the
+    /// digest of the parameters it is proven with. This is `None` if the
+    /// digest was not computed while synthesizing.
+    pub fn digest(&self) -> Option<CircuitDigest> {
+        self.hasher.as_ref().map(ShapeHasher::finish)
+    }
+
+    /// Writes the assignment as a checkpoint, which starts with a magic and
+    /// the version of its format.
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        fn write_len<W: Write>(writer: &mut W, len: usize) -> io::Result<()> {
+            let len = u32::try_from(len).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "the assignment is too large for a checkpoint",
+                )
+            })?;
+            writer.write_u32::<BigEndian>(len)
+        }
+
+        fn write_frs<E: Engine, W: Write>(writer: &mut W, frs: &[E::Fr]) -> io::Result<()> {
+            write_len(writer, frs.len())?;
+            for fr in frs {
+                fr.into_repr().write_be(&mut *writer)?;
+            }
+            Ok(())
+        }
+
+        fn write_density<W: Write>(writer: &mut W, density: &DensityTracker) -> io::Result<()> {
+            write_len(writer, density.bv.len())?;
+            writer.write_all(&density.bv.to_bytes())
+        }
+
+        writer.write_all(&CHECKPOINT_MAGIC)?;
+        writer.write_u32::<BigEndian>(CHECKPOINT_VERSION)?;
+
+        write_frs::<E, _>(&mut writer, &self.input_assignment)?;
+        write_frs::<E, _>(&mut writer, &self.aux_assignment)?;
+
+        for evaluations in &[&self.a, &self.b, &self.c] {
+            write_len(&mut writer, evaluations.len())?;
+            for s in evaluations.iter() {
+                s.0.into_repr().write_be(&mut writer)?;
+            }
+        }
+
+        write_density(&mut writer, &self.a_aux_density)?;
+        write_density(&mut writer, &self.b_input_density)?;
+        write_density(&mut writer, &self.b_aux_density)?;
+
+        match &self.hasher {
+            Some(hasher) => {
+                writer.write_u8(1)?;
+                hasher.write(&mut writer)
+            }
+            None => writer.write_u8(0),
+        }
+    }
+
+    /// Reads a checkpoint written by `write`. Checkpoints of an unknown
+    /// format version are rejected.
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        fn read_frs<E: Engine, R: Read>(reader: &mut R) -> io::Result<Vec<E::Fr>> {
+            let len = reader.read_u32::<BigEndian>()? as usize;
+            // The length is not trusted to preallocate, as the input may be truncated.
+            let mut frs = Vec::new();
+            for _ in 0..len {
+                let mut repr = <E::Fr as PrimeField>::Repr::default();
+                repr.read_be(&mut *reader)?;
+                frs.push(
+                    E::Fr::from_repr(repr)
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+                );
+            }
+            Ok(frs)
+        }
+
+        fn read_density<R: Read>(reader: &mut R, len: usize) -> io::Result<DensityTracker> {
+            if reader.read_u32::<BigEndian>()? as usize != len {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "density length does not match the assignment",
+                ));
+            }
+
+            let mut bytes = vec![0u8; (len + 7) / 8];
+            reader.read_exact(&mut bytes)?;
+            let mut bv = BitVec::from_bytes(&bytes);
+            bv.truncate(len);
+
+            let total_density = bv.iter().filter(|b| *b).count();
+            Ok(DensityTracker { bv, total_density })
+        }
+
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != CHECKPOINT_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a proving assignment checkpoint",
+            ));
+        }
+        let version = reader.read_u32::<BigEndian>()?;
+        if version == 0 || version > CHECKPOINT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported checkpoint version {}", version),
+            ));
+        }
+
+        let input_assignment = read_frs::<E, _>(&mut reader)?;
+        let aux_assignment = read_frs::<E, _>(&mut reader)?;
+
+        let a = read_frs::<E, _>(&mut reader)?;
+        let b = read_frs::<E, _>(&mut reader)?;
+        let c = read_frs::<E, _>(&mut reader)?;
+        if a.len() != b.len() || a.len() != c.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "evaluations of A, B and C differ in length",
+            ));
+        }
+
+        let a_aux_density = read_density(&mut reader, aux_assignment.len())?;
+        let b_input_density = read_density(&mut reader, input_assignment.len())?;
+        let b_aux_density = read_density(&mut reader, aux_assignment.len())?;
+
+        // The first version always carried the state of the digest.
+        let has_hasher = version == 1 || reader.read_u8()? != 0;
+        let hasher = if has_hasher {
+            let hasher = ShapeHasher::read(&mut reader)?;
+            if hasher.num_inputs() != input_assignment.len()
+                || hasher.num_aux() != aux_assignment.len()
+                || hasher.num_constraints() != a.len()
+            {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "digest state does not match the assignment",
+                ));
+            }
+            Some(hasher)
+        } else {
+            None
+        };
+
+        Ok(ProvingAssignment {
+            a_aux_density,
+            b_input_density,
+            b_aux_density,
+            a: a.into_iter().map(Scalar).collect(),
+            b: b.into_iter().map(Scalar).collect(),
+            c: c.into_iter().map(Scalar).collect(),
+            input_assignment,
+            aux_assignment,
+            hasher,
+        })
+    }
+}
+
 impl<E: Engine> fmt::Debug for ProvingAssignment<E> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         fmt.debug_struct("ProvingAssignment")
@@ -107,6 +341,7 @@ impl<E: Engine> fmt::Debug for ProvingAssignment<E> {
             )
             .field("input_assignment", &self.input_assignment)
             .field("aux_assignment", &self.aux_assignment)
+            .field("digest", &self.digest())
             .finish()
     }
 }
@@ -121,12 +356,16 @@ impl<E: Engine> PartialEq for ProvingAssignment<E> {
             && self.c == other.c
             && self.input_assignment == other.input_assignment
             && self.aux_assignment == other.aux_assignment
+            && self.digest() == other.digest()
     }
 }
 
 impl<E: Engine> ConstraintSystem<E> for ProvingAssignment<E> {
     type Root = Self;
 
+    /// The digest is not computed, as this is also how the components of a
+    /// circuit synthesized in parallel are created. An assignment extended
+    /// with such a component has no digest anymore.
     fn new() -> Self {
         Self {
             a_aux_density: DensityTracker::new(),
@@ -137,6 +376,7 @@ impl<E: Engine> ConstraintSystem<E> for ProvingAssignment<E> {
             c: vec![],
             input_assignment: vec![],
             aux_assignment: vec![],
+            hasher: None,
         }
     }
 
@@ -149,6 +389,9 @@ impl<E: Engine> ConstraintSystem<E> for ProvingAssignment<E> {
         self.aux_assignment.push(f()?);
         self.a_aux_density.add_element();
         self.b_aux_density.add_element();
+        if let Some(hasher) = &mut self.hasher {
+            hasher.alloc();
+        }
 
         Ok(Variable(Index::Aux(self.aux_assignment.len() - 1)))
     }
@@ -161,6 +404,9 @@ impl<E: Engine> ConstraintSystem<E> for ProvingAssignment<E> {
     {
         self.input_assignment.push(f()?);
         self.b_input_density.add_element();
+        if let Some(hasher) = &mut self.hasher {
+            hasher.alloc_input();
+        }
 
         Ok(Variable(Index::Input(self.input_assignment.len() - 1)))
     }
@@ -177,6 +423,10 @@ impl<E: Engine> ConstraintSystem<E> for ProvingAssignment<E> {
         let b = b(LinearCombination::zero());
         let c = c(LinearCombination::zero());
 
+        if let Some(hasher) = &mut self.hasher {
+            hasher.enforce(&a, &b, &c);
+        }
+
         self.a.push(Scalar(eval(
             &a,
             // Inputs have full density in the A query
@@ -240,6 +490,81 @@ impl<E: Engine> ConstraintSystem<E> for ProvingAssignment<E> {
             // Skip first input, which must have been a temporarily allocated one variable.
             .extend(&other.input_assignment[1..]);
         self.aux_assignment.extend(other.aux_assignment);
+
+        match (&mut self.hasher, &other.hasher) {
+            (Some(hasher), Some(other)) => hasher.extend(other),
+            // Without the digest of `other`, ours can not be computed anymore.
+            (hasher, None) => *hasher = None,
+            (None, Some(_)) => {}
+        }
+    }
+}
+
+/// Whether the prover checks the proofs it produced against the verifying key
+/// of the parameters before returning them, to catch proofs corrupted by a
+/// faulty GPU or parameters file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProofVerification {
+    /// The proofs are returned without being checked.
+    Skip,
+    /// Fails with `SynthesisError::InvalidProofProduced` if a proof is invalid.
+    Verify,
+    /// Proves the circuits again on the CPU if a proof is invalid, and only
+    /// fails if the proofs are still invalid.
+    VerifyAndRetryOnCpu,
+}
+
+impl ProofVerification {
+    /// The verification selected with `BELLMAN_VERIFY_PROOFS`, which can be
+    /// `off` (the default), `on` or `retry`.
+    pub fn from_env() -> Self {
+        match &std::env::var("BELLMAN_VERIFY_PROOFS")
+            .unwrap_or_else(|_| "off".to_string())
+            .to_lowercase()[..]
+        {
+            "off" => ProofVerification::Skip,
+            "on" => ProofVerification::Verify,
+            "retry" => ProofVerification::VerifyAndRetryOnCpu,
+            s => panic!("Invalid proof verification selected: {}", s),
+        }
+    }
+}
+
+/// Options of `create_proof_batch_with_options`. The defaults are those of
+/// `create_proof_batch_priority` without priority.
+#[derive(Clone)]
+pub struct ProverOptions<'a> {
+    /// Whether the proofs take priority over others on the GPU.
+    pub priority: bool,
+    /// How the proofs are checked against the verifying key of the
+    /// parameters. Defaults to the verification set by
+    /// `BELLMAN_VERIFY_PROOFS`.
+    pub verification: ProofVerification,
+    /// The prover gives up with `SynthesisError::Cancelled` once this token is
+    /// cancelled. The token is checked before synthesizing each circuit,
+    /// between the phases of the prover and within the FFTs and multiexps,
+    /// and any GPU kernels and locks are released before returning.
+    pub cancel: CancellationToken,
+    /// Notified of the start and end of each phase of the prover.
+    pub observer: &'a dyn ProverObserver,
+    /// Whether the digest of each circuit is computed while synthesizing it,
+    /// to check it against the digest of the parameters. This hashes every
+    /// constraint, so it is off by default, and only the numbers of inputs,
+    /// auxiliary variables and constraints are checked. Circuits synthesized
+    /// in parallel components have no digest, so only their sizes are
+    /// checked either way.
+    pub check_digest: bool,
+}
+
+impl Default for ProverOptions<'_> {
+    fn default() -> Self {
+        ProverOptions {
+            priority: false,
+            verification: ProofVerification::from_env(),
+            cancel: CancellationToken::new(),
+            observer: &NoObserver,
+            check_digest: false,
+        }
     }
 }
 
@@ -270,14 +595,140 @@ pub fn create_proof_batch_priority<E, C, P: ParameterSource<E>>(
 where
     E: Engine,
     C: Circuit<E> + Send,
+{
+    create_proof_batch_with_options::<E, C, P>(
+        circuits,
+        params,
+        r_s,
+        s_s,
+        &ProverOptions {
+            priority,
+            ..ProverOptions::default()
+        },
+    )
+}
+
+pub fn create_random_proof_batch_with_options<E, C, R, P: ParameterSource<E>>(
+    circuits: Vec<C>,
+    params: P,
+    rng: &mut R,
+    options: &ProverOptions,
+) -> Result<Vec<Proof<E>>, SynthesisError>
+where
+    E: Engine,
+    C: Circuit<E> + Send,
+    R: RngCore,
+{
+    let r_s = (0..circuits.len()).map(|_| E::Fr::random(rng)).collect();
+    let s_s = (0..circuits.len()).map(|_| E::Fr::random(rng)).collect();
+
+    create_proof_batch_with_options::<E, C, P>(circuits, params, r_s, s_s, options)
+}
+
+/// Like `create_proof_batch_priority`, but with the priority, verification,
+/// cancellation and observer of `options`. All the circuits must be of the
+/// size of `params`, see `create_proof_batch_with_params_for` otherwise.
+pub fn create_proof_batch_with_options<E, C, P: ParameterSource<E>>(
+    circuits: Vec<C>,
+    params: P,
+    r_s: Vec<E::Fr>,
+    s_s: Vec<E::Fr>,
+    options: &ProverOptions,
+) -> Result<Vec<Proof<E>>, SynthesisError>
+where
+    E: Engine,
+    C: Circuit<E> + Send,
+{
+    info!("Bellperson {} is being used!", BELLMAN_VERSION);
+
+    THREAD_POOL.install(|| {
+        let with_digest = options.check_digest && params.has_digest();
+        let provers = synthesize_reported(circuits, with_digest, options)?;
+        create_proof_batch_priority_inner(provers, |_| Ok(&params), r_s, s_s, options)
+    })
+}
+
+/// Like `create_proof_batch_with_options`, but for circuits of different
+/// sizes. The circuits are proven in groups of circuits of the same size,
+/// with the parameters `params_for` returns for the size of the group.
+pub fn create_proof_batch_with_params_for<'p, E, C, P, F>(
+    circuits: Vec<C>,
+    params_for: F,
+    r_s: Vec<E::Fr>,
+    s_s: Vec<E::Fr>,
+    options: &ProverOptions,
+) -> Result<Vec<Proof<E>>, SynthesisError>
+where
+    E: Engine,
+    C: Circuit<E> + Send,
+    P: ParameterSource<E> + 'p,
+    F: Fn(&CircuitSize) -> Result<&'p P, SynthesisError> + Send,
 {
     info!("Bellperson {} is being used!", BELLMAN_VERSION);
 
-    THREAD_POOL.install(|| create_proof_batch_priority_inner(circuits, params, r_s, s_s, priority))
+    THREAD_POOL.install(|| {
+        // The parameters are only known once the circuits are synthesized,
+        // so whether they carry digests is not known yet.
+        let provers = synthesize_reported(circuits, options.check_digest, options)?;
+        create_proof_batch_priority_inner(provers, params_for, r_s, s_s, options)
+    })
+}
+
+/// Synthesizes `circuits` with `synthesize_batch`, reporting the synthesis
+/// phase to the observer of `options`.
+fn synthesize_reported<E, C>(
+    circuits: Vec<C>,
+    with_digest: bool,
+    options: &ProverOptions,
+) -> Result<Vec<ProvingAssignment<E>>, SynthesisError>
+where
+    E: Engine,
+    C: Circuit<E> + Send,
+{
+    options.observer.phase_started(ProverPhase::Synthesis);
+    let start = Instant::now();
+    let provers = synthesize_batch(circuits, with_digest, &options.cancel)?;
+    report_phase(
+        options.observer,
+        ProverPhase::Synthesis,
+        provers.len(),
+        provers
+            .iter()
+            .map(|prover| prover.num_constraints())
+            .max()
+            .unwrap_or(0),
+        ProverDevice::Cpu,
+        start.elapsed(),
+    );
+
+    Ok(provers)
+}
+
+pub fn create_random_proof_batch_with_shape_priority<E, C, R, P: ParameterSource<E>>(
+    circuits: Vec<C>,
+    shape: &CircuitShape<E>,
+    params: P,
+    rng: &mut R,
+    priority: bool,
+) -> Result<Vec<Proof<E>>, SynthesisError>
+where
+    E: Engine,
+    C: Circuit<E> + Send,
+    R: RngCore,
+{
+    let r_s = (0..circuits.len()).map(|_| E::Fr::random(rng)).collect();
+    let s_s = (0..circuits.len()).map(|_| E::Fr::random(rng)).collect();
+
+    create_proof_batch_with_shape_priority::<E, C, P>(circuits, shape, params, r_s, s_s, priority)
 }
 
-fn create_proof_batch_priority_inner<E, C, P: ParameterSource<E>>(
+/// Like `create_proof_batch_priority`, but the constraints are taken from a
+/// previously captured `CircuitShape` instead of being built while
+/// synthesizing. The circuits are only synthesized for their variable
+/// assignments, so they must produce exactly the shape's variables.
+pub fn create_proof_batch_with_shape_priority<E, C, P: ParameterSource<E>>(
     circuits: Vec<C>,
+    shape: &CircuitShape<E>,
     params: P,
     r_s: Vec<E::Fr>,
     s_s: Vec<E::Fr>,
@@ -287,35 +738,470 @@ where
     E: Engine,
     C: Circuit<E> + Send,
 {
-    let mut provers = circuits
-        .into_par_iter()
-        .map(|circuit| -> Result<_, SynthesisError> {
-            let mut prover = ProvingAssignment::new();
+    info!("Bellperson {} is being used!", BELLMAN_VERSION);
+
+    THREAD_POOL.install(|| {
+        let provers = circuits
+            .into_par_iter()
+            .map(|circuit| synthesize_with_shape(circuit, shape))
+            .collect::<Result<Vec<_>, _>>()?;
+        create_proof_batch_priority_inner(
+            provers,
+            |_| Ok(&params),
+            r_s,
+            s_s,
+            &ProverOptions {
+                priority,
+                ..ProverOptions::default()
+            },
+        )
+    })
+}
+
+/// Runs only the synthesis phase of `create_proof_batch_priority`. The
+/// resulting assignments can be persisted with `ProvingAssignment::write`, and
+/// proven with `create_proof_batch_from_assignments_priority`. Their digests
+/// are not computed, see `ProvingAssignment::synthesize` to check them against
+/// the parameters.
+pub fn synthesize_circuits_batch<E, C>(
+    circuits: Vec<C>,
+) -> Result<Vec<ProvingAssignment<E>>, SynthesisError>
+where
+    E: Engine,
+    C: Circuit<E> + Send,
+{
+    synthesize_batch(circuits, false, &CancellationToken::new())
+}
 
-            prover.alloc_input(|| "", || Ok(E::Fr::one()))?;
+/// Synthesizes `circuits` in parallel, checking `cancel` before each of them.
+/// Their digests are only computed if `with_digest` is set.
+pub(super) fn synthesize_batch<E, C>(
+    circuits: Vec<C>,
+    with_digest: bool,
+    cancel: &CancellationToken,
+) -> Result<Vec<ProvingAssignment<E>>, SynthesisError>
+where
+    E: Engine,
+    C: Circuit<E> + Send,
+{
+    THREAD_POOL.install(|| {
+        circuits
+            .into_par_iter()
+            .map(|circuit| {
+                cancel.check()?;
+                ProvingAssignment::synthesize_with_digest(circuit, with_digest)
+            })
+            .collect::<Result<Vec<_>, _>>()
+    })
+}
 
-            circuit.synthesize(&mut prover)?;
+pub fn create_random_proof_batch_from_assignments_priority<E, R, P: ParameterSource<E>>(
+    provers: Vec<ProvingAssignment<E>>,
+    params: P,
+    rng: &mut R,
+    priority: bool,
+) -> Result<Vec<Proof<E>>, SynthesisError>
+where
+    E: Engine,
+    R: RngCore,
+{
+    let r_s = (0..provers.len()).map(|_| E::Fr::random(rng)).collect();
+    let s_s = (0..provers.len()).map(|_| E::Fr::random(rng)).collect();
+
+    create_proof_batch_from_assignments_priority::<E, P>(provers, params, r_s, s_s, priority)
+}
+
+/// Resumes `create_proof_batch_priority` after the synthesis phase, from
+/// assignments produced by `synthesize_circuits_batch`.
+pub fn create_proof_batch_from_assignments_priority<E, P: ParameterSource<E>>(
+    provers: Vec<ProvingAssignment<E>>,
+    params: P,
+    r_s: Vec<E::Fr>,
+    s_s: Vec<E::Fr>,
+    priority: bool,
+) -> Result<Vec<Proof<E>>, SynthesisError>
+where
+    E: Engine,
+{
+    info!("Bellperson {} is being used!", BELLMAN_VERSION);
+
+    THREAD_POOL.install(|| {
+        create_proof_batch_priority_inner(
+            provers,
+            |_| Ok(&params),
+            r_s,
+            s_s,
+            &ProverOptions {
+                priority,
+                ..ProverOptions::default()
+            },
+        )
+    })
+}
+
+/// Synthesizes only the witness of `circuit` and evaluates the constraints
+/// of `shape` on it.
+fn synthesize_with_shape<E, C>(
+    circuit: C,
+    shape: &CircuitShape<E>,
+) -> Result<ProvingAssignment<E>, SynthesisError>
+where
+    E: Engine,
+    C: Circuit<E>,
+{
+    let mut witness = WitnessCS::new();
 
-            for i in 0..prover.input_assignment.len() {
-                prover.enforce(|| "", |lc| lc + Variable(Index::Input(i)), |lc| lc, |lc| lc);
+    witness.alloc_input(|| "", || Ok(E::Fr::one()))?;
+
+    circuit.synthesize(&mut witness)?;
+
+    let (input_assignment, aux_assignment) = witness.into_assignment();
+    if input_assignment.len() != shape.num_inputs || aux_assignment.len() != shape.num_aux {
+        return Err(SynthesisError::ShapeMismatch);
+    }
+
+    let evaluate = |lcs: &[LinearCombination<E>]| {
+        lcs.par_iter()
+            .map(|lc| Scalar(eval(lc, None, None, &input_assignment, &aux_assignment)))
+            .collect::<Vec<_>>()
+    };
+
+    Ok(ProvingAssignment {
+        a_aux_density: shape.a_aux_density.clone(),
+        b_input_density: shape.b_input_density.clone(),
+        b_aux_density: shape.b_aux_density.clone(),
+        a: evaluate(&shape.a[..]),
+        b: evaluate(&shape.b[..]),
+        c: evaluate(&shape.c[..]),
+        input_assignment,
+        aux_assignment,
+        hasher: Some(shape.hasher.clone()),
+    })
+}
+
+/// Logs the end of a phase of the prover and reports it to `observer`.
+fn report_phase(
+    observer: &dyn ProverObserver,
+    phase: ProverPhase,
+    num_proofs: usize,
+    size: usize,
+    device: ProverDevice,
+    elapsed: Duration,
+) {
+    let report = PhaseReport {
+        phase,
+        num_proofs,
+        size,
+        device,
+        elapsed,
+    };
+    debug!(
+        "{:?} of {} proof(s) of size {} took {:?} on the {:?}",
+        report.phase, report.num_proofs, report.size, report.elapsed, report.device
+    );
+    observer.phase_finished(&report);
+}
+
+/// Waits for `future`, moving `end` forward to when it finished if that is
+/// later.
+fn wait_until<T: Send + 'static>(
+    future: WorkerFuture<Result<T, SynthesisError>>,
+    end: &mut Instant,
+) -> Result<T, SynthesisError> {
+    let (result, finished) = future.wait_timed();
+    *end = (*end).max(finished);
+    result
+}
+
+fn device(gpu_runs: usize) -> ProverDevice {
+    if gpu_runs > 0 {
+        ProverDevice::Gpu
+    } else {
+        ProverDevice::Cpu
+    }
+}
+
+/// Computes the coefficients of H from the evaluations of the A, B and C
+/// polynomials of `prover`, which are taken out of it. The evaluations are at
+/// the roots of unity of `domain`.
+pub(super) fn compute_h<E: Engine>(
+    prover: &mut ProvingAssignment<E>,
+    keep: bool,
+    domain: QapDomain,
+    worker: &Worker,
+    fft_kern: &mut Option<LockedFFTKernel<E>>,
+) -> Result<Arc<Vec<<E::Fr as PrimeField>::Repr>>, SynthesisError> {
+    let mut a = EvaluationDomain::from_coeffs_in(take_or_clone(&mut prover.a, keep), domain)?;
+    let mut b = EvaluationDomain::from_coeffs_in(take_or_clone(&mut prover.b, keep), domain)?;
+    let mut c = EvaluationDomain::from_coeffs_in(take_or_clone(&mut prover.c, keep), domain)?;
+
+    a.ifft(worker, fft_kern)?;
+    a.coset_fft(worker, fft_kern)?;
+    b.ifft(worker, fft_kern)?;
+    b.coset_fft(worker, fft_kern)?;
+    c.ifft(worker, fft_kern)?;
+    c.coset_fft(worker, fft_kern)?;
+
+    a.mul_assign(worker, &b);
+    drop(b);
+    a.sub_assign(worker, &c);
+    drop(c);
+    a.divide_by_z_on_coset(worker);
+    a.icoset_fft(worker, fft_kern)?;
+    let mut a = a.into_coeffs();
+    let a_len = a.len() - 1;
+    a.truncate(a_len);
+
+    Ok(Arc::new(
+        a.into_iter().map(|s| s.0.into_repr()).collect::<Vec<_>>(),
+    ))
+}
+
+/// Takes `value` out of a prover, or copies it when `keep` is set so that the
+/// prover can be proven again.
+fn take_or_clone<T: Clone + Default>(value: &mut T, keep: bool) -> T {
+    if keep {
+        value.clone()
+    } else {
+        std::mem::take(value)
+    }
+}
+
+/// Assembles a proof from the results of its multiexps and its randomizers
+/// `r` and `s`. The results of the A and B queries are given for the inputs
+/// and for the auxiliary variables.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn assemble_proof<E: Engine>(
+    vk: &VerifyingKey<E>,
+    r: E::Fr,
+    s: E::Fr,
+    h: E::G1,
+    l: E::G1,
+    (a_inputs, a_aux): (E::G1, E::G1),
+    (b_g1_inputs, b_g1_aux): (E::G1, E::G1),
+    (b_g2_inputs, b_g2_aux): (E::G2, E::G2),
+) -> Result<Proof<E>, SynthesisError> {
+    if vk.delta_g1.is_zero() || vk.delta_g2.is_zero() {
+        // If this element is zero, someone is trying to perform a
+        // subversion-CRS attack.
+        return Err(SynthesisError::UnexpectedIdentity);
+    }
+
+    let mut g_a = vk.delta_g1.mul(r);
+    g_a.add_assign_mixed(&vk.alpha_g1);
+    let mut g_b = vk.delta_g2.mul(s);
+    g_b.add_assign_mixed(&vk.beta_g2);
+    let mut g_c;
+    {
+        let mut rs = r;
+        rs.mul_assign(&s);
+
+        g_c = vk.delta_g1.mul(rs);
+        g_c.add_assign(&vk.alpha_g1.mul(s));
+        g_c.add_assign(&vk.beta_g1.mul(r));
+    }
+    let mut a_answer = a_inputs;
+    a_answer.add_assign(&a_aux);
+    g_a.add_assign(&a_answer);
+    a_answer.mul_assign(s);
+    g_c.add_assign(&a_answer);
+
+    let mut b1_answer = b_g1_inputs;
+    b1_answer.add_assign(&b_g1_aux);
+    let mut b2_answer = b_g2_inputs;
+    b2_answer.add_assign(&b_g2_aux);
+
+    g_b.add_assign(&b2_answer);
+    b1_answer.mul_assign(r);
+    g_c.add_assign(&b1_answer);
+    g_c.add_assign(&h);
+    g_c.add_assign(&l);
+
+    Ok(Proof {
+        a: g_a.into_affine(),
+        b: g_b.into_affine(),
+        c: g_c.into_affine(),
+    })
+}
+
+/// Proves `provers` in groups of the same size, each with the parameters
+/// `params_for` returns for its size.
+fn create_proof_batch_priority_inner<'p, E, P, F>(
+    provers: Vec<ProvingAssignment<E>>,
+    params_for: F,
+    r_s: Vec<E::Fr>,
+    s_s: Vec<E::Fr>,
+    options: &ProverOptions,
+) -> Result<Vec<Proof<E>>, SynthesisError>
+where
+    E: Engine,
+    P: ParameterSource<E> + 'p,
+    F: Fn(&CircuitSize) -> Result<&'p P, SynthesisError>,
+{
+    let ProverOptions {
+        priority,
+        verification,
+        ref cancel,
+        observer,
+        ..
+    } = *options;
+
+    for randomness in &[&r_s, &s_s] {
+        if randomness.len() != provers.len() {
+            return Err(SynthesisError::BatchSizeMismatch {
+                expected: provers.len(),
+                actual: randomness.len(),
+            });
+        }
+    }
+
+    let mut groups = BTreeMap::new();
+    for (i, prover) in provers.iter().enumerate() {
+        groups.entry(prover.size()).or_insert_with(Vec::new).push(i);
+    }
+
+    let mut provers = provers.into_iter().map(Some).collect::<Vec<_>>();
+    let mut proofs = vec![None; provers.len()];
+    for (size, indices) in &groups {
+        let params = params_for(size)?;
+        params.check_size(size)?;
+
+        let mut group_provers = indices
+            .iter()
+            .map(|&i| provers[i].take().expect("every prover is in one group"))
+            .collect::<Vec<_>>();
+        let group_r_s = indices.iter().map(|&i| r_s[i]).collect::<Vec<_>>();
+        let group_s_s = indices.iter().map(|&i| s_s[i]).collect::<Vec<_>>();
+
+        // The public inputs are taken before proving, as the prover consumes
+        // the assignments unless they are kept to prove them again on CPU.
+        let public_inputs = match verification {
+            ProofVerification::Skip => None,
+            _ => Some(
+                group_provers
+                    .iter()
+                    .map(|prover| prover.input_assignment[1..].to_vec())
+                    .collect::<Vec<_>>(),
+            ),
+        };
+        let retry = verification == ProofVerification::VerifyAndRetryOnCpu;
+
+        let mut group_proofs = create_proof_batch_group(
+            &mut group_provers,
+            retry,
+            params,
+            group_r_s.clone(),
+            group_s_s.clone(),
+            priority,
+            true,
+            cancel,
+            observer,
+        )?;
+
+        if let Some(public_inputs) = public_inputs {
+            if !verify_group(params, &group_proofs, &public_inputs)? {
+                if !retry {
+                    return Err(SynthesisError::InvalidProofProduced);
+                }
+                warn!("Invalid proof produced! Proving again on CPU...");
+
+                group_proofs = create_proof_batch_group(
+                    &mut group_provers,
+                    false,
+                    params,
+                    group_r_s,
+                    group_s_s,
+                    priority,
+                    false,
+                    cancel,
+                    observer,
+                )?;
+                if !verify_group(params, &group_proofs, &public_inputs)? {
+                    return Err(SynthesisError::InvalidProofProduced);
+                }
             }
+        }
 
-            Ok(prover)
-        })
-        .collect::<Result<Vec<_>, _>>()?;
+        for (&i, proof) in indices.iter().zip(group_proofs) {
+            proofs[i] = Some(proof);
+        }
+    }
+
+    Ok(proofs
+        .into_iter()
+        .map(|proof| proof.expect("every prover has been proven"))
+        .collect())
+}
+
+/// Makes sure the parameters of `vk` were generated for the circuit of
+/// `prover`. Parameters without a digest, and assignments synthesized without
+/// computing theirs, are not checked.
+fn check_digest<E: Engine>(
+    vk: &VerifyingKey<E>,
+    prover: &ProvingAssignment<E>,
+) -> Result<(), SynthesisError> {
+    if let (Some(expected), Some(actual)) = (vk.digest(), prover.digest()) {
+        if actual != expected {
+            return Err(SynthesisError::DigestMismatch { expected, actual });
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the proofs of a group against the verifying key of `params`.
+fn verify_group<E, P: ParameterSource<E>>(
+    params: &P,
+    proofs: &[Proof<E>],
+    public_inputs: &[Vec<E::Fr>],
+) -> Result<bool, SynthesisError>
+where
+    E: Engine,
+{
+    let pvk = prepare_verifying_key(params.get_vk(public_inputs[0].len() + 1)?);
+    for (proof, inputs) in proofs.iter().zip(public_inputs.iter()) {
+        if !verify_proof(&pvk, proof, inputs)? {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Proves a group of circuits with the same domain size and number of inputs.
+/// The FFTs and multiexps only run on the GPU if `use_gpu` is set. The
+/// assignments are consumed, unless `keep` is set to prove them again.
+#[allow(clippy::too_many_arguments)]
+fn create_proof_batch_group<E, P: ParameterSource<E>>(
+    provers: &mut [ProvingAssignment<E>],
+    keep: bool,
+    params: &P,
+    r_s: Vec<E::Fr>,
+    s_s: Vec<E::Fr>,
+    priority: bool,
+    use_gpu: bool,
+    cancel: &CancellationToken,
+    observer: &dyn ProverObserver,
+) -> Result<Vec<Proof<E>>, SynthesisError>
+where
+    E: Engine,
+{
+    cancel.check()?;
 
-    let worker = Worker::new();
+    // The FFTs and multiexps check the token of the worker they run on.
+    let worker = Worker::with_cancellation(cancel.clone());
+    let num_proofs = provers.len();
     let input_len = provers[0].input_assignment.len();
+    let aux_len = provers[0].aux_assignment.len();
     let vk = params.get_vk(input_len)?;
-    let n = provers[0].a.len();
+    let n = provers
+        .iter()
+        .map(|prover| prover.a.len())
+        .max()
+        .unwrap_or(0);
 
-    // Make sure all circuits have the same input len.
-    for prover in &provers {
-        assert_eq!(
-            prover.a.len(),
-            n,
-            "only equaly sized circuits are supported"
-        );
+    for prover in provers.iter() {
+        check_digest(vk, prover)?;
     }
 
     let mut log_d = 0;
@@ -324,50 +1210,52 @@ where
     }
 
     #[cfg(feature = "gpu")]
-    let prio_lock = if priority {
+    let prio_lock = if priority && use_gpu {
         Some(PriorityLock::lock())
     } else {
         None
     };
 
-    let mut fft_kern = Some(LockedFFTKernel::<E>::new(log_d, priority));
+    observer.phase_started(ProverPhase::Fft);
+    let fft_start = Instant::now();
+    let mut fft_kern = if use_gpu {
+        Some(LockedFFTKernel::<E>::new(log_d, priority))
+    } else {
+        None
+    };
 
     let a_s = provers
         .iter_mut()
-        .map(|prover| {
-            let mut a =
-                EvaluationDomain::from_coeffs(std::mem::replace(&mut prover.a, Vec::new()))?;
-            let mut b =
-                EvaluationDomain::from_coeffs(std::mem::replace(&mut prover.b, Vec::new()))?;
-            let mut c =
-                EvaluationDomain::from_coeffs(std::mem::replace(&mut prover.c, Vec::new()))?;
-
-            a.ifft(&worker, &mut fft_kern)?;
-            a.coset_fft(&worker, &mut fft_kern)?;
-            b.ifft(&worker, &mut fft_kern)?;
-            b.coset_fft(&worker, &mut fft_kern)?;
-            c.ifft(&worker, &mut fft_kern)?;
-            c.coset_fft(&worker, &mut fft_kern)?;
-
-            a.mul_assign(&worker, &b);
-            drop(b);
-            a.sub_assign(&worker, &c);
-            drop(c);
-            a.divide_by_z_on_coset(&worker);
-            a.icoset_fft(&worker, &mut fft_kern)?;
-            let mut a = a.into_coeffs();
-            let a_len = a.len() - 1;
-            a.truncate(a_len);
-
-            Ok(Arc::new(
-                a.into_iter().map(|s| s.0.into_repr()).collect::<Vec<_>>(),
-            ))
-        })
+        .map(|prover| compute_h(prover, keep, vk.domain(), &worker, &mut fft_kern))
         .collect::<Result<Vec<_>, SynthesisError>>()?;
 
+    let fft_device = device(fft_kern.as_ref().map_or(0, |kern| kern.runs()));
     drop(fft_kern);
-    let mut multiexp_kern = Some(LockedMultiexpKernel::<E>::new(log_d, priority));
+    report_phase(
+        observer,
+        ProverPhase::Fft,
+        num_proofs,
+        1 << log_d,
+        fft_device,
+        fft_start.elapsed(),
+    );
+    cancel.check()?;
+
+    // All multiexps are started before any of them is waited for, so that
+    // they run concurrently on the CPU. Each phase is reported once all of
+    // its multiexps finished, which is when the last of their jobs did.
+    let mut multiexp_kern = if use_gpu {
+        Some(LockedMultiexpKernel::<E>::new(log_d, priority))
+    } else {
+        None
+    };
+    let multiexp_runs =
+        |kern: &Option<LockedMultiexpKernel<E>>| kern.as_ref().map_or(0, |kern| kern.runs());
 
+    observer.phase_started(ProverPhase::HMultiexp);
+    let h_start = Instant::now();
+    let h_runs = multiexp_runs(&multiexp_kern);
+    let h_size = a_s.first().map_or(0, |a| a.len());
     let h_s = a_s
         .into_iter()
         .map(|a| {
@@ -381,33 +1269,45 @@ where
             Ok(h)
         })
         .collect::<Result<Vec<_>, SynthesisError>>()?;
+    let h_device = device(multiexp_runs(&multiexp_kern) - h_runs);
 
     let input_assignments = provers
         .par_iter_mut()
         .map(|prover| {
-            let input_assignment = std::mem::replace(&mut prover.input_assignment, Vec::new());
-            Arc::new(
-                input_assignment
-                    .into_iter()
+            let input_assignment = Arc::new(
+                prover
+                    .input_assignment
+                    .iter()
                     .map(|s| s.into_repr())
                     .collect::<Vec<_>>(),
-            )
+            );
+            if !keep {
+                prover.input_assignment = Vec::new();
+            }
+            input_assignment
         })
         .collect::<Vec<_>>();
 
     let aux_assignments = provers
         .par_iter_mut()
         .map(|prover| {
-            let aux_assignment = std::mem::replace(&mut prover.aux_assignment, Vec::new());
-            Arc::new(
-                aux_assignment
-                    .into_iter()
+            let aux_assignment = Arc::new(
+                prover
+                    .aux_assignment
+                    .iter()
                     .map(|s| s.into_repr())
                     .collect::<Vec<_>>(),
-            )
+            );
+            if !keep {
+                prover.aux_assignment = Vec::new();
+            }
+            aux_assignment
         })
         .collect::<Vec<_>>();
 
+    observer.phase_started(ProverPhase::LMultiexp);
+    let l_start = Instant::now();
+    let l_runs = multiexp_runs(&multiexp_kern);
     let l_s = aux_assignments
         .iter()
         .map(|aux_assignment| {
@@ -421,9 +1321,13 @@ where
             Ok(l)
         })
         .collect::<Result<Vec<_>, SynthesisError>>()?;
+    let l_device = device(multiexp_runs(&multiexp_kern) - l_runs);
 
+    observer.phase_started(ProverPhase::AbMultiexp);
+    let ab_start = Instant::now();
+    let ab_runs = multiexp_runs(&multiexp_kern);
     let inputs = provers
-        .into_iter()
+        .iter_mut()
         .zip(input_assignments.iter())
         .zip(aux_assignments.iter())
         .map(|((prover, input_assignment), aux_assignment)| {
@@ -443,14 +1347,14 @@ where
             let a_aux = multiexp(
                 &worker,
                 a_aux_source,
-                Arc::new(prover.a_aux_density),
+                Arc::new(take_or_clone(&mut prover.a_aux_density, keep)),
                 aux_assignment.clone(),
                 &mut multiexp_kern,
             );
 
-            let b_input_density = Arc::new(prover.b_input_density);
+            let b_input_density = Arc::new(take_or_clone(&mut prover.b_input_density, keep));
             let b_input_density_total = b_input_density.get_total_density();
-            let b_aux_density = Arc::new(prover.b_aux_density);
+            let b_aux_density = Arc::new(take_or_clone(&mut prover.b_aux_density, keep));
             let b_aux_density_total = b_aux_density.get_total_density();
 
             let (b_g1_inputs_source, b_g1_aux_source) =
@@ -500,12 +1404,68 @@ where
             ))
         })
         .collect::<Result<Vec<_>, SynthesisError>>()?;
+    let ab_device = device(multiexp_runs(&multiexp_kern) - ab_runs);
+
+    let mut h_end = h_start;
+    let h_s = h_s
+        .into_iter()
+        .map(|h| wait_until(h, &mut h_end))
+        .collect::<Result<Vec<_>, _>>()?;
+    report_phase(
+        observer,
+        ProverPhase::HMultiexp,
+        num_proofs,
+        h_size,
+        h_device,
+        h_end - h_start,
+    );
+
+    let mut l_end = l_start;
+    let l_s = l_s
+        .into_iter()
+        .map(|l| wait_until(l, &mut l_end))
+        .collect::<Result<Vec<_>, _>>()?;
+    report_phase(
+        observer,
+        ProverPhase::LMultiexp,
+        num_proofs,
+        aux_len,
+        l_device,
+        l_end - l_start,
+    );
+
+    let mut ab_end = ab_start;
+    let inputs = inputs
+        .into_iter()
+        .map(
+            |(a_inputs, a_aux, b_g1_inputs, b_g1_aux, b_g2_inputs, b_g2_aux)| {
+                Ok((
+                    wait_until(a_inputs, &mut ab_end)?,
+                    wait_until(a_aux, &mut ab_end)?,
+                    wait_until(b_g1_inputs, &mut ab_end)?,
+                    wait_until(b_g1_aux, &mut ab_end)?,
+                    wait_until(b_g2_inputs, &mut ab_end)?,
+                    wait_until(b_g2_aux, &mut ab_end)?,
+                ))
+            },
+        )
+        .collect::<Result<Vec<_>, SynthesisError>>()?;
+    report_phase(
+        observer,
+        ProverPhase::AbMultiexp,
+        num_proofs,
+        input_len + aux_len,
+        ab_device,
+        ab_end - ab_start,
+    );
 
     drop(multiexp_kern);
 
     #[cfg(feature = "gpu")]
     drop(prio_lock);
 
+    cancel.check()?;
+
     let proofs = h_s
         .into_iter()
         .zip(l_s.into_iter())
@@ -517,47 +1477,16 @@ where
                 (((h, l), (a_inputs, a_aux, b_g1_inputs, b_g1_aux, b_g2_inputs, b_g2_aux)), r),
                 s,
             )| {
-                if vk.delta_g1.is_zero() || vk.delta_g2.is_zero() {
-                    // If this element is zero, someone is trying to perform a
-                    // subversion-CRS attack.
-                    return Err(SynthesisError::UnexpectedIdentity);
-                }
-
-                let mut g_a = vk.delta_g1.mul(r);
-                g_a.add_assign_mixed(&vk.alpha_g1);
-                let mut g_b = vk.delta_g2.mul(s);
-                g_b.add_assign_mixed(&vk.beta_g2);
-                let mut g_c;
-                {
-                    let mut rs = r;
-                    rs.mul_assign(&s);
-
-                    g_c = vk.delta_g1.mul(rs);
-                    g_c.add_assign(&vk.alpha_g1.mul(s));
-                    g_c.add_assign(&vk.beta_g1.mul(r));
-                }
-                let mut a_answer = a_inputs.wait()?;
-                a_answer.add_assign(&a_aux.wait()?);
-                g_a.add_assign(&a_answer);
-                a_answer.mul_assign(s);
-                g_c.add_assign(&a_answer);
-
-                let mut b1_answer = b_g1_inputs.wait()?;
-                b1_answer.add_assign(&b_g1_aux.wait()?);
-                let mut b2_answer = b_g2_inputs.wait()?;
-                b2_answer.add_assign(&b_g2_aux.wait()?);
-
-                g_b.add_assign(&b2_answer);
-                b1_answer.mul_assign(r);
-                g_c.add_assign(&b1_answer);
-                g_c.add_assign(&h.wait()?);
-                g_c.add_assign(&l.wait()?);
-
-                Ok(Proof {
-                    a: g_a.into_affine(),
-                    b: g_b.into_affine(),
-                    c: g_c.into_affine(),
-                })
+                assemble_proof(
+                    vk,
+                    r,
+                    s,
+                    h,
+                    l,
+                    (a_inputs, a_aux),
+                    (b_g1_inputs, b_g1_aux),
+                    (b_g2_inputs, b_g2_aux),
+                )
             },
         )
         .collect::<Result<Vec<_>, SynthesisError>>()?;
@@ -0,0 +1,44 @@
+use ff::Field;
+use groupy::{CurveAffine, CurveProjective};
+use paired::Engine;
+use rand_core::RngCore;
+
+use super::{Proof, VerifyingKey};
+
+/// Re-randomizes a proof without knowledge of the witness. The result proves
+/// the same statement, but can't be linked to the original proof.
+///
+/// For random `r1 != 0` and `r2`, the new proof is `A' = A / r1`,
+/// `B' = r1 * B + r1 * r2 * delta` and `C' = C + r2 * A`, which passes
+/// verification if and only if the original proof does.
+pub fn rerandomize_proof<E, R>(vk: &VerifyingKey<E>, proof: &Proof<E>, rng: &mut R) -> Proof<E>
+where
+    E: Engine,
+    R: RngCore,
+{
+    let r1 = loop {
+        let r1 = E::Fr::random(rng);
+        if !r1.is_zero() {
+            break r1;
+        }
+    };
+    let r2 = E::Fr::random(rng);
+
+    let r1_inv = r1.inverse().expect("r1 is non-zero");
+    let mut r1_r2 = r1;
+    r1_r2.mul_assign(&r2);
+
+    let a = proof.a.mul(r1_inv);
+
+    let mut b = proof.b.mul(r1);
+    b.add_assign(&vk.delta_g2.mul(r1_r2));
+
+    let mut c = proof.a.mul(r2);
+    c.add_assign_mixed(&proof.c);
+
+    Proof {
+        a: a.into_affine(),
+        b: b.into_affine(),
+        c: c.into_affine(),
+    }
+}
@@ -0,0 +1,180 @@
+use ff::Field;
+use paired::Engine;
+
+use crate::digest::{CircuitDigest, ShapeHasher};
+use crate::multiexp::DensityTracker;
+use crate::{Circuit, ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
+
+/// The rank-1 constraint system of a circuit, without any witness values.
+///
+/// Synthesizing a circuit into a `CircuitShape` once allows it to be proven
+/// many times without re-building and re-evaluating all of its linear
+/// combinations: the prover then only needs the variable assignments, and
+/// computes the A, B and C evaluations with a sparse matrix-vector product.
+///
+/// The shape includes the `x * 0 = 0` constraints the prover adds for every
+/// public input, as well as the query densities those constraints induce.
+pub struct CircuitShape<E: Engine> {
+    pub(super) num_inputs: usize,
+    pub(super) num_aux: usize,
+
+    // One linear combination per constraint, for each of A, B and C.
+    pub(super) a: Vec<LinearCombination<E>>,
+    pub(super) b: Vec<LinearCombination<E>>,
+    pub(super) c: Vec<LinearCombination<E>>,
+
+    // Density of queries
+    pub(super) a_aux_density: DensityTracker,
+    pub(super) b_input_density: DensityTracker,
+    pub(super) b_aux_density: DensityTracker,
+
+    pub(super) hasher: ShapeHasher<E>,
+}
+
+impl<E: Engine> CircuitShape<E> {
+    /// Synthesizes `circuit` and captures its constraints. Variable
+    /// assignments are never requested, so this can be called on a circuit
+    /// without a witness, just like parameter generation.
+    pub fn synthesize<C: Circuit<E>>(circuit: C) -> Result<Self, SynthesisError> {
+        let mut shape = ShapeAssembly::new();
+
+        // Allocate the "one" input variable
+        shape.alloc_input(|| "", || Ok(E::Fr::one()))?;
+
+        circuit.synthesize(&mut shape)?;
+
+        // Input constraints to ensure full density of IC query
+        // x * 0 = 0
+        for i in 0..shape.0.num_inputs {
+            shape.enforce(|| "", |lc| lc + Variable(Index::Input(i)), |lc| lc, |lc| lc);
+        }
+
+        Ok(shape.0)
+    }
+
+    /// The number of constraints, including the input constraints.
+    pub fn num_constraints(&self) -> usize {
+        self.a.len()
+    }
+
+    /// The number of public inputs, including the "one" input.
+    pub fn num_inputs(&self) -> usize {
+        self.num_inputs
+    }
+
+    pub fn num_aux(&self) -> usize {
+        self.num_aux
+    }
+
+    /// The digest of the constraint system, as embedded in parameters
+    /// generated for the same circuit.
+    pub fn digest(&self) -> CircuitDigest {
+        self.hasher.finish()
+    }
+}
+
+/// Wrapper through which the constraints of a `CircuitShape` are collected.
+struct ShapeAssembly<E: Engine>(CircuitShape<E>);
+
+impl<E: Engine> ConstraintSystem<E> for ShapeAssembly<E> {
+    type Root = Self;
+
+    fn new() -> Self {
+        ShapeAssembly(CircuitShape {
+            num_inputs: 0,
+            num_aux: 0,
+            a: vec![],
+            b: vec![],
+            c: vec![],
+            a_aux_density: DensityTracker::new(),
+            b_input_density: DensityTracker::new(),
+            b_aux_density: DensityTracker::new(),
+            hasher: ShapeHasher::new(),
+        })
+    }
+
+    fn alloc<F, A, AR>(&mut self, _: A, _: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<E::Fr, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        // There is no assignment, so we don't even invoke the
+        // function for obtaining one.
+
+        let index = self.0.num_aux;
+        self.0.num_aux += 1;
+        self.0.hasher.alloc();
+
+        self.0.a_aux_density.add_element();
+        self.0.b_aux_density.add_element();
+
+        Ok(Variable(Index::Aux(index)))
+    }
+
+    fn alloc_input<F, A, AR>(&mut self, _: A, _: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<E::Fr, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        // There is no assignment, so we don't even invoke the
+        // function for obtaining one.
+
+        let index = self.0.num_inputs;
+        self.0.num_inputs += 1;
+        self.0.hasher.alloc_input();
+
+        self.0.b_input_density.add_element();
+
+        Ok(Variable(Index::Input(index)))
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(&mut self, _: A, a: LA, b: LB, c: LC)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+        LA: FnOnce(LinearCombination<E>) -> LinearCombination<E>,
+        LB: FnOnce(LinearCombination<E>) -> LinearCombination<E>,
+        LC: FnOnce(LinearCombination<E>) -> LinearCombination<E>,
+    {
+        let a = a(LinearCombination::zero());
+        let b = b(LinearCombination::zero());
+        let c = c(LinearCombination::zero());
+
+        self.0.hasher.enforce(&a, &b, &c);
+
+        // Track densities the same way the prover does while evaluating.
+        for (var, _) in a.iter() {
+            if let Variable(Index::Aux(i)) = var {
+                self.0.a_aux_density.inc(*i);
+            }
+        }
+        for (var, _) in b.iter() {
+            match var {
+                Variable(Index::Input(i)) => self.0.b_input_density.inc(*i),
+                Variable(Index::Aux(i)) => self.0.b_aux_density.inc(*i),
+            }
+        }
+
+        self.0.a.push(a);
+        self.0.b.push(b);
+        self.0.c.push(c);
+    }
+
+    fn push_namespace<NR, N>(&mut self, _: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        // Do nothing; we don't care about namespaces in this context.
+    }
+
+    fn pop_namespace(&mut self) {
+        // Do nothing; we don't care about namespaces in this context.
+    }
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+}
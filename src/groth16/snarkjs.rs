@@ -0,0 +1,450 @@
+//! Serialization of proofs, verifying keys and public inputs over BLS12-381 in
+//! the JSON format of [snarkjs], whose `proof.json`, `verification_key.json`
+//! and `public.json` files hold the coordinates of points and the public
+//! inputs as decimal strings. It is only built with the `snarkjs` feature.
+//!
+//! [snarkjs]: https://github.com/iden3/snarkjs
+
+use std::io::{self, Read, Write};
+
+use ff::{PrimeField, PrimeFieldRepr};
+use groupy::{CurveAffine, EncodedPoint};
+use paired::bls12_381::{Bls12, Fq, Fq12, Fr, FrRepr, G1Affine, G2Affine};
+use paired::Engine;
+use serde_json::{json, Map, Value};
+
+use super::{Proof, VerifyingKey};
+
+/// The size in bytes of a coordinate of a point, in the base field.
+const FQ_BYTES: usize = 48;
+
+/// The size in bytes of a scalar.
+const FR_BYTES: usize = 32;
+
+impl Proof<Bls12> {
+    /// Writes the proof as a snarkjs `proof.json`.
+    pub fn write_json<W: Write>(&self, writer: W) -> io::Result<()> {
+        let proof = json!({
+            "pi_a": g1_to_json(&self.a),
+            "pi_b": g2_to_json(&self.b),
+            "pi_c": g1_to_json(&self.c),
+            "protocol": "groth16",
+            "curve": "bls12381",
+        });
+
+        serde_json::to_writer_pretty(writer, &proof)?;
+        Ok(())
+    }
+
+    /// Reads a proof from a snarkjs `proof.json`.
+    pub fn read_json<R: Read>(reader: R) -> io::Result<Self> {
+        let proof: Value = serde_json::from_reader(reader)?;
+        let proof = object(&proof)?;
+        check_header(proof)?;
+
+        let a = non_zero(g1_from_json(field(proof, "pi_a")?)?)?;
+        let b = non_zero(g2_from_json(field(proof, "pi_b")?)?)?;
+        let c = non_zero(g1_from_json(field(proof, "pi_c")?)?)?;
+
+        Ok(Proof { a, b, c })
+    }
+}
+
+impl VerifyingKey<Bls12> {
+    /// Writes the key as a snarkjs `verification_key.json`, with the pairing
+    /// of `vk_alpha_1` and `vk_beta_2` as `vk_alphabeta_12`.
+    ///
+    /// Like the keys exported by snarkjs, it only holds the elements used for
+    /// verifying: `beta_g1`, `delta_g1`, the digest and the QAP domain of the
+    /// circuit are not written.
+    pub fn write_json<W: Write>(&self, writer: W) -> io::Result<()> {
+        if self.ic.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the key has no IC",
+            ));
+        }
+
+        let vk = json!({
+            "protocol": "groth16",
+            "curve": "bls12381",
+            "nPublic": self.ic.len() - 1,
+            "vk_alpha_1": g1_to_json(&self.alpha_g1),
+            "vk_beta_2": g2_to_json(&self.beta_g2),
+            "vk_gamma_2": g2_to_json(&self.gamma_g2),
+            "vk_delta_2": g2_to_json(&self.delta_g2),
+            "vk_alphabeta_12": fq12_to_json(&Bls12::pairing(self.alpha_g1, self.beta_g2)),
+            "IC": self.ic.iter().map(g1_to_json).collect::<Vec<_>>(),
+        });
+
+        serde_json::to_writer_pretty(writer, &vk)?;
+        Ok(())
+    }
+
+    /// Reads a key from a snarkjs `verification_key.json`.
+    ///
+    /// Such keys lack `beta_g1` and `delta_g1`, which are set to the point at
+    /// infinity, so they can verify proofs but not create them.
+    /// `vk_alphabeta_12` is not read, as proofs are verified with `vk_alpha_1`
+    /// and `vk_beta_2`.
+    pub fn read_json<R: Read>(reader: R) -> io::Result<Self> {
+        let vk: Value = serde_json::from_reader(reader)?;
+        let vk = object(&vk)?;
+        check_header(vk)?;
+
+        let ic = array(field(vk, "IC")?)?
+            .iter()
+            .map(|point| g1_from_json(point).and_then(non_zero))
+            .collect::<io::Result<Vec<_>>>()?;
+        if ic.is_empty() {
+            return Err(invalid_data("IC is empty"));
+        }
+        if let Some(n_public) = vk.get("nPublic") {
+            if n_public.as_u64() != Some(ic.len() as u64 - 1) {
+                return Err(invalid_data("nPublic does not match the length of IC"));
+            }
+        }
+
+        Ok(VerifyingKey::new(
+            non_zero(g1_from_json(field(vk, "vk_alpha_1")?)?)?,
+            G1Affine::zero(),
+            non_zero(g2_from_json(field(vk, "vk_beta_2")?)?)?,
+            non_zero(g2_from_json(field(vk, "vk_gamma_2")?)?)?,
+            G1Affine::zero(),
+            non_zero(g2_from_json(field(vk, "vk_delta_2")?)?)?,
+            ic,
+        ))
+    }
+}
+
+/// Writes public inputs as a snarkjs `public.json`.
+pub fn write_public_inputs_json<W: Write>(inputs: &[Fr], writer: W) -> io::Result<()> {
+    let inputs = inputs.iter().map(fr_to_json).collect::<Vec<_>>();
+
+    serde_json::to_writer_pretty(writer, &inputs)?;
+    Ok(())
+}
+
+/// Reads public inputs from a snarkjs `public.json`.
+pub fn read_public_inputs_json<R: Read>(reader: R) -> io::Result<Vec<Fr>> {
+    let inputs: Value = serde_json::from_reader(reader)?;
+
+    array(&inputs)?.iter().map(fr_from_json).collect()
+}
+
+fn fr_to_json(fr: &Fr) -> Value {
+    let mut bytes = Vec::with_capacity(FR_BYTES);
+    fr.into_repr()
+        .write_be(&mut bytes)
+        .expect("writing to a vector never fails");
+
+    Value::String(to_decimal(&bytes))
+}
+
+fn fr_from_json(value: &Value) -> io::Result<Fr> {
+    let bytes = from_decimal(decimal(value)?, FR_BYTES)?;
+    let mut repr = FrRepr::default();
+    repr.read_be(&bytes[..])?;
+
+    Fr::from_repr(repr).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn fq_to_json(fq: &Fq) -> Value {
+    let mut bytes = Vec::with_capacity(FQ_BYTES);
+    fq.into_repr()
+        .write_be(&mut bytes)
+        .expect("writing to a vector never fails");
+
+    Value::String(to_decimal(&bytes))
+}
+
+/// Elements of the target group are written as `[c0, c1]`, where each
+/// coefficient is written as `[c0, c1, c2]` of coefficients `[c0, c1]`.
+fn fq12_to_json(fq12: &Fq12) -> Value {
+    let fq6s = [&fq12.c0, &fq12.c1]
+        .iter()
+        .map(|fq6| {
+            [&fq6.c0, &fq6.c1, &fq6.c2]
+                .iter()
+                .map(|fq2| json!([fq_to_json(&fq2.c0), fq_to_json(&fq2.c1)]))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    json!(fq6s)
+}
+
+/// Points are written as their projective coordinates, with `z = 1` for affine
+/// points and `[0, 1, 0]` for the point at infinity.
+fn g1_to_json(point: &G1Affine) -> Value {
+    if point.is_zero() {
+        return json!(["0", "1", "0"]);
+    }
+
+    let encoded = point.into_uncompressed();
+    let (x, y) = encoded.as_ref().split_at(FQ_BYTES);
+
+    json!([to_decimal(x), to_decimal(y), "1"])
+}
+
+fn g1_from_json(value: &Value) -> io::Result<G1Affine> {
+    let coordinates = array_of(value, 3)?;
+    let z = decimal(&coordinates[2])?;
+    if z == "0" {
+        return Ok(G1Affine::zero());
+    }
+    if z != "1" {
+        return Err(invalid_data("point is not affine"));
+    }
+
+    let mut encoded = <G1Affine as CurveAffine>::Uncompressed::empty();
+    for (i, coordinate) in coordinates[..2].iter().enumerate() {
+        encoded.as_mut()[i * FQ_BYTES..(i + 1) * FQ_BYTES]
+            .copy_from_slice(&from_decimal(decimal(coordinate)?, FQ_BYTES)?);
+    }
+
+    encoded
+        .into_affine()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// The coordinates of points in G2 are written as `[c0, c1]`, while they are
+/// encoded as `c1` followed by `c0`.
+fn g2_to_json(point: &G2Affine) -> Value {
+    if point.is_zero() {
+        return json!([["0", "0"], ["1", "0"], ["0", "0"]]);
+    }
+
+    let encoded = point.into_uncompressed();
+    let limbs = encoded
+        .as_ref()
+        .chunks(FQ_BYTES)
+        .map(to_decimal)
+        .collect::<Vec<_>>();
+
+    json!([[limbs[1], limbs[0]], [limbs[3], limbs[2]], ["1", "0"]])
+}
+
+fn g2_from_json(value: &Value) -> io::Result<G2Affine> {
+    let coordinates = array_of(value, 3)?;
+    let z = array_of(&coordinates[2], 2)?;
+    match (decimal(&z[0])?, decimal(&z[1])?) {
+        ("0", "0") => return Ok(G2Affine::zero()),
+        ("1", "0") => {}
+        _ => return Err(invalid_data("point is not affine")),
+    }
+
+    let mut encoded = <G2Affine as CurveAffine>::Uncompressed::empty();
+    for (i, coordinate) in coordinates[..2].iter().enumerate() {
+        let coordinate = array_of(coordinate, 2)?;
+        for (j, c) in coordinate.iter().rev().enumerate() {
+            let offset = (2 * i + j) * FQ_BYTES;
+            encoded.as_mut()[offset..offset + FQ_BYTES]
+                .copy_from_slice(&from_decimal(decimal(c)?, FQ_BYTES)?);
+        }
+    }
+
+    encoded
+        .into_affine()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Formats a big-endian integer in decimal.
+fn to_decimal(bytes: &[u8]) -> String {
+    let mut n = bytes.to_vec();
+    let mut digits = Vec::new();
+    while n.iter().any(|&b| b != 0) {
+        let mut remainder = 0u32;
+        for b in n.iter_mut() {
+            let acc = (remainder << 8) | u32::from(*b);
+            *b = (acc / 10) as u8;
+            remainder = acc % 10;
+        }
+        digits.push(b'0' + remainder as u8);
+    }
+    if digits.is_empty() {
+        digits.push(b'0');
+    }
+    digits.reverse();
+
+    String::from_utf8(digits).expect("digits are ascii")
+}
+
+/// Parses a decimal integer into `len` big-endian bytes.
+fn from_decimal(s: &str, len: usize) -> io::Result<Vec<u8>> {
+    if s.is_empty() || !s.bytes().all(|c| c.is_ascii_digit()) {
+        return Err(invalid_data("invalid decimal number"));
+    }
+
+    let mut n = vec![0u8; len];
+    for c in s.bytes() {
+        let mut carry = u32::from(c - b'0');
+        for b in n.iter_mut().rev() {
+            let acc = u32::from(*b) * 10 + carry;
+            *b = acc as u8;
+            carry = acc >> 8;
+        }
+        if carry != 0 {
+            return Err(invalid_data("decimal number is too large"));
+        }
+    }
+
+    Ok(n)
+}
+
+fn check_header(object: &Map<String, Value>) -> io::Result<()> {
+    for &(key, expected) in &[("protocol", "groth16"), ("curve", "bls12381")] {
+        match object.get(key) {
+            None => {}
+            Some(Value::String(value)) if value == expected => {}
+            Some(_) => {
+                return Err(invalid_data(&format!("{} is not {}", key, expected)));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn object(value: &Value) -> io::Result<&Map<String, Value>> {
+    value
+        .as_object()
+        .ok_or_else(|| invalid_data("expected an object"))
+}
+
+fn field<'a>(object: &'a Map<String, Value>, key: &str) -> io::Result<&'a Value> {
+    object
+        .get(key)
+        .ok_or_else(|| invalid_data(&format!("missing {}", key)))
+}
+
+fn array(value: &Value) -> io::Result<&Vec<Value>> {
+    value
+        .as_array()
+        .ok_or_else(|| invalid_data("expected an array"))
+}
+
+fn array_of(value: &Value, len: usize) -> io::Result<&Vec<Value>> {
+    let array = array(value)?;
+    if array.len() != len {
+        return Err(invalid_data(&format!("expected an array of {}", len)));
+    }
+
+    Ok(array)
+}
+
+fn decimal(value: &Value) -> io::Result<&str> {
+    value
+        .as_str()
+        .ok_or_else(|| invalid_data("expected a decimal string"))
+}
+
+fn non_zero<G: CurveAffine>(point: G) -> io::Result<G> {
+    if point.is_zero() {
+        Err(invalid_data("point at infinity"))
+    } else {
+        Ok(point)
+    }
+}
+
+fn invalid_data(error: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ff::Field;
+
+    use crate::groth16::{prepare_verifying_key, verify_proof};
+
+    const PROOF: &str = include_str!("tests/snarkjs/proof.json");
+    const VERIFICATION_KEY: &str = include_str!("tests/snarkjs/verification_key.json");
+    const PUBLIC: &str = include_str!("tests/snarkjs/public.json");
+
+    fn parse(json: &[u8]) -> Value {
+        serde_json::from_slice(json).unwrap()
+    }
+
+    #[test]
+    fn test_snarkjs_vectors() {
+        let proof = Proof::<Bls12>::read_json(PROOF.as_bytes()).unwrap();
+        let vk = VerifyingKey::<Bls12>::read_json(VERIFICATION_KEY.as_bytes()).unwrap();
+        let inputs = read_public_inputs_json(PUBLIC.as_bytes()).unwrap();
+
+        let mut last = Fr::zero();
+        last.sub_assign(&Fr::one());
+        assert_eq!(inputs, vec![Fr::from_str("3").unwrap(), last]);
+
+        // Keys exported by snarkjs lack the elements only used for proving.
+        assert!(vk.beta_g1.is_zero());
+        assert!(vk.delta_g1.is_zero());
+
+        let pvk = prepare_verifying_key(&vk);
+        assert!(verify_proof(&pvk, &proof, &inputs).unwrap());
+        assert!(!verify_proof(&pvk, &proof, &[inputs[1], inputs[0]]).unwrap());
+
+        // Writing the vectors gives back the same JSON, with the same
+        // `vk_alphabeta_12`.
+        let mut v = vec![];
+        proof.write_json(&mut v).unwrap();
+        assert_eq!(parse(&v), parse(PROOF.as_bytes()));
+
+        let mut v = vec![];
+        vk.write_json(&mut v).unwrap();
+        assert_eq!(parse(&v), parse(VERIFICATION_KEY.as_bytes()));
+
+        let mut v = vec![];
+        write_public_inputs_json(&inputs, &mut v).unwrap();
+        assert_eq!(parse(&v), parse(PUBLIC.as_bytes()));
+
+        // A key without IC has no number of public inputs.
+        let mut empty = vk.clone();
+        empty.ic.clear();
+        assert_eq!(
+            empty.write_json(&mut vec![]).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn test_snarkjs_invalid() {
+        let mut proof = parse(PROOF.as_bytes());
+        proof["curve"] = json!("bn128");
+        let proof = serde_json::to_vec(&proof).unwrap();
+        assert!(Proof::<Bls12>::read_json(&proof[..]).is_err());
+
+        // A point which is not on the curve.
+        let mut proof = parse(PROOF.as_bytes());
+        proof["pi_a"][1] = json!("1");
+        let proof = serde_json::to_vec(&proof).unwrap();
+        assert!(Proof::<Bls12>::read_json(&proof[..]).is_err());
+
+        let mut vk = parse(VERIFICATION_KEY.as_bytes());
+        vk["nPublic"] = json!(1);
+        let vk = serde_json::to_vec(&vk).unwrap();
+        assert!(VerifyingKey::<Bls12>::read_json(&vk[..]).is_err());
+
+        // The modulus of the scalar field.
+        let inputs =
+            br#"["52435875175126190479447740508185965837690552500527637822603658699938581184513"]"#;
+        assert!(read_public_inputs_json(&inputs[..]).is_err());
+        assert!(read_public_inputs_json(&br#"["-1"]"#[..]).is_err());
+        assert!(read_public_inputs_json(&br#"[1]"#[..]).is_err());
+    }
+
+    #[test]
+    fn test_decimal() {
+        assert_eq!(to_decimal(&[]), "0");
+        assert_eq!(to_decimal(&[0, 0]), "0");
+        assert_eq!(to_decimal(&[1, 0]), "256");
+        assert_eq!(to_decimal(&[0xff; 8]), u64::max_value().to_string());
+
+        assert_eq!(from_decimal("0", 2).unwrap(), vec![0, 0]);
+        assert_eq!(from_decimal("65535", 2).unwrap(), vec![0xff, 0xff]);
+        assert!(from_decimal("65536", 2).is_err());
+        assert!(from_decimal("", 2).is_err());
+        assert!(from_decimal("1a", 2).is_err());
+    }
+}
@@ -0,0 +1,221 @@
+//! Verification of proofs over BLS12-381 on EVM chains, with a Solidity
+//! contract generated from the verifying key which uses the precompiles of
+//! [EIP-2537].
+//!
+//! [EIP-2537]: https://eips.ethereum.org/EIPS/eip-2537
+
+use byteorder::{BigEndian, WriteBytesExt};
+use ff::{PrimeField, PrimeFieldRepr};
+use groupy::CurveAffine;
+use paired::bls12_381::{Bls12, Fr, G1Affine, G2Affine};
+
+use super::{Proof, VerifyingKey};
+use crate::SynthesisError;
+
+static VERIFIER_SRC: &str = include_str!("solidity/verifier.sol");
+
+/// The selector of `verifyProof(bytes,uint256[])`.
+const VERIFY_PROOF_SELECTOR: [u8; 4] = [0x1e, 0x8e, 0x1e, 0x13];
+
+/// The size in bytes of a coordinate of a point, in the base field.
+const FQ_BYTES: usize = 48;
+
+/// The size in bytes of a coordinate of a point in EIP-2537, which pads the
+/// coordinates to 64 bytes.
+const EIP2537_FQ_BYTES: usize = 64;
+
+/// The size in bytes of a proof encoded for the verifier contract.
+const PROOF_BYTES: usize = 8 * EIP2537_FQ_BYTES;
+
+/// Generates the source of a Solidity contract `Verifier`, whose
+/// `verifyProof(bytes proof, uint256[] input)` checks proofs against `vk`.
+/// The calldata of such calls is encoded by `encode_verifier_calldata`.
+///
+/// Fails with `MalformedVerifyingKey` if `vk` has no IC, as the contract then
+/// has no number of public inputs.
+pub fn generate_solidity_verifier(vk: &VerifyingKey<Bls12>) -> Result<String, SynthesisError> {
+    if vk.ic.is_empty() {
+        return Err(SynthesisError::MalformedVerifyingKey);
+    }
+
+    let mut neg_beta = vk.beta_g2;
+    neg_beta.negate();
+    let mut neg_gamma = vk.gamma_g2;
+    neg_gamma.negate();
+    let mut neg_delta = vk.delta_g2;
+    neg_delta.negate();
+
+    let mut ic = Vec::new();
+    for point in &vk.ic {
+        ic.extend(encode_g1(point));
+    }
+    let mut alpha_neg_beta = encode_g1(&vk.alpha_g1);
+    alpha_neg_beta.extend(encode_g2(&neg_beta));
+
+    Ok(String::from(VERIFIER_SRC)
+        .replace("{{num_inputs}}", &(vk.ic.len() - 1).to_string())
+        .replace("{{ic}}", &hex_literal(&ic))
+        .replace("{{alpha_neg_beta}}", &hex_literal(&alpha_neg_beta))
+        .replace("{{neg_gamma}}", &hex_literal(&encode_g2(&neg_gamma)))
+        .replace("{{neg_delta}}", &hex_literal(&encode_g2(&neg_delta))))
+}
+
+/// Encodes a call to `verifyProof` of the contract generated by
+/// `generate_solidity_verifier`, with `proof` and its public inputs.
+pub fn encode_verifier_calldata(proof: &Proof<Bls12>, public_inputs: &[Fr]) -> Vec<u8> {
+    let mut calldata = VERIFY_PROOF_SELECTOR.to_vec();
+
+    // The offsets of the arguments, which follow them.
+    write_word(&mut calldata, 64);
+    write_word(&mut calldata, 64 + 32 + PROOF_BYTES as u64);
+
+    write_word(&mut calldata, PROOF_BYTES as u64);
+    calldata.extend(encode_g1(&proof.a));
+    calldata.extend(encode_g2(&proof.b));
+    calldata.extend(encode_g1(&proof.c));
+
+    write_word(&mut calldata, public_inputs.len() as u64);
+    for input in public_inputs {
+        input
+            .into_repr()
+            .write_be(&mut calldata)
+            .expect("writing to a vector never fails");
+    }
+
+    calldata
+}
+
+/// Writes `n` as a 32 byte ABI word.
+fn write_word(calldata: &mut Vec<u8>, n: u64) {
+    calldata.extend(&[0u8; 24]);
+    calldata
+        .write_u64::<BigEndian>(n)
+        .expect("writing to a vector never fails");
+}
+
+/// Points at infinity are all zeros, while other points are encoded as their
+/// coordinates.
+fn encode_g1(point: &G1Affine) -> Vec<u8> {
+    if point.is_zero() {
+        return vec![0; 2 * EIP2537_FQ_BYTES];
+    }
+
+    pad_coordinates(point.into_uncompressed().as_ref().chunks(FQ_BYTES))
+}
+
+/// The coordinates of points in G2 are encoded as `c1` followed by `c0` by
+/// bellperson, but as `c0` followed by `c1` in EIP-2537.
+fn encode_g2(point: &G2Affine) -> Vec<u8> {
+    if point.is_zero() {
+        return vec![0; 4 * EIP2537_FQ_BYTES];
+    }
+
+    let encoded = point.into_uncompressed();
+    let c = encoded.as_ref().chunks(FQ_BYTES).collect::<Vec<_>>();
+
+    pad_coordinates(vec![c[1], c[0], c[3], c[2]])
+}
+
+fn pad_coordinates<'a, I: IntoIterator<Item = &'a [u8]>>(coordinates: I) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    for coordinate in coordinates {
+        encoded.extend(&[0u8; EIP2537_FQ_BYTES - FQ_BYTES]);
+        encoded.extend(coordinate);
+    }
+
+    encoded
+}
+
+/// Formats `bytes` as a sequence of Solidity hex literals, with one
+/// coordinate per line.
+fn hex_literal(bytes: &[u8]) -> String {
+    bytes
+        .chunks(EIP2537_FQ_BYTES)
+        .map(|chunk| {
+            let hex = chunk
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>();
+            format!("hex\"{}\"", hex)
+        })
+        .collect::<Vec<_>>()
+        .join("\n        ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The verifier and the calldata are checked against the snarkjs vectors.
+    #[cfg(feature = "snarkjs")]
+    mod vectors {
+        use super::*;
+
+        const PROOF: &str = include_str!("tests/snarkjs/proof.json");
+        const VERIFICATION_KEY: &str = include_str!("tests/snarkjs/verification_key.json");
+        const PUBLIC: &str = include_str!("tests/snarkjs/public.json");
+
+        const VERIFIER: &str = include_str!("tests/solidity/Verifier.sol");
+        const CALLDATA: &str = include_str!("tests/solidity/calldata.hex");
+
+        #[test]
+        fn test_solidity_verifier() {
+            let vk = VerifyingKey::<Bls12>::read_json(VERIFICATION_KEY.as_bytes()).unwrap();
+
+            assert_eq!(generate_solidity_verifier(&vk).unwrap(), VERIFIER);
+        }
+
+        #[test]
+        fn test_verifier_calldata() {
+            let proof = Proof::<Bls12>::read_json(PROOF.as_bytes()).unwrap();
+            let inputs = crate::groth16::read_public_inputs_json(PUBLIC.as_bytes()).unwrap();
+
+            let calldata = encode_verifier_calldata(&proof, &inputs);
+            assert_eq!(
+                calldata.len(),
+                4 + 3 * 32 + PROOF_BYTES + 32 * (1 + inputs.len())
+            );
+
+            let hex = calldata
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>();
+            assert_eq!(hex, CALLDATA.trim());
+        }
+    }
+
+    #[test]
+    fn test_solidity_verifier_without_ic() {
+        let vk = VerifyingKey::<Bls12>::new(
+            G1Affine::one(),
+            G1Affine::one(),
+            G2Affine::one(),
+            G2Affine::one(),
+            G1Affine::one(),
+            G2Affine::one(),
+            vec![],
+        );
+
+        match generate_solidity_verifier(&vk) {
+            Err(SynthesisError::MalformedVerifyingKey) => {}
+            _ => panic!("a key without IC must be rejected"),
+        }
+    }
+
+    #[test]
+    fn test_encode_points() {
+        assert_eq!(encode_g1(&G1Affine::zero()), vec![0u8; 128]);
+        assert_eq!(encode_g2(&G2Affine::zero()), vec![0u8; 256]);
+
+        let g1 = encode_g1(&G1Affine::one());
+        assert_eq!(g1.len(), 128);
+        assert_eq!(&g1[..16], &[0u8; 16]);
+        assert_eq!(&g1[64..80], &[0u8; 16]);
+
+        // The x coordinate of the generator of G2 starts with c0 = 0x024aa2b2...
+        let g2 = encode_g2(&G2Affine::one());
+        assert_eq!(g2.len(), 256);
+        assert_eq!(&g2[16..20], &[0x02, 0x4a, 0xa2, 0xb2]);
+        assert_eq!(&g2[80..84], &[0x13, 0xe0, 0x2b, 0x60]);
+    }
+}
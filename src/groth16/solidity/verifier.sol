@@ -0,0 +1,77 @@
+// SPDX-License-Identifier: MIT
+// Generated by bellperson from a Groth16 verifying key.
+pragma solidity ^0.8.0;
+
+/// Verifies Groth16 proofs over BLS12-381 with the precompiles of EIP-2537.
+///
+/// Points are encoded as in EIP-2537: each coordinate takes 64 bytes, and the
+/// coordinates of points in G2 are written as c0 followed by c1.
+contract Verifier {
+    address constant G1_MSM = address(0x0c);
+    address constant PAIRING_CHECK = address(0x0f);
+
+    // The order of the scalar field, which public inputs must be below.
+    uint256 constant R = 0x73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000001;
+
+    uint256 constant NUM_INPUTS = {{num_inputs}};
+
+    // The elements of IC in G1.
+    bytes constant IC =
+        {{ic}};
+
+    // alpha in G1, followed by -beta in G2.
+    bytes constant ALPHA_NEG_BETA =
+        {{alpha_neg_beta}};
+
+    // -gamma in G2.
+    bytes constant NEG_GAMMA =
+        {{neg_gamma}};
+
+    // -delta in G2.
+    bytes constant NEG_DELTA =
+        {{neg_delta}};
+
+    /// Returns whether `proof`, which holds A, B and C, is valid for the
+    /// public inputs `input`.
+    function verifyProof(bytes calldata proof, uint256[] calldata input) public view returns (bool) {
+        require(proof.length == 512, "invalid proof length");
+        require(input.length == NUM_INPUTS, "invalid number of public inputs");
+
+        // IC[0] + input[0] * IC[1] + ... + input[n - 1] * IC[n]
+        bytes memory ic = IC;
+        bytes memory msm = new bytes(160 * (NUM_INPUTS + 1));
+        for (uint256 i = 0; i <= NUM_INPUTS; i++) {
+            uint256 scalar = 1;
+            if (i > 0) {
+                scalar = input[i - 1];
+                require(scalar < R, "public input is not in the scalar field");
+            }
+
+            assembly {
+                let src := add(add(ic, 32), mul(i, 128))
+                let dst := add(add(msm, 32), mul(i, 160))
+                mstore(dst, mload(src))
+                mstore(add(dst, 32), mload(add(src, 32)))
+                mstore(add(dst, 64), mload(add(src, 64)))
+                mstore(add(dst, 96), mload(add(src, 96)))
+                mstore(add(dst, 128), scalar)
+            }
+        }
+
+        (bool ok, bytes memory acc) = G1_MSM.staticcall(msm);
+        require(ok && acc.length == 128, "multi-scalar multiplication failed");
+
+        // e(A, B) * e(alpha, -beta) * e(acc, -gamma) * e(C, -delta) == 1
+        bytes memory ab = proof[0:384];
+        bytes memory c = proof[384:512];
+        bytes memory pairs = abi.encodePacked(ab, ALPHA_NEG_BETA, acc, NEG_GAMMA, c, NEG_DELTA);
+
+        bytes memory result;
+        (ok, result) = PAIRING_CHECK.staticcall(pairs);
+        if (!ok || result.length != 32) {
+            return false;
+        }
+
+        return abi.decode(result, (uint256)) == 1;
+    }
+}
@@ -1,14 +1,40 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use ff::{Field, PrimeField};
+use paired::bls12_381::{Bls12, Fr as BlsFr, G1};
 use paired::Engine;
+use rand_core::SeedableRng;
+use rand_xorshift::XorShiftRng;
 
 mod dummy_engine;
 use self::dummy_engine::*;
 
+use std::env;
+use std::fs::{self, File};
+use std::future::Future;
+use std::io::{BufReader, BufWriter, Write};
 use std::marker::PhantomData;
+use std::mem;
+use std::process::{self, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+use std::thread::{self, Thread};
+use std::time::Instant;
 
 use super::{
-    create_proof, create_proof_batch, generate_parameters, prepare_verifying_key, verify_proof,
+    compute_multiexp_job, create_distributed_proof_batch, create_proof, create_proof_async,
+    create_proof_batch, create_proof_batch_async, create_proof_batch_from_assignments,
+    create_proof_batch_with_options, create_proof_batch_with_options_async,
+    create_proof_batch_with_params_for, create_proof_batch_with_shape, create_proof_with_shape,
+    create_random_proof, generate_parameters, generate_random_parameters,
+    generate_random_parameters_in, prepare_verifying_key, rerandomize_proof,
+    synthesize_circuits_batch, verify_proof, CircuitShape, CircuitSize, MappedParameters,
+    MultiexpJob, MultiexpOutput, MultiexpResult, ParameterSource, Parameters, PhaseReport,
+    ProofVerification, ProverDevice, ProverObserver, ProverOptions, ProverPhase, ProvingAssignment,
+    VerifyingKey,
 };
+use crate::domain::QapDomain;
+use crate::multicore::{CancellationToken, WorkerFuture};
+use crate::parallel::ParallelCircuit;
 use crate::{Circuit, ConstraintSystem, SynthesisError};
 
 #[derive(Clone)]
@@ -436,3 +462,1441 @@ fn test_create_batch_single() {
         assert!(verify_proof(&pvk, &proof, &[Fr::one()]).unwrap());
     }
 }
+
+/// Polls `future` to completion on the current thread, which is parked until
+/// the future wakes it, like the executor of an async runtime would.
+fn block_on<F: Future>(future: F) -> F::Output {
+    fn clone(data: *const ()) -> RawWaker {
+        let thread = unsafe { Arc::from_raw(data as *const Thread) };
+        let cloned = thread.clone();
+        mem::forget(thread);
+        RawWaker::new(Arc::into_raw(cloned) as *const (), &VTABLE)
+    }
+
+    fn wake(data: *const ()) {
+        unsafe { Arc::from_raw(data as *const Thread) }.unpark();
+    }
+
+    fn wake_by_ref(data: *const ()) {
+        unsafe { &*(data as *const Thread) }.unpark();
+    }
+
+    fn drop_waker(data: *const ()) {
+        drop(unsafe { Arc::from_raw(data as *const Thread) });
+    }
+
+    static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake_by_ref, drop_waker);
+
+    let data = Arc::into_raw(Arc::new(thread::current())) as *const ();
+    let waker = unsafe { Waker::from_raw(RawWaker::new(data, &VTABLE)) };
+    let mut cx = Context::from_waker(&waker);
+
+    let mut future = Box::pin(future);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[test]
+fn test_create_proof_async() {
+    let g1 = Fr::one();
+    let g2 = Fr::one();
+    let alpha = Fr::from_str("48577").unwrap();
+    let beta = Fr::from_str("22580").unwrap();
+    let gamma = Fr::from_str("53332").unwrap();
+    let delta = Fr::from_str("5481").unwrap();
+    let tau = Fr::from_str("3673").unwrap();
+
+    let params = {
+        let c = XORDemo::<DummyEngine> {
+            a: None,
+            b: None,
+            _marker: PhantomData,
+        };
+
+        Arc::new(generate_parameters(c, g1, g2, alpha, beta, gamma, delta, tau).unwrap())
+    };
+
+    let pvk = prepare_verifying_key(&params.vk);
+
+    let r1 = Fr::from_str("27134").unwrap();
+    let s1 = Fr::from_str("17146").unwrap();
+
+    let r2 = Fr::from_str("27132").unwrap();
+    let s2 = Fr::from_str("17142").unwrap();
+
+    let c = XORDemo {
+        a: Some(true),
+        b: Some(false),
+        _marker: PhantomData,
+    };
+
+    // Both provers run concurrently with the test thread.
+    let single = create_proof_async(c.clone(), params.clone(), r1, s1);
+    let batch = create_proof_batch_async(
+        vec![c.clone(), c.clone()],
+        params.clone(),
+        vec![r1, r2],
+        vec![s1, s2],
+    );
+
+    let proof_single = block_on(single).unwrap();
+    let proof_batch = batch.wait().unwrap();
+
+    assert_eq!(
+        proof_single,
+        create_proof(c.clone(), &*params, r1, s1).unwrap()
+    );
+    assert_eq!(proof_batch[0], proof_single);
+    assert!(verify_proof(&pvk, &proof_single, &[Fr::one()]).unwrap());
+    for proof in &proof_batch {
+        assert!(verify_proof(&pvk, &proof, &[Fr::one()]).unwrap());
+    }
+
+    let prove = |cancel: &CancellationToken| {
+        create_proof_batch_with_options_async(
+            vec![c.clone()],
+            params.clone(),
+            vec![r1],
+            vec![s1],
+            ProverOptions {
+                cancel: cancel.clone(),
+                ..ProverOptions::default()
+            },
+        )
+    };
+
+    // Dropping the future cancels the prover, unless it already resolved.
+    let cancel = CancellationToken::new();
+    drop(prove(&cancel));
+    assert!(cancel.is_cancelled());
+
+    let cancel = CancellationToken::new();
+    assert_eq!(prove(&cancel).wait().unwrap(), vec![proof_single]);
+    assert!(!cancel.is_cancelled());
+}
+
+#[test]
+#[should_panic(expected = "WorkerFuture polled after it completed")]
+fn test_worker_future_polled_after_completion() {
+    let mut future = WorkerFuture::ready(1);
+    assert_eq!(block_on(&mut future), 1);
+    block_on(&mut future);
+}
+
+#[test]
+#[should_panic(expected = "WorkerFuture waited for after it completed")]
+fn test_worker_future_waited_for_after_completion() {
+    let mut future = WorkerFuture::ready(1);
+    assert_eq!(block_on(&mut future), 1);
+    future.wait();
+}
+
+#[test]
+fn test_rerandomize_proof() {
+    let rng = &mut XorShiftRng::from_seed([
+        0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06, 0xbc,
+        0xe5,
+    ]);
+
+    let g1 = Fr::one();
+    let g2 = Fr::one();
+    let alpha = Fr::from_str("48577").unwrap();
+    let beta = Fr::from_str("22580").unwrap();
+    let gamma = Fr::from_str("53332").unwrap();
+    let delta = Fr::from_str("5481").unwrap();
+    let tau = Fr::from_str("3673").unwrap();
+
+    let params = {
+        let c = XORDemo::<DummyEngine> {
+            a: None,
+            b: None,
+            _marker: PhantomData,
+        };
+
+        generate_parameters(c, g1, g2, alpha, beta, gamma, delta, tau).unwrap()
+    };
+
+    let pvk = prepare_verifying_key(&params.vk);
+
+    let r = Fr::from_str("27134").unwrap();
+    let s = Fr::from_str("17146").unwrap();
+
+    let c = XORDemo {
+        a: Some(true),
+        b: Some(false),
+        _marker: PhantomData,
+    };
+    let proof = create_proof(c, &params, r, s).unwrap();
+
+    let rerandomized = rerandomize_proof(&params.vk, &proof, rng);
+    assert!(rerandomized != proof);
+    assert!(rerandomized.a != proof.a);
+    assert!(rerandomized.b != proof.b);
+    assert!(rerandomized.c != proof.c);
+    assert!(verify_proof(&pvk, &rerandomized, &[Fr::one()]).unwrap());
+    assert!(!verify_proof(&pvk, &rerandomized, &[Fr::zero()]).unwrap());
+
+    // Re-randomizing twice gives unrelated proofs that still verify.
+    let again = rerandomize_proof(&params.vk, &rerandomized, rng);
+    assert!(again != rerandomized);
+    assert!(again != proof);
+    assert!(verify_proof(&pvk, &again, &[Fr::one()]).unwrap());
+
+    // An invalid proof stays invalid.
+    let mut invalid = proof.clone();
+    invalid.c = proof.a;
+    assert!(!verify_proof(&pvk, &invalid, &[Fr::one()]).unwrap());
+    let invalid = rerandomize_proof(&params.vk, &invalid, rng);
+    assert!(!verify_proof(&pvk, &invalid, &[Fr::one()]).unwrap());
+}
+
+#[test]
+fn test_create_proof_with_tables() {
+    let g1 = Fr::one();
+    let g2 = Fr::one();
+    let alpha = Fr::from_str("48577").unwrap();
+    let beta = Fr::from_str("22580").unwrap();
+    let gamma = Fr::from_str("53332").unwrap();
+    let delta = Fr::from_str("5481").unwrap();
+    let tau = Fr::from_str("3673").unwrap();
+
+    let mut params = {
+        let c = XORDemo::<DummyEngine> {
+            a: None,
+            b: None,
+            _marker: PhantomData,
+        };
+
+        generate_parameters(c, g1, g2, alpha, beta, gamma, delta, tau).unwrap()
+    };
+
+    let pvk = prepare_verifying_key(&params.vk);
+
+    let r = Fr::from_str("27134").unwrap();
+    let s = Fr::from_str("17146").unwrap();
+
+    let c = XORDemo {
+        a: Some(true),
+        b: Some(false),
+        _marker: PhantomData,
+    };
+    let expected = create_proof(c.clone(), &params, r, s).unwrap();
+
+    for &window in &[1, 3, 16] {
+        params.precompute_tables(window);
+        let tables = params.tables().unwrap();
+        assert!(tables.are_tables_of(&params.h, &params.l, &params.a, &params.b_g1, &params.b_g2));
+
+        let proof = create_proof(c.clone(), &params, r, s).unwrap();
+        assert_eq!(proof, expected);
+        assert!(verify_proof(&pvk, &proof, &[Fr::one()]).unwrap());
+    }
+
+    // A query replaced after precomputing the tables is used without its
+    // table.
+    let mut replaced = params.clone();
+    let mut h = (*replaced.h).clone();
+    h.reverse();
+    replaced.h = Arc::new(h);
+    let without_tables = Parameters::new(
+        replaced.vk.clone(),
+        replaced.h.clone(),
+        replaced.l.clone(),
+        replaced.a.clone(),
+        replaced.b_g1.clone(),
+        replaced.b_g2.clone(),
+    );
+    assert!(!replaced.tables().unwrap().are_tables_of(
+        &replaced.h,
+        &replaced.l,
+        &replaced.a,
+        &replaced.b_g1,
+        &replaced.b_g2
+    ));
+    assert_eq!(
+        create_proof(c.clone(), &replaced, r, s).unwrap(),
+        create_proof(c, &without_tables, r, s).unwrap()
+    );
+}
+
+#[test]
+fn test_create_proof_with_verification() {
+    let g1 = Fr::one();
+    let g2 = Fr::one();
+    let alpha = Fr::from_str("48577").unwrap();
+    let beta = Fr::from_str("22580").unwrap();
+    let gamma = Fr::from_str("53332").unwrap();
+    let delta = Fr::from_str("5481").unwrap();
+    let tau = Fr::from_str("3673").unwrap();
+
+    let params = {
+        let c = XORDemo::<DummyEngine> {
+            a: None,
+            b: None,
+            _marker: PhantomData,
+        };
+
+        generate_parameters(c, g1, g2, alpha, beta, gamma, delta, tau).unwrap()
+    };
+
+    let r = Fr::from_str("27134").unwrap();
+    let s = Fr::from_str("17146").unwrap();
+
+    let c = XORDemo {
+        a: Some(true),
+        b: Some(false),
+        _marker: PhantomData,
+    };
+    let expected = create_proof(c.clone(), &params, r, s).unwrap();
+
+    for &verification in &[
+        ProofVerification::Skip,
+        ProofVerification::Verify,
+        ProofVerification::VerifyAndRetryOnCpu,
+    ] {
+        let proofs = create_proof_batch_with_options(
+            vec![c.clone()],
+            &params,
+            vec![r],
+            vec![s],
+            &ProverOptions {
+                verification,
+                ..ProverOptions::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(proofs, vec![expected.clone()]);
+    }
+
+    // A corrupted verifying key makes every proof invalid, also on the CPU.
+    let mut corrupted = params.clone();
+    corrupted.vk.alpha_g1 = Fr::from_str("48578").unwrap();
+
+    let proofs = create_proof_batch_with_options(
+        vec![c.clone()],
+        &corrupted,
+        vec![r],
+        vec![s],
+        &ProverOptions {
+            verification: ProofVerification::Skip,
+            ..ProverOptions::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(proofs, vec![expected]);
+
+    for &verification in &[
+        ProofVerification::Verify,
+        ProofVerification::VerifyAndRetryOnCpu,
+    ] {
+        match create_proof_batch_with_options(
+            vec![c.clone()],
+            &corrupted,
+            vec![r],
+            vec![s],
+            &ProverOptions {
+                verification,
+                ..ProverOptions::default()
+            },
+        ) {
+            Err(SynthesisError::InvalidProofProduced) => {}
+            _ => panic!("expected InvalidProofProduced"),
+        }
+    }
+}
+
+#[test]
+fn test_create_proof_with_shape() {
+    // test consistency between proving with and without a cached circuit shape
+    let g1 = Fr::one();
+    let g2 = Fr::one();
+    let alpha = Fr::from_str("48577").unwrap();
+    let beta = Fr::from_str("22580").unwrap();
+    let gamma = Fr::from_str("53332").unwrap();
+    let delta = Fr::from_str("5481").unwrap();
+    let tau = Fr::from_str("3673").unwrap();
+
+    let params = {
+        let c = XORDemo::<DummyEngine> {
+            a: None,
+            b: None,
+            _marker: PhantomData,
+        };
+
+        generate_parameters(c, g1, g2, alpha, beta, gamma, delta, tau).unwrap()
+    };
+
+    let shape = CircuitShape::synthesize(XORDemo::<DummyEngine> {
+        a: None,
+        b: None,
+        _marker: PhantomData,
+    })
+    .unwrap();
+
+    // 3 circuit constraints and 2 input constraints.
+    assert_eq!(shape.num_constraints(), 5);
+    assert_eq!(shape.num_inputs(), 2);
+    assert_eq!(shape.num_aux(), 2);
+
+    let pvk = prepare_verifying_key(&params.vk);
+
+    let r1 = Fr::from_str("27134").unwrap();
+    let s1 = Fr::from_str("17146").unwrap();
+
+    let r2 = Fr::from_str("27132").unwrap();
+    let s2 = Fr::from_str("17142").unwrap();
+
+    let c1 = XORDemo {
+        a: Some(true),
+        b: Some(false),
+        _marker: PhantomData,
+    };
+    let c2 = XORDemo {
+        a: Some(true),
+        b: Some(true),
+        _marker: PhantomData,
+    };
+
+    let proof = create_proof(c1.clone(), &params, r1, s1).unwrap();
+    let proof_with_shape = create_proof_with_shape(c1.clone(), &shape, &params, r1, s1).unwrap();
+    assert_eq!(proof, proof_with_shape);
+    assert!(verify_proof(&pvk, &proof_with_shape, &[Fr::one()]).unwrap());
+
+    let proof_batch = create_proof_batch(
+        vec![c1.clone(), c2.clone()],
+        &params,
+        vec![r1, r2],
+        vec![s1, s2],
+    )
+    .unwrap();
+    let proof_batch_with_shape =
+        create_proof_batch_with_shape(vec![c1, c2], &shape, &params, vec![r1, r2], vec![s1, s2])
+            .unwrap();
+    assert_eq!(proof_batch, proof_batch_with_shape);
+    assert!(verify_proof(&pvk, &proof_batch_with_shape[0], &[Fr::one()]).unwrap());
+    assert!(verify_proof(&pvk, &proof_batch_with_shape[1], &[Fr::zero()]).unwrap());
+}
+
+/// Synthesizes its sub-circuits one after the other.
+struct SequentialCircuit<C>(Vec<C>);
+
+impl<E: Engine, C: Circuit<E>> Circuit<E> for SequentialCircuit<C> {
+    fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        for circuit in self.0 {
+            circuit.synthesize(cs)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_parallel_circuit() {
+    let g1 = Fr::one();
+    let g2 = Fr::one();
+    let alpha = Fr::from_str("48577").unwrap();
+    let beta = Fr::from_str("22580").unwrap();
+    let gamma = Fr::from_str("53332").unwrap();
+    let delta = Fr::from_str("5481").unwrap();
+    let tau = Fr::from_str("3673").unwrap();
+
+    let xor = |a, b| XORDemo::<DummyEngine> {
+        a,
+        b,
+        _marker: PhantomData,
+    };
+    let blank = vec![xor(None, None), xor(None, None), xor(None, None)];
+    let circuits = vec![
+        xor(Some(true), Some(false)),
+        xor(Some(true), Some(true)),
+        xor(Some(false), Some(true)),
+    ];
+
+    let params = generate_parameters(
+        ParallelCircuit::new(blank.clone()),
+        g1,
+        g2,
+        alpha,
+        beta,
+        gamma,
+        delta,
+        tau,
+    )
+    .unwrap();
+    let sequential_params = generate_parameters(
+        SequentialCircuit(blank),
+        g1,
+        g2,
+        alpha,
+        beta,
+        gamma,
+        delta,
+        tau,
+    )
+    .unwrap();
+    assert!(params == sequential_params);
+
+    let pvk = prepare_verifying_key(&params.vk);
+
+    let r = Fr::from_str("27134").unwrap();
+    let s = Fr::from_str("17146").unwrap();
+
+    let proof = create_proof(ParallelCircuit::new(circuits.clone()), &params, r, s).unwrap();
+    let sequential_proof = create_proof(SequentialCircuit(circuits), &params, r, s).unwrap();
+    assert_eq!(proof, sequential_proof);
+
+    assert!(verify_proof(&pvk, &proof, &[Fr::one(), Fr::zero(), Fr::one()]).unwrap());
+}
+
+#[test]
+fn test_create_proof_from_checkpoint() {
+    let g1 = Fr::one();
+    let g2 = Fr::one();
+    let alpha = Fr::from_str("48577").unwrap();
+    let beta = Fr::from_str("22580").unwrap();
+    let gamma = Fr::from_str("53332").unwrap();
+    let delta = Fr::from_str("5481").unwrap();
+    let tau = Fr::from_str("3673").unwrap();
+
+    let params = {
+        let c = XORDemo::<DummyEngine> {
+            a: None,
+            b: None,
+            _marker: PhantomData,
+        };
+
+        generate_parameters(c, g1, g2, alpha, beta, gamma, delta, tau).unwrap()
+    };
+
+    let pvk = prepare_verifying_key(&params.vk);
+
+    let r1 = Fr::from_str("27134").unwrap();
+    let s1 = Fr::from_str("17146").unwrap();
+
+    let r2 = Fr::from_str("27132").unwrap();
+    let s2 = Fr::from_str("17142").unwrap();
+
+    let c1 = XORDemo {
+        a: Some(true),
+        b: Some(false),
+        _marker: PhantomData,
+    };
+    let c2 = XORDemo {
+        a: Some(false),
+        b: Some(false),
+        _marker: PhantomData,
+    };
+
+    let provers = synthesize_circuits_batch(vec![c1.clone(), c2.clone()]).unwrap();
+
+    let mut checkpoint = vec![];
+    for prover in &provers {
+        prover.write(&mut checkpoint).unwrap();
+    }
+
+    let mut reader = &checkpoint[..];
+    let resumed = (0..2)
+        .map(|_| ProvingAssignment::read(&mut reader).unwrap())
+        .collect::<Vec<_>>();
+    assert!(reader.is_empty());
+    assert_eq!(resumed, provers);
+    assert!(
+        ProvingAssignment::<DummyEngine>::read(&checkpoint[..checkpoint.len() / 2 - 1]).is_err()
+    );
+
+    // The digest is kept if it was computed.
+    let with_digest = ProvingAssignment::synthesize(c1.clone()).unwrap();
+    let mut digest_checkpoint = vec![];
+    with_digest.write(&mut digest_checkpoint).unwrap();
+    let resumed_with_digest = ProvingAssignment::read(&digest_checkpoint[..]).unwrap();
+    assert_eq!(resumed_with_digest.digest(), with_digest.digest());
+    assert_eq!(resumed_with_digest, with_digest);
+
+    // Checkpoints of another format version are rejected.
+    let mut other_version = checkpoint.clone();
+    other_version[7] += 1;
+    assert!(ProvingAssignment::<DummyEngine>::read(&other_version[..]).is_err());
+    let mut not_a_checkpoint = checkpoint.clone();
+    not_a_checkpoint[0] = 0;
+    assert!(ProvingAssignment::<DummyEngine>::read(&not_a_checkpoint[..]).is_err());
+
+    let proofs =
+        create_proof_batch_from_assignments(resumed, &params, vec![r1, r2], vec![s1, s2]).unwrap();
+    let expected = create_proof_batch(vec![c1, c2], &params, vec![r1, r2], vec![s1, s2]).unwrap();
+    assert_eq!(proofs, expected);
+
+    assert!(verify_proof(&pvk, &proofs[0], &[Fr::one()]).unwrap());
+    assert!(verify_proof(&pvk, &proofs[1], &[Fr::zero()]).unwrap());
+}
+
+/// Adds a redundant `1 * 1 = 1` constraint to a circuit.
+#[derive(Clone)]
+struct WithExtraConstraint<C>(C);
+
+impl<E: Engine, C: Circuit<E>> Circuit<E> for WithExtraConstraint<C> {
+    fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        self.0.synthesize(cs)?;
+        cs.enforce(
+            || "extra",
+            |lc| lc + CS::one(),
+            |lc| lc + CS::one(),
+            |lc| lc + CS::one(),
+        );
+        Ok(())
+    }
+}
+
+#[test]
+fn test_circuit_digest() {
+    let g1 = Fr::one();
+    let g2 = Fr::one();
+    let alpha = Fr::from_str("48577").unwrap();
+    let beta = Fr::from_str("22580").unwrap();
+    let gamma = Fr::from_str("53332").unwrap();
+    let delta = Fr::from_str("5481").unwrap();
+    let tau = Fr::from_str("3673").unwrap();
+
+    let blank = XORDemo::<DummyEngine> {
+        a: None,
+        b: None,
+        _marker: PhantomData,
+    };
+    let c = XORDemo {
+        a: Some(true),
+        b: Some(false),
+        _marker: PhantomData,
+    };
+    let r = Fr::from_str("27134").unwrap();
+    let s = Fr::from_str("17146").unwrap();
+
+    let params =
+        generate_parameters(blank.clone(), g1, g2, alpha, beta, gamma, delta, tau).unwrap();
+    let shape = CircuitShape::synthesize(blank.clone()).unwrap();
+    assert_eq!(params.vk.digest(), Some(shape.digest()));
+
+    let prover = ProvingAssignment::synthesize(c.clone()).unwrap();
+    assert_eq!(prover.digest(), Some(shape.digest()));
+
+    // The parallel synthesis of a circuit has the same shape, but its
+    // components are synthesized without computing their digests.
+    let parallel = CircuitShape::synthesize(ParallelCircuit::new(vec![blank.clone()])).unwrap();
+    assert_eq!(parallel.digest(), shape.digest());
+    let prover = ProvingAssignment::synthesize(ParallelCircuit::new(vec![c.clone()])).unwrap();
+    assert_eq!(prover.digest(), None);
+
+    // The digest is only computed on request.
+    let provers = synthesize_circuits_batch(vec![c.clone()]).unwrap();
+    assert_eq!(provers[0].digest(), None);
+    assert_eq!(
+        create_proof_batch_from_assignments(provers, &params, vec![r], vec![s]).unwrap(),
+        vec![create_proof(c.clone(), &params, r, s).unwrap()]
+    );
+
+    let other = CircuitShape::synthesize(WithExtraConstraint(blank.clone())).unwrap();
+    assert_ne!(other.digest(), shape.digest());
+
+    let check_digest = ProverOptions {
+        check_digest: true,
+        ..ProverOptions::default()
+    };
+    let prove = |options: &ProverOptions| {
+        create_proof_batch_with_options(
+            vec![WithExtraConstraint(c.clone())],
+            &params,
+            vec![r],
+            vec![s],
+            options,
+        )
+    };
+    match prove(&check_digest) {
+        Err(SynthesisError::DigestMismatch { expected, actual }) => {
+            assert_eq!(expected, shape.digest());
+            assert_eq!(actual, other.digest());
+        }
+        _ => panic!("expected DigestMismatch"),
+    }
+    match create_proof_with_shape(c.clone(), &other, &params, r, s) {
+        Err(SynthesisError::DigestMismatch { .. }) => {}
+        _ => panic!("expected DigestMismatch"),
+    }
+
+    // Otherwise only the size of the circuit is checked, which the extra
+    // constraint does not change, so the proof is simply invalid.
+    let pvk = prepare_verifying_key(&params.vk);
+    let proofs = prove(&ProverOptions {
+        verification: ProofVerification::Skip,
+        ..ProverOptions::default()
+    })
+    .unwrap();
+    assert!(!verify_proof(&pvk, &proofs[0], &[Fr::one()]).unwrap());
+
+    // Parameters without a digest are not checked.
+    let mut legacy = params.clone();
+    legacy.vk.digest = None;
+    assert!(!(&legacy).has_digest());
+    assert_eq!(
+        create_proof_batch_with_options(vec![c.clone()], &legacy, vec![r], vec![s], &check_digest)
+            .unwrap(),
+        vec![create_proof(c, &params, r, s).unwrap()]
+    );
+}
+
+/// Cancels `token` while being synthesized.
+#[derive(Clone)]
+struct CancellingCircuit<C> {
+    circuit: C,
+    token: CancellationToken,
+}
+
+impl<E: Engine, C: Circuit<E>> Circuit<E> for CancellingCircuit<C> {
+    fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        self.token.cancel();
+        self.circuit.synthesize(cs)
+    }
+}
+
+#[test]
+fn test_cancellation() {
+    let g1 = Fr::one();
+    let g2 = Fr::one();
+    let alpha = Fr::from_str("48577").unwrap();
+    let beta = Fr::from_str("22580").unwrap();
+    let gamma = Fr::from_str("53332").unwrap();
+    let delta = Fr::from_str("5481").unwrap();
+    let tau = Fr::from_str("3673").unwrap();
+
+    let params = {
+        let c = XORDemo::<DummyEngine> {
+            a: None,
+            b: None,
+            _marker: PhantomData,
+        };
+
+        generate_parameters(c, g1, g2, alpha, beta, gamma, delta, tau).unwrap()
+    };
+
+    let r = Fr::from_str("27134").unwrap();
+    let s = Fr::from_str("17146").unwrap();
+
+    let c = XORDemo {
+        a: Some(true),
+        b: Some(false),
+        _marker: PhantomData,
+    };
+
+    let prove = |circuits, cancel: &CancellationToken| {
+        create_proof_batch_with_options(
+            circuits,
+            &params,
+            vec![r],
+            vec![s],
+            &ProverOptions {
+                cancel: cancel.clone(),
+                ..ProverOptions::default()
+            },
+        )
+    };
+
+    // A token which is never cancelled does not change the proof.
+    let proofs = prove(vec![c.clone()], &CancellationToken::new()).unwrap();
+    assert_eq!(proofs[0], create_proof(c.clone(), &params, r, s).unwrap());
+
+    let cancelled = CancellationToken::new();
+    cancelled.cancel();
+    match prove(vec![c.clone()], &cancelled) {
+        Err(SynthesisError::Cancelled) => {}
+        _ => panic!("expected Cancelled"),
+    }
+
+    let expired = CancellationToken::with_deadline(Instant::now());
+    match prove(vec![c.clone()], &expired) {
+        Err(SynthesisError::Cancelled) => {}
+        _ => panic!("expected Cancelled"),
+    }
+
+    // Cancelling after synthesis stops the remaining phases.
+    let token = CancellationToken::new();
+    let circuit = CancellingCircuit {
+        circuit: c,
+        token: token.clone(),
+    };
+    match create_proof_batch_with_options(
+        vec![circuit],
+        &params,
+        vec![r],
+        vec![s],
+        &ProverOptions {
+            cancel: token,
+            ..ProverOptions::default()
+        },
+    ) {
+        Err(SynthesisError::Cancelled) => {}
+        _ => panic!("expected Cancelled"),
+    }
+}
+
+#[derive(Default)]
+struct RecordingObserver {
+    started: Mutex<Vec<ProverPhase>>,
+    finished: Mutex<Vec<PhaseReport>>,
+}
+
+impl ProverObserver for RecordingObserver {
+    fn phase_started(&self, phase: ProverPhase) {
+        let mut started = self.started.lock().unwrap();
+        // The previous phases have finished, unless they are multiexps, which
+        // run concurrently.
+        match phase {
+            ProverPhase::LMultiexp | ProverPhase::AbMultiexp => {}
+            _ => assert_eq!(self.finished.lock().unwrap().len(), started.len()),
+        }
+        started.push(phase);
+    }
+
+    fn phase_finished(&self, report: &PhaseReport) {
+        self.finished.lock().unwrap().push(report.clone());
+    }
+}
+
+#[test]
+fn test_prover_observer() {
+    let g1 = Fr::one();
+    let g2 = Fr::one();
+    let alpha = Fr::from_str("48577").unwrap();
+    let beta = Fr::from_str("22580").unwrap();
+    let gamma = Fr::from_str("53332").unwrap();
+    let delta = Fr::from_str("5481").unwrap();
+    let tau = Fr::from_str("3673").unwrap();
+
+    let params = {
+        let c = XORDemo::<DummyEngine> {
+            a: None,
+            b: None,
+            _marker: PhantomData,
+        };
+
+        generate_parameters(c, g1, g2, alpha, beta, gamma, delta, tau).unwrap()
+    };
+
+    let r1 = Fr::from_str("27134").unwrap();
+    let s1 = Fr::from_str("17146").unwrap();
+
+    let r2 = Fr::from_str("27132").unwrap();
+    let s2 = Fr::from_str("17142").unwrap();
+
+    let c = XORDemo {
+        a: Some(true),
+        b: Some(false),
+        _marker: PhantomData,
+    };
+
+    let observer = RecordingObserver::default();
+    let proofs = create_proof_batch_with_options(
+        vec![c.clone(), c.clone()],
+        &params,
+        vec![r1, r2],
+        vec![s1, s2],
+        &ProverOptions {
+            observer: &observer,
+            ..ProverOptions::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        proofs,
+        create_proof_batch(vec![c.clone(), c], &params, vec![r1, r2], vec![s1, s2]).unwrap()
+    );
+
+    let phases = vec![
+        ProverPhase::Synthesis,
+        ProverPhase::Fft,
+        ProverPhase::HMultiexp,
+        ProverPhase::LMultiexp,
+        ProverPhase::AbMultiexp,
+    ];
+    assert_eq!(*observer.started.lock().unwrap(), phases);
+
+    let finished = observer.finished.lock().unwrap();
+    assert_eq!(
+        finished
+            .iter()
+            .map(|report| report.phase)
+            .collect::<Vec<_>>(),
+        phases
+    );
+    for report in finished.iter() {
+        assert_eq!(report.num_proofs, 2);
+        assert_eq!(report.device, ProverDevice::Cpu);
+    }
+
+    // 3 circuit constraints and 2 input constraints, in a domain of size 8,
+    // with 2 inputs and 2 auxiliary variables.
+    assert_eq!(
+        finished
+            .iter()
+            .map(|report| report.size)
+            .collect::<Vec<_>>(),
+        vec![5, 8, 7, 2, 4]
+    );
+}
+
+/// Proves knowledge of square roots of its public inputs.
+#[derive(Clone)]
+struct SquareRoots<E: Engine> {
+    roots: Vec<Option<E::Fr>>,
+}
+
+impl<E: Engine> Circuit<E> for SquareRoots<E> {
+    fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        for (i, root) in self.roots.into_iter().enumerate() {
+            let root_var = cs.alloc(
+                || format!("root {}", i),
+                || root.ok_or(SynthesisError::AssignmentMissing),
+            )?;
+            let square_var = cs.alloc_input(
+                || format!("square {}", i),
+                || {
+                    let mut square = root.ok_or(SynthesisError::AssignmentMissing)?;
+                    square.square();
+                    Ok(square)
+                },
+            )?;
+            cs.enforce(
+                || format!("square {} constraint", i),
+                |lc| lc + root_var,
+                |lc| lc + root_var,
+                |lc| lc + square_var,
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn test_create_proof_batch_mixed_sizes() {
+    let g1 = Fr::one();
+    let g2 = Fr::one();
+    let alpha = Fr::from_str("48577").unwrap();
+    let beta = Fr::from_str("22580").unwrap();
+    let gamma = Fr::from_str("53332").unwrap();
+    let delta = Fr::from_str("5481").unwrap();
+    let tau = Fr::from_str("3673").unwrap();
+
+    let xor_params = {
+        let c = XORDemo::<DummyEngine> {
+            a: None,
+            b: None,
+            _marker: PhantomData,
+        };
+
+        generate_parameters(c, g1, g2, alpha, beta, gamma, delta, tau).unwrap()
+    };
+    let roots_params = {
+        let c = SquareRoots::<DummyEngine> {
+            roots: vec![None; 4],
+        };
+
+        generate_parameters(c, g1, g2, alpha, beta, gamma, delta, tau).unwrap()
+    };
+    let params = [&xor_params, &roots_params];
+    let params_for = |size: &CircuitSize| {
+        params
+            .iter()
+            .find(|params| params.check_size(size).is_ok())
+            .ok_or(SynthesisError::MalformedVerifyingKey)
+    };
+
+    let xor = XORDemo {
+        a: Some(true),
+        b: Some(false),
+        _marker: PhantomData,
+    };
+    let roots = ["3", "5", "7", "11"]
+        .iter()
+        .map(|root| Some(Fr::from_str(root).unwrap()))
+        .collect::<Vec<_>>();
+    let squares = roots
+        .iter()
+        .map(|root| {
+            let mut square = root.unwrap();
+            square.square();
+            square
+        })
+        .collect::<Vec<_>>();
+
+    // The circuits are of different sizes, so they are proven in two groups.
+    let circuits = vec![
+        MixedCircuit::Xor(xor.clone()),
+        MixedCircuit::Roots(SquareRoots {
+            roots: roots.clone(),
+        }),
+        MixedCircuit::Xor(xor.clone()),
+        MixedCircuit::Roots(SquareRoots { roots }),
+    ];
+    let provers = synthesize_circuits_batch(circuits.clone()).unwrap();
+    assert_ne!(provers[0].num_constraints(), provers[1].num_constraints());
+
+    let r_s = ["27134", "27132", "27130", "27128"]
+        .iter()
+        .map(|r| Fr::from_str(r).unwrap())
+        .collect::<Vec<_>>();
+    let s_s = ["17146", "17142", "17138", "17134"]
+        .iter()
+        .map(|s| Fr::from_str(s).unwrap())
+        .collect::<Vec<_>>();
+
+    let proofs = create_proof_batch_with_params_for(
+        circuits.clone(),
+        params_for,
+        r_s.clone(),
+        s_s.clone(),
+        &ProverOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(proofs.len(), 4);
+
+    // The proofs are returned in the order of the circuits.
+    let xor_pvk = prepare_verifying_key(&xor_params.vk);
+    let roots_pvk = prepare_verifying_key(&roots_params.vk);
+    for (i, circuit) in circuits.iter().enumerate() {
+        match circuit {
+            MixedCircuit::Xor(c) => {
+                let proof = create_proof(c.clone(), &xor_params, r_s[i], s_s[i]).unwrap();
+                assert_eq!(proofs[i], proof);
+                assert!(verify_proof(&xor_pvk, &proofs[i], &[Fr::one()]).unwrap());
+            }
+            MixedCircuit::Roots(c) => {
+                let proof = create_proof(c.clone(), &roots_params, r_s[i], s_s[i]).unwrap();
+                assert_eq!(proofs[i], proof);
+                assert!(verify_proof(&roots_pvk, &proofs[i], &squares).unwrap());
+            }
+        }
+    }
+
+    // Parameters for a single circuit can not prove the others.
+    let xor_size = provers[0].size();
+    let roots_size = provers[1].size();
+    match create_proof_batch(circuits.clone(), &xor_params, r_s.clone(), s_s.clone()) {
+        Err(SynthesisError::ParameterSizeMismatch {
+            domain_size,
+            num_inputs,
+            num_aux,
+        }) => assert_eq!(
+            CircuitSize {
+                domain_size,
+                num_inputs,
+                num_aux
+            },
+            roots_size
+        ),
+        _ => panic!("expected ParameterSizeMismatch"),
+    }
+    match create_proof_batch(circuits.clone(), &roots_params, r_s.clone(), s_s.clone()) {
+        Err(SynthesisError::ParameterSizeMismatch { num_inputs, .. }) => {
+            assert_eq!(num_inputs, xor_size.num_inputs)
+        }
+        _ => panic!("expected ParameterSizeMismatch"),
+    }
+
+    match create_proof_batch_with_params_for(
+        circuits,
+        params_for,
+        r_s[..3].to_vec(),
+        s_s,
+        &ProverOptions::default(),
+    ) {
+        Err(SynthesisError::BatchSizeMismatch { expected, actual }) => {
+            assert_eq!(expected, 4);
+            assert_eq!(actual, 3);
+        }
+        _ => panic!("expected BatchSizeMismatch"),
+    }
+
+    let empty: Vec<MixedCircuit> = vec![];
+    assert!(create_proof_batch(empty, &xor_params, vec![], vec![])
+        .unwrap()
+        .is_empty());
+}
+
+/// One of the circuits of `test_create_proof_batch_mixed_sizes`.
+#[derive(Clone)]
+enum MixedCircuit {
+    Xor(XORDemo<DummyEngine>),
+    Roots(SquareRoots<DummyEngine>),
+}
+
+impl Circuit<DummyEngine> for MixedCircuit {
+    fn synthesize<CS: ConstraintSystem<DummyEngine>>(
+        self,
+        cs: &mut CS,
+    ) -> Result<(), SynthesisError> {
+        match self {
+            MixedCircuit::Xor(c) => c.synthesize(cs),
+            MixedCircuit::Roots(c) => c.synthesize(cs),
+        }
+    }
+}
+
+/// The environment variables through which `test_distributed_proof_batch`
+/// hands the files of jobs and results to its worker processes.
+const DISTRIBUTED_JOBS_ENV: &str = "BELLPERSON_TEST_DISTRIBUTED_JOBS";
+const DISTRIBUTED_RESULTS_ENV: &str = "BELLPERSON_TEST_DISTRIBUTED_RESULTS";
+
+/// The parameters of the distributed prover test, which every worker process
+/// generates for itself.
+fn distributed_params() -> Parameters<Bls12> {
+    let mut rng = XorShiftRng::from_seed([
+        0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06, 0xbc,
+        0xe5,
+    ]);
+    let c = SquareRoots::<Bls12> {
+        roots: vec![None; 8],
+    };
+
+    generate_random_parameters(c, &mut rng).unwrap()
+}
+
+/// The worker process of `test_distributed_proof_batch`, which computes the
+/// jobs it is given. It is ignored, as it needs the jobs to run.
+#[test]
+#[ignore]
+fn distributed_worker() {
+    let (jobs_path, results_path) = match (
+        env::var(DISTRIBUTED_JOBS_ENV),
+        env::var(DISTRIBUTED_RESULTS_ENV),
+    ) {
+        (Ok(jobs_path), Ok(results_path)) => (jobs_path, results_path),
+        _ => panic!("the worker is only run by test_distributed_proof_batch"),
+    };
+
+    let params = distributed_params();
+
+    let mut jobs = BufReader::new(File::open(jobs_path).unwrap());
+    let mut results = BufWriter::new(File::create(results_path).unwrap());
+    let num_jobs = jobs.read_u32::<BigEndian>().unwrap();
+    results.write_u32::<BigEndian>(num_jobs).unwrap();
+    for _ in 0..num_jobs {
+        let job = MultiexpJob::<Bls12>::read(&mut jobs).unwrap();
+        let result = compute_multiexp_job(&job, &params).unwrap();
+        result.write(&mut results).unwrap();
+    }
+    results.flush().unwrap();
+}
+
+#[test]
+fn test_distributed_proof_batch() {
+    let params = distributed_params();
+    let pvk = prepare_verifying_key(&params.vk);
+
+    let mut rng = XorShiftRng::from_seed([
+        0x3d, 0xbe, 0x62, 0x59, 0x8d, 0x31, 0x3d, 0x76, 0x32, 0x37, 0xdb, 0x17, 0xe5, 0xbc, 0x06,
+        0x54,
+    ]);
+    let circuits = (0..2)
+        .map(|_| SquareRoots::<Bls12> {
+            roots: (0..8).map(|_| Some(BlsFr::random(&mut rng))).collect(),
+        })
+        .collect::<Vec<_>>();
+    let inputs = circuits
+        .iter()
+        .map(|c| {
+            c.roots
+                .iter()
+                .map(|root| {
+                    let mut square = root.unwrap();
+                    square.square();
+                    square
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let r_s = (0..2).map(|_| BlsFr::random(&mut rng)).collect::<Vec<_>>();
+    let s_s = (0..2).map(|_| BlsFr::random(&mut rng)).collect::<Vec<_>>();
+
+    // Jobs of three exponents split the queries unevenly, and the queries
+    // with a density into jobs whose bases do not start at a multiple of
+    // three.
+    let mut distributed =
+        create_distributed_proof_batch(circuits.clone(), &params, r_s.clone(), s_s.clone(), 3)
+            .unwrap();
+    let num_jobs = distributed.jobs().len();
+    assert_eq!(distributed.pending_jobs().count(), num_jobs);
+    match distributed.add_result(MultiexpResult {
+        id: num_jobs,
+        output: MultiexpOutput::G1(<G1 as groupy::CurveProjective>::zero()),
+    }) {
+        Err(SynthesisError::UnexpectedJobResult(id)) => assert_eq!(id, num_jobs),
+        _ => panic!("expected the result to be rejected"),
+    }
+
+    match create_distributed_proof_batch(circuits.clone(), &params, r_s.clone(), s_s.clone(), 0) {
+        Err(SynthesisError::EmptyMultiexpJobs) => {}
+        _ => panic!("expected EmptyMultiexpJobs"),
+    }
+
+    // Jobs claiming more exponents or density than they hold are rejected.
+    let mut job = vec![];
+    distributed.jobs()[0].write(&mut job).unwrap();
+    assert!(MultiexpJob::<Bls12>::read(&job[..]).is_ok());
+    assert!(MultiexpJob::<Bls12>::read(&job[..job.len() - 1]).is_err());
+    let mut huge = job[..21].to_vec();
+    huge[17..21].copy_from_slice(&u32::max_value().to_be_bytes());
+    for &flag in &[0, 1] {
+        let mut huge = huge.clone();
+        huge.push(flag);
+        assert!(MultiexpJob::<Bls12>::read(&huge[..]).is_err());
+    }
+
+    // Hand the jobs out to two worker processes, which run the
+    // `distributed_worker` test of this binary.
+    let num_workers = 2;
+    let dir = env::temp_dir();
+    let workers = (0..num_workers)
+        .map(|worker| {
+            let jobs_path = dir.join(format!(
+                "bellperson-distributed-{}-{}.jobs",
+                process::id(),
+                worker
+            ));
+            let results_path = jobs_path.with_extension("results");
+
+            let jobs = distributed
+                .jobs()
+                .iter()
+                .skip(worker)
+                .step_by(num_workers)
+                .collect::<Vec<_>>();
+            let mut file = BufWriter::new(File::create(&jobs_path).unwrap());
+            file.write_u32::<BigEndian>(jobs.len() as u32).unwrap();
+            for job in jobs {
+                job.write(&mut file).unwrap();
+            }
+            file.flush().unwrap();
+
+            let child = Command::new(env::current_exe().unwrap())
+                .args(&["groth16::tests::distributed_worker", "--exact", "--ignored"])
+                .env(DISTRIBUTED_JOBS_ENV, &jobs_path)
+                .env(DISTRIBUTED_RESULTS_ENV, &results_path)
+                .stdout(Stdio::null())
+                .spawn()
+                .unwrap();
+
+            (child, jobs_path, results_path)
+        })
+        .collect::<Vec<_>>();
+
+    for (mut child, jobs_path, results_path) in workers {
+        assert!(child.wait().unwrap().success());
+
+        let mut results = BufReader::new(File::open(&results_path).unwrap());
+        let num_results = results.read_u32::<BigEndian>().unwrap();
+        for _ in 0..num_results {
+            let result = MultiexpResult::<Bls12>::read(&mut results).unwrap();
+            distributed.add_result(result).unwrap();
+        }
+
+        fs::remove_file(jobs_path).unwrap();
+        fs::remove_file(results_path).unwrap();
+    }
+    assert_eq!(distributed.pending_jobs().count(), 0);
+
+    let proofs = distributed.finish().unwrap();
+    let expected = create_proof_batch(circuits, &params, r_s, s_s).unwrap();
+    assert_eq!(proofs, expected);
+    for (proof, inputs) in proofs.iter().zip(&inputs) {
+        assert!(verify_proof(&pvk, proof, inputs).unwrap());
+    }
+}
+
+#[test]
+fn test_zkey() {
+    let mut rng = XorShiftRng::from_seed([
+        0x76, 0x3d, 0x59, 0x62, 0x17, 0xdb, 0xbe, 0x5d, 0x54, 0x06, 0x31, 0x8d, 0xe5, 0x37, 0x32,
+        0xbc,
+    ]);
+    let blank = SquareRoots::<Bls12> {
+        roots: vec![None; 3],
+    };
+    let shape = CircuitShape::synthesize(blank.clone()).unwrap();
+    let circuit = SquareRoots::<Bls12> {
+        roots: (0..3).map(|_| Some(BlsFr::random(&mut rng))).collect(),
+    };
+    let inputs = circuit
+        .roots
+        .iter()
+        .map(|root| {
+            let mut square = root.unwrap();
+            square.square();
+            square
+        })
+        .collect::<Vec<_>>();
+
+    // Parameters for the roots of unity of snarkjs are read back as they
+    // were written, without the digest.
+    let params =
+        generate_random_parameters_in(blank.clone(), QapDomain::Snarkjs, &mut rng).unwrap();
+    let mut zkey = vec![];
+    params.write_zkey(&shape, &mut zkey).unwrap();
+    let de_params = Parameters::<Bls12>::read_zkey(&zkey[..], true).unwrap();
+    let mut vk = params.vk.clone();
+    vk.digest = None;
+    assert!(de_params.vk == vk);
+    assert_eq!(de_params.h, params.h);
+    assert_eq!(de_params.l, params.l);
+    assert_eq!(de_params.a, params.a);
+    assert_eq!(de_params.b_g1, params.b_g1);
+    assert_eq!(de_params.b_g2, params.b_g2);
+
+    let pvk = prepare_verifying_key(&de_params.vk);
+    let proof = create_random_proof(circuit.clone(), &de_params, &mut rng).unwrap();
+    assert!(verify_proof(&pvk, &proof, &inputs).unwrap());
+
+    // Mapped parameters converted from the key prove, and write the same key.
+    let path = env::temp_dir().join(format!("bellperson-zkey-{}.params", process::id()));
+    let mapped = MappedParameters::build_from_zkey(&zkey[..], path.clone(), true).unwrap();
+    assert!(mapped.vk == de_params.vk);
+    let proof = create_random_proof(circuit.clone(), &mapped, &mut rng).unwrap();
+    assert!(verify_proof(&pvk, &proof, &inputs).unwrap());
+
+    let mut mapped_zkey = vec![];
+    mapped.write_zkey(&shape, &mut mapped_zkey).unwrap();
+    assert_eq!(mapped_zkey, zkey);
+    drop(mapped);
+    fs::remove_file(path).unwrap();
+
+    // Parameters read from a key keep the roots of snarkjs when they are
+    // serialized, and still prove.
+    let mut v = vec![];
+    de_params.write(&mut v).unwrap();
+    let de_de_params = Parameters::<Bls12>::read(&v[..], true).unwrap();
+    assert_eq!(de_de_params.vk.domain(), QapDomain::Snarkjs);
+    assert!(de_de_params.vk == de_params.vk);
+    let proof = create_random_proof(circuit, &de_de_params, &mut rng).unwrap();
+    assert!(verify_proof(&pvk, &proof, &inputs).unwrap());
+
+    // Keys are only written with the shape of the circuit of the parameters.
+    let other = CircuitShape::synthesize(SquareRoots::<Bls12> {
+        roots: vec![None; 4],
+    })
+    .unwrap();
+    assert!(de_params.write_zkey(&other, std::io::sink()).is_err());
+
+    // Parameters for the roots of unity of bellperson cannot be written, as
+    // snarkjs would prove with other roots.
+    let params = generate_random_parameters(blank, &mut rng).unwrap();
+    assert_eq!(
+        params
+            .write_zkey(&shape, std::io::sink())
+            .unwrap_err()
+            .kind(),
+        std::io::ErrorKind::InvalidInput
+    );
+}
+
+#[test]
+fn test_snarkjs_zkey() {
+    // A key for `SquareRoots` with two roots, laid out as snarkjs writes it
+    // with `zkey new`: gamma and delta are one, and it has no contributions.
+    const ZKEY: &[u8] = include_bytes!("snarkjs/square_roots.zkey");
+
+    let mut rng = XorShiftRng::from_seed([
+        0x54, 0x06, 0x76, 0x3d, 0x59, 0x62, 0x17, 0xdb, 0xbe, 0x5d, 0x31, 0x8d, 0xe5, 0x37, 0x32,
+        0xbc,
+    ]);
+    let params = Parameters::<Bls12>::read_zkey(ZKEY, true).unwrap();
+    assert_eq!(params.vk.domain(), QapDomain::Snarkjs);
+    assert_eq!(params.vk.digest(), None);
+    assert_eq!(params.vk.ic.len(), 3);
+
+    let circuit = SquareRoots::<Bls12> {
+        roots: (0..2).map(|_| Some(BlsFr::random(&mut rng))).collect(),
+    };
+    let inputs = circuit
+        .roots
+        .iter()
+        .map(|root| {
+            let mut square = root.unwrap();
+            square.square();
+            square
+        })
+        .collect::<Vec<_>>();
+
+    let pvk = prepare_verifying_key(&params.vk);
+    let proof = create_random_proof(circuit, &params, &mut rng).unwrap();
+    assert!(verify_proof(&pvk, &proof, &inputs).unwrap());
+    assert!(!verify_proof(&pvk, &proof, &[inputs[1], inputs[0]]).unwrap());
+
+    // The parameters write a key which is read back as they are.
+    let shape = CircuitShape::synthesize(SquareRoots::<Bls12> {
+        roots: vec![None; 2],
+    })
+    .unwrap();
+    let mut zkey = vec![];
+    params.write_zkey(&shape, &mut zkey).unwrap();
+    let de_params = Parameters::<Bls12>::read_zkey(&zkey[..], true).unwrap();
+    assert!(de_params.vk == params.vk);
+    assert_eq!(de_params.h, params.h);
+    assert_eq!(de_params.l, params.l);
+    assert_eq!(de_params.a, params.a);
+    assert_eq!(de_params.b_g1, params.b_g1);
+    assert_eq!(de_params.b_g2, params.b_g2);
+}
+
+#[cfg(feature = "snarkjs")]
+#[test]
+fn test_snarkjs_json() {
+    use super::{read_public_inputs_json, write_public_inputs_json, Proof};
+
+    let mut rng = XorShiftRng::from_seed([
+        0x17, 0xdb, 0x76, 0x3d, 0x59, 0x62, 0xbe, 0x5d, 0xe5, 0x37, 0x54, 0x06, 0x31, 0x8d, 0x32,
+        0xbc,
+    ]);
+    let params = generate_random_parameters(
+        SquareRoots::<Bls12> {
+            roots: vec![None; 2],
+        },
+        &mut rng,
+    )
+    .unwrap();
+    let circuit = SquareRoots::<Bls12> {
+        roots: (0..2).map(|_| Some(BlsFr::random(&mut rng))).collect(),
+    };
+    let inputs = circuit
+        .roots
+        .iter()
+        .map(|root| {
+            let mut square = root.unwrap();
+            square.square();
+            square
+        })
+        .collect::<Vec<_>>();
+    let proof = create_random_proof(circuit, &params, &mut rng).unwrap();
+
+    // The proof is verified with the files snarkjs would verify it with.
+    let mut vk_json = vec![];
+    params.vk.write_json(&mut vk_json).unwrap();
+    let mut proof_json = vec![];
+    proof.write_json(&mut proof_json).unwrap();
+    let mut public_json = vec![];
+    write_public_inputs_json(&inputs, &mut public_json).unwrap();
+
+    let vk = VerifyingKey::<Bls12>::read_json(&vk_json[..]).unwrap();
+    let de_proof = Proof::<Bls12>::read_json(&proof_json[..]).unwrap();
+    let de_inputs = read_public_inputs_json(&public_json[..]).unwrap();
+    assert_eq!(vk.ic, params.vk.ic);
+    assert_eq!(de_proof, proof);
+    assert_eq!(de_inputs, inputs);
+
+    let pvk = prepare_verifying_key(&vk);
+    assert!(verify_proof(&pvk, &de_proof, &de_inputs).unwrap());
+    assert!(!verify_proof(&pvk, &de_proof, &[de_inputs[1], de_inputs[0]]).unwrap());
+
+    // The key read back writes the same file.
+    let mut de_vk_json = vec![];
+    vk.write_json(&mut de_vk_json).unwrap();
+    assert_eq!(de_vk_json, vk_json);
+}
@@ -0,0 +1,93 @@
+// SPDX-License-Identifier: MIT
+// Generated by bellperson from a Groth16 verifying key.
+pragma solidity ^0.8.0;
+
+/// Verifies Groth16 proofs over BLS12-381 with the precompiles of EIP-2537.
+///
+/// Points are encoded as in EIP-2537: each coordinate takes 64 bytes, and the
+/// coordinates of points in G2 are written as c0 followed by c1.
+contract Verifier {
+    address constant G1_MSM = address(0x0c);
+    address constant PAIRING_CHECK = address(0x0f);
+
+    // The order of the scalar field, which public inputs must be below.
+    uint256 constant R = 0x73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000001;
+
+    uint256 constant NUM_INPUTS = 2;
+
+    // The elements of IC in G1.
+    bytes constant IC =
+        hex"000000000000000000000000000000001098f178f84fc753a76bb63709e9be91eec3ff5f7f3a5f4836f34fe8a1a6d6c5578d8fd820573cef3a01e2bfef3eaf3a"
+        hex"000000000000000000000000000000000ea923110b733b531006075f796cc9368f2477fe26020f465468efbb380ce1f8eebaf5c770f31d320f9bd378dc758436"
+        hex"000000000000000000000000000000001271205227c7aa27f45f20b3ba380dfea8b51efae91fd32e552774c99e2a1237aa59c0c43f52aad99bba3783ea2f36a4"
+        hex"000000000000000000000000000000001407ffc2c1a2fe3b00d1f91e1f4febcda31004f7c301075c9031c55dd3dfa8104b156a6a3b7017fccd27f81c2af222ef"
+        hex"000000000000000000000000000000000c8b694b04d98a749a0763c72fc020ef61b2bb3f63ebb182cb2e568f6a8b9ca3ae013ae78317599e7e7ba2a528ec754a"
+        hex"000000000000000000000000000000000951b70c206350e1edc2aefdfaa95318368c151e01e468b9fb1cf7c3c6575e4f06c135715cc5e51e1b492d19adf9bee0";
+
+    // alpha in G1, followed by -beta in G2.
+    bytes constant ALPHA_NEG_BETA =
+        hex"0000000000000000000000000000000010e7791fb972fe014159aa33a98622da3cdc98ff707965e536d8636b5fcc5ac7a91a8c46e59a00dca575af0f18fb13dc"
+        hex"0000000000000000000000000000000016ba437edcc6551e30c10512367494bfb6b01cc6681e8a4c3cd2501832ab5c4abc40b4578b85cbaffbf0bcd70d67c6e2"
+        hex"00000000000000000000000000000000049cd1dbb2d2c3581e54c088135fef36505a6823d61b859437bfc79b617030dc8b40e32bad1fa85b9c0f368af6d38d3c"
+        hex"000000000000000000000000000000000d0273f6bf31ed37c3b8d68083ec3d8e20b5f2cc170fa24b9b5be35b34ed013f9a921f1cad1644d4bdb14674247234c8"
+        hex"000000000000000000000000000000001149639c79ffba82a4b71f73b11f186f8016a4686ab17ed0ec3d7bc6e476c6ee04c3f3c2d48b1d4ddfac073266ebddce"
+        hex"00000000000000000000000000000000141418b3e4c84511f485fcc78b80b8bc623d6f3f1282e6da09f9c1860402272ba7129c72c4fcd2174f8ac87671053a8b";
+
+    // -gamma in G2.
+    bytes constant NEG_GAMMA =
+        hex"0000000000000000000000000000000009303f04d568e289a35102b6df883d5ed620355c0eb5d02236718cdaf99fba6e19ef5cee2996268eb9a53ae1ee09bce3"
+        hex"000000000000000000000000000000000190be857d602284393305bfe0a29e29a6982ed3f04ccaabafb7e59cdc7eda85c22bc3e8690355c7a0fb7590ae40f1b0"
+        hex"0000000000000000000000000000000003113d5298ba1fe4b0fbc88aea3cf65ce6ce7f9dc43c0a70f6e05a6105867de77193a61d10a3136fc27a38fc9e893a78"
+        hex"000000000000000000000000000000000257b9ffec2bf19dc708dcb4e862919f201a7046830c3a49a2cb095f15d74dd1571e6f4b95e89feeca1642aee8177948";
+
+    // -delta in G2.
+    bytes constant NEG_DELTA =
+        hex"00000000000000000000000000000000152110e866f1a6e8c5348f6e005dbd93de671b7d0fbfa04d6614bcdd27a3cb2a70f0deacb3608ba95226268481a0be7c"
+        hex"000000000000000000000000000000000bf78a97086750eb166986ed8e428ca1d23ae3bbf8b2ee67451d7dd84445311e8bc8ab558b0bc008199f577195fc39b7"
+        hex"0000000000000000000000000000000011bb53988c727613f35bf6dbb45f4809ecfd867762cf7884a08ece4449ab944dc9fc6579d443a31565f52d520d78f54a"
+        hex"000000000000000000000000000000000fd782803c1ac14869f98e9117a0e2266300bbbe8e72097a58e70acd3b5e3ecf9f8a72ebd2ef69e5120ea6fda4719df6";
+
+    /// Returns whether `proof`, which holds A, B and C, is valid for the
+    /// public inputs `input`.
+    function verifyProof(bytes calldata proof, uint256[] calldata input) public view returns (bool) {
+        require(proof.length == 512, "invalid proof length");
+        require(input.length == NUM_INPUTS, "invalid number of public inputs");
+
+        // IC[0] + input[0] * IC[1] + ... + input[n - 1] * IC[n]
+        bytes memory ic = IC;
+        bytes memory msm = new bytes(160 * (NUM_INPUTS + 1));
+        for (uint256 i = 0; i <= NUM_INPUTS; i++) {
+            uint256 scalar = 1;
+            if (i > 0) {
+                scalar = input[i - 1];
+                require(scalar < R, "public input is not in the scalar field");
+            }
+
+            assembly {
+                let src := add(add(ic, 32), mul(i, 128))
+                let dst := add(add(msm, 32), mul(i, 160))
+                mstore(dst, mload(src))
+                mstore(add(dst, 32), mload(add(src, 32)))
+                mstore(add(dst, 64), mload(add(src, 64)))
+                mstore(add(dst, 96), mload(add(src, 96)))
+                mstore(add(dst, 128), scalar)
+            }
+        }
+
+        (bool ok, bytes memory acc) = G1_MSM.staticcall(msm);
+        require(ok && acc.length == 128, "multi-scalar multiplication failed");
+
+        // e(A, B) * e(alpha, -beta) * e(acc, -gamma) * e(C, -delta) == 1
+        bytes memory ab = proof[0:384];
+        bytes memory c = proof[384:512];
+        bytes memory pairs = abi.encodePacked(ab, ALPHA_NEG_BETA, acc, NEG_GAMMA, c, NEG_DELTA);
+
+        bytes memory result;
+        (ok, result) = PAIRING_CHECK.staticcall(pairs);
+        if (!ok || result.length != 32) {
+            return false;
+        }
+
+        return abi.decode(result, (uint256)) == 1;
+    }
+}
@@ -6,6 +6,9 @@ use memmap::Mmap;
 use std::io::{self, Read, Write};
 use std::mem;
 
+use crate::digest::CircuitDigest;
+use crate::domain::QapDomain;
+
 #[derive(Clone)]
 pub struct VerifyingKey<E: Engine> {
     // alpha in g1 for verifying and for creating A/C elements of
@@ -32,8 +35,31 @@ pub struct VerifyingKey<E: Engine> {
     // this is the same size as the number of inputs, and never contains points
     // at infinity.
     pub ic: Vec<E::G1Affine>,
+
+    // Digest of the shape of the circuit these parameters were generated
+    // for. Absent for keys which were serialized before digests existed.
+    pub(crate) digest: Option<CircuitDigest>,
+
+    // Roots of unity the constraints of the circuit are assigned to in the
+    // QAP these parameters were generated for. Only parameters converted from
+    // snarkjs use other roots than bellperson.
+    pub(crate) domain: QapDomain,
 }
 
+// Keys which carry more than the points of the original format start with
+// `VK_MAGIC`, the version of the format and its flags. An uncompressed point
+// never starts with the byte 0xff, so keys without this header are read in the
+// original format. Keys which only have the points are still written in it.
+const VK_MAGIC: [u8; 4] = [0xff, b'B', b'V', b'K'];
+const VK_VERSION: u32 = 1;
+
+// Set in the flags of the header if the digest follows the header.
+const DIGEST_FLAG: u32 = 1;
+// Set in the flags of the header if the QAP uses the roots of unity of
+// snarkjs.
+const SNARKJS_DOMAIN_FLAG: u32 = 2;
+const KNOWN_FLAGS: u32 = DIGEST_FLAG | SNARKJS_DOMAIN_FLAG;
+
 impl<E: Engine> PartialEq for VerifyingKey<E> {
     fn eq(&self, other: &Self) -> bool {
         self.alpha_g1 == other.alpha_g1
@@ -43,11 +69,130 @@ impl<E: Engine> PartialEq for VerifyingKey<E> {
             && self.delta_g1 == other.delta_g1
             && self.delta_g2 == other.delta_g2
             && self.ic == other.ic
+            && self.digest == other.digest
+            && self.domain == other.domain
+    }
+}
+
+/// What the header of a key carries besides its points.
+struct Header {
+    digest: Option<CircuitDigest>,
+    domain: QapDomain,
+}
+
+impl Default for Header {
+    fn default() -> Self {
+        Header {
+            digest: None,
+            domain: QapDomain::Bellperson,
+        }
     }
 }
 
+/// Reads the header of a key, whose first four bytes are in `magic`. Returns
+/// `None` if the key has no header.
+fn read_header<R: Read>(magic: [u8; 4], mut reader: R) -> io::Result<Option<Header>> {
+    if magic != VK_MAGIC {
+        return Ok(None);
+    }
+
+    let version = reader.read_u32::<BigEndian>()?;
+    if version != VK_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unsupported verifying key version {}", version),
+        ));
+    }
+    let flags = reader.read_u32::<BigEndian>()?;
+    if flags & !KNOWN_FLAGS != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unknown verifying key flags {:#x}", flags),
+        ));
+    }
+
+    let mut header = Header::default();
+    if flags & DIGEST_FLAG != 0 {
+        let mut digest = [0u8; 32];
+        reader.read_exact(&mut digest)?;
+        header.digest = Some(CircuitDigest(digest));
+    }
+    if flags & SNARKJS_DOMAIN_FLAG != 0 {
+        header.domain = QapDomain::Snarkjs;
+    }
+
+    Ok(Some(header))
+}
+
+/// The `len` bytes of `mmap` at `offset`, which is advanced past them.
+fn mmap_slice<'a>(mmap: &'a Mmap, offset: &mut usize, len: usize) -> io::Result<&'a [u8]> {
+    let slice = offset
+        .checked_add(len)
+        .and_then(|end| mmap.get(*offset..end))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the verifying key is truncated",
+            )
+        })?;
+    *offset += len;
+    Ok(slice)
+}
+
 impl<E: Engine> VerifyingKey<E> {
+    /// A key for the points of a circuit whose parameters were generated
+    /// without a digest, for the roots of unity of bellperson.
+    pub fn new(
+        alpha_g1: E::G1Affine,
+        beta_g1: E::G1Affine,
+        beta_g2: E::G2Affine,
+        gamma_g2: E::G2Affine,
+        delta_g1: E::G1Affine,
+        delta_g2: E::G2Affine,
+        ic: Vec<E::G1Affine>,
+    ) -> Self {
+        VerifyingKey {
+            alpha_g1,
+            beta_g1,
+            beta_g2,
+            gamma_g2,
+            delta_g1,
+            delta_g2,
+            ic,
+            digest: None,
+            domain: QapDomain::Bellperson,
+        }
+    }
+
+    /// The digest of the circuit the parameters of this key were generated
+    /// for, if known. The prover checks it against the circuits it proves.
+    pub fn digest(&self) -> Option<CircuitDigest> {
+        self.digest
+    }
+
+    /// The roots of unity the constraints of the circuit are assigned to in
+    /// the QAP the parameters of this key were generated for.
+    pub fn domain(&self) -> QapDomain {
+        self.domain
+    }
+
     pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut flags = 0;
+        if self.digest.is_some() {
+            flags |= DIGEST_FLAG;
+        }
+        if self.domain == QapDomain::Snarkjs {
+            flags |= SNARKJS_DOMAIN_FLAG;
+        }
+        if flags != 0 {
+            writer.write_all(&VK_MAGIC)?;
+            writer.write_u32::<BigEndian>(VK_VERSION)?;
+            writer.write_u32::<BigEndian>(flags)?;
+            if let Some(digest) = self.digest {
+                writer.write_all(&digest.0)?;
+            }
+        }
+
         writer.write_all(self.alpha_g1.into_uncompressed().as_ref())?;
         writer.write_all(self.beta_g1.into_uncompressed().as_ref())?;
         writer.write_all(self.beta_g2.into_uncompressed().as_ref())?;
@@ -66,7 +211,17 @@ impl<E: Engine> VerifyingKey<E> {
         let mut g1_repr = <E::G1Affine as CurveAffine>::Uncompressed::empty();
         let mut g2_repr = <E::G2Affine as CurveAffine>::Uncompressed::empty();
 
-        reader.read_exact(g1_repr.as_mut())?;
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        let header = read_header(magic, &mut reader)?;
+        if header.is_some() {
+            reader.read_exact(g1_repr.as_mut())?;
+        } else {
+            // The key has no header, so the bytes read belong to alpha.
+            g1_repr.as_mut()[..magic.len()].copy_from_slice(&magic);
+            reader.read_exact(&mut g1_repr.as_mut()[magic.len()..])?;
+        }
+        let Header { digest, domain } = header.unwrap_or_default();
         let alpha_g1 = g1_repr
             .into_affine()
             .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
@@ -127,6 +282,8 @@ impl<E: Engine> VerifyingKey<E> {
             delta_g1,
             delta_g2,
             ic,
+            digest,
+            domain,
         })
     }
 
@@ -138,7 +295,7 @@ impl<E: Engine> VerifyingKey<E> {
         let read_g1 = |mmap: &Mmap,
                        offset: &mut usize|
          -> Result<<E as paired::Engine>::G1Affine, std::io::Error> {
-            let ptr = &mmap[*offset..*offset + g1_len];
+            let ptr = mmap_slice(mmap, offset, g1_len)?;
             // Safety: this operation is safe, because it's simply
             // casting to a known struct at the correct offset, given
             // the structure of the on-disk data.
@@ -146,7 +303,6 @@ impl<E: Engine> VerifyingKey<E> {
                 *(ptr as *const [u8] as *const <E::G1Affine as CurveAffine>::Uncompressed)
             };
 
-            *offset += g1_len;
             g1_repr
                 .into_affine()
                 .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
@@ -155,7 +311,7 @@ impl<E: Engine> VerifyingKey<E> {
         let read_g2 = |mmap: &Mmap,
                        offset: &mut usize|
          -> Result<<E as paired::Engine>::G2Affine, std::io::Error> {
-            let ptr = &mmap[*offset..*offset + g2_len];
+            let ptr = mmap_slice(mmap, offset, g2_len)?;
             // Safety: this operation is safe, because it's simply
             // casting to a known struct at the correct offset, given
             // the structure of the on-disk data.
@@ -163,12 +319,23 @@ impl<E: Engine> VerifyingKey<E> {
                 *(ptr as *const [u8] as *const <E::G2Affine as CurveAffine>::Uncompressed)
             };
 
-            *offset += g2_len;
             g2_repr
                 .into_affine()
                 .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
         };
 
+        let mut magic = [0u8; 4];
+        magic.copy_from_slice(mmap_slice(mmap, &mut *offset, magic.len())?);
+        let mut rest = &mmap[*offset..];
+        let header = read_header(magic, &mut rest)?;
+        if header.is_some() {
+            *offset = mmap.len() - rest.len();
+        } else {
+            // The key has no header, so the bytes read belong to alpha.
+            *offset -= magic.len();
+        }
+        let Header { digest, domain } = header.unwrap_or_default();
+
         let alpha_g1 = read_g1(&mmap, &mut *offset)?;
         let beta_g1 = read_g1(&mmap, &mut *offset)?;
         let beta_g2 = read_g2(&mmap, &mut *offset)?;
@@ -176,9 +343,8 @@ impl<E: Engine> VerifyingKey<E> {
         let delta_g1 = read_g1(&mmap, &mut *offset)?;
         let delta_g2 = read_g2(&mmap, &mut *offset)?;
 
-        let mut raw_ic_len = &mmap[*offset..*offset + u32_len];
+        let mut raw_ic_len = mmap_slice(mmap, &mut *offset, u32_len)?;
         let ic_len = raw_ic_len.read_u32::<BigEndian>()? as usize;
-        *offset += u32_len;
 
         let mut ic = vec![];
 
@@ -206,6 +372,8 @@ impl<E: Engine> VerifyingKey<E> {
             delta_g1,
             delta_g2,
             ic,
+            digest,
+            domain,
         })
     }
 }
@@ -0,0 +1,666 @@
+//! Conversion of parameters over BLS12-381 from and to the `.zkey` proving
+//! keys of [snarkjs], which hold the same queries in other forms:
+//!
+//! - The coordinates of points are written in little-endian Montgomery form.
+//! - The A and B queries hold a point for every variable, including the
+//!   points at infinity which bellperson leaves out. The C query is the L
+//!   query.
+//! - The H query holds the Lagrange polynomials of the odd roots of unity of a
+//!   domain of twice the size, evaluated at tau and divided by delta, instead
+//!   of `tau^i * t(tau) / delta`. One is converted into the other with an FFT
+//!   over the points.
+//! - snarkjs assigns the constraints to other roots of unity, see `QapDomain`.
+//!   Parameters read from a key keep the roots of snarkjs, and only parameters
+//!   for those roots are written to keys.
+//!
+//! [snarkjs]: https://github.com/iden3/snarkjs
+
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use ff::{Field, PrimeField, PrimeFieldRepr};
+use groupy::{CurveAffine, CurveProjective, EncodedPoint};
+use lazy_static::lazy_static;
+use paired::bls12_381::{Bls12, Fq, FqRepr, Fr, G1Affine, G2Affine, G1};
+use rayon::prelude::*;
+
+use super::{CircuitShape, MappedParameters, Parameters, VerifyingKey};
+use crate::domain::{EvaluationDomain, Point, QapDomain};
+use crate::multicore::Worker;
+use crate::Index;
+
+const MAGIC: &[u8; 4] = b"zkey";
+const VERSION: u32 = 1;
+
+/// The protocol of Groth16 keys, in the header section.
+const GROTH16: u32 = 1;
+
+// The sections of a key, which are written in this order.
+const HEADER: u32 = 1;
+const GROTH16_HEADER: u32 = 2;
+const IC: u32 = 3;
+const COEFFS: u32 = 4;
+const POINTS_A: u32 = 5;
+const POINTS_B1: u32 = 6;
+const POINTS_B2: u32 = 7;
+const POINTS_C: u32 = 8;
+const POINTS_H: u32 = 9;
+const CONTRIBUTIONS: u32 = 10;
+const NUM_SECTIONS: u32 = 10;
+
+/// The size in bytes of a coordinate of a point, in the base field.
+const FQ_BYTES: usize = 48;
+
+/// The size in bytes of a scalar.
+const FR_BYTES: usize = 32;
+
+const G1_BYTES: usize = 2 * FQ_BYTES;
+const G2_BYTES: usize = 4 * FQ_BYTES;
+
+/// The size in bytes of the hash of the circuit in the contributions section.
+const CS_HASH_BYTES: usize = 64;
+
+lazy_static! {
+    /// The Montgomery factor `2^384` of the base field, and its inverse.
+    static ref FQ_R: (Fq, Fq) = {
+        let r = montgomery_factor::<Fq>(FQ_BYTES);
+        (r, r.inverse().expect("2^384 is not zero"))
+    };
+
+    /// The square of the Montgomery factor `2^256` of the scalar field, by
+    /// which snarkjs multiplies the coefficients of the constraints.
+    static ref FR_R2: Fr = {
+        let mut r2 = montgomery_factor::<Fr>(FR_BYTES);
+        r2.square();
+        r2
+    };
+}
+
+impl Parameters<Bls12> {
+    /// Reads parameters from a snarkjs `.zkey` proving key.
+    ///
+    /// The parameters assign the constraints to the roots of unity of
+    /// snarkjs, so they prove circuits whose constraints and variables are in
+    /// the order of the key. The parameters have no digest.
+    pub fn read_zkey<R: Read>(reader: R, checked: bool) -> io::Result<Self> {
+        read_zkey(reader, checked)
+    }
+
+    /// Writes the parameters as a snarkjs `.zkey` proving key, with the
+    /// constraints of `shape`, which must be the circuit the parameters were
+    /// generated for. Parameters for the roots of unity of bellperson are
+    /// refused, as snarkjs would prove with other roots: generate them with
+    /// `generate_random_parameters_in` for `QapDomain::Snarkjs` instead.
+    ///
+    /// The key has no contributions. The parameters lack the last power of
+    /// tau of the H query, which quotient polynomials never use, so snarkjs
+    /// proves with the key but fails to verify it against a ceremony.
+    pub fn write_zkey<W: Write>(&self, shape: &CircuitShape<Bls12>, writer: W) -> io::Result<()> {
+        write_zkey(
+            &self.vk, &self.h, &self.l, &self.a, &self.b_g1, &self.b_g2, shape, writer,
+        )
+    }
+}
+
+impl MappedParameters<Bls12> {
+    /// Converts a snarkjs `.zkey` proving key into parameters, which are
+    /// written to `param_file_path` and mapped from there. See
+    /// `Parameters::read_zkey`.
+    pub fn build_from_zkey<R: Read>(
+        reader: R,
+        param_file_path: PathBuf,
+        checked: bool,
+    ) -> io::Result<Self> {
+        let params = read_zkey(reader, checked)?;
+
+        let mut param_file = BufWriter::new(File::create(&param_file_path)?);
+        params.write(&mut param_file)?;
+        param_file.flush()?;
+        drop(param_file);
+
+        Parameters::build_mapped_parameters(param_file_path, checked)
+    }
+
+    /// Writes the parameters as a snarkjs `.zkey` proving key, see
+    /// `Parameters::write_zkey`.
+    pub fn write_zkey<W: Write>(&self, shape: &CircuitShape<Bls12>, writer: W) -> io::Result<()> {
+        let queries = self.read_queries()?;
+
+        write_zkey(
+            &self.vk,
+            &queries.h,
+            &queries.l,
+            &queries.a,
+            &queries.b_g1,
+            &queries.b_g2,
+            shape,
+            writer,
+        )
+    }
+}
+
+/// The Groth16 header section of a key.
+struct ZkeyHeader {
+    num_vars: usize,
+    num_public: usize,
+    domain_size: usize,
+    alpha_g1: G1Affine,
+    beta_g1: G1Affine,
+    beta_g2: G2Affine,
+    gamma_g2: G2Affine,
+    delta_g1: G1Affine,
+    delta_g2: G2Affine,
+}
+
+fn read_zkey<R: Read>(mut reader: R, checked: bool) -> io::Result<Parameters<Bls12>> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("not a zkey file"));
+    }
+    if reader.read_u32::<LittleEndian>()? != VERSION {
+        return Err(invalid_data("unsupported zkey version"));
+    }
+    let num_sections = reader.read_u32::<LittleEndian>()?;
+
+    let mut protocol = None;
+    let mut header = None;
+    let (mut ic, mut a, mut b_g1, mut b_g2, mut c, mut h) = (None, None, None, None, None, None);
+    for _ in 0..num_sections {
+        let section = reader.read_u32::<LittleEndian>()?;
+        let size = reader.read_u64::<LittleEndian>()?;
+        let mut data = (&mut reader).take(size);
+
+        match section {
+            HEADER => protocol = Some(data.read_u32::<LittleEndian>()?),
+            GROTH16_HEADER => header = Some(read_header(&mut data, checked)?),
+            IC | POINTS_A | POINTS_B1 | POINTS_B2 | POINTS_C | POINTS_H => {
+                let header = header
+                    .as_ref()
+                    .ok_or_else(|| invalid_data("points precede the Groth16 header"))?;
+                let num_vars = header.num_vars;
+                match section {
+                    IC => ic = Some(read_points(&mut data, header.num_public + 1, checked)?),
+                    POINTS_A => a = Some(read_points(&mut data, num_vars, checked)?),
+                    POINTS_B1 => b_g1 = Some(read_points(&mut data, num_vars, checked)?),
+                    POINTS_B2 => b_g2 = Some(read_points(&mut data, num_vars, checked)?),
+                    POINTS_C => {
+                        let num_private = num_vars - header.num_public - 1;
+                        c = Some(read_points(&mut data, num_private, checked)?);
+                    }
+                    _ => h = Some(read_points(&mut data, header.domain_size, checked)?),
+                }
+            }
+            _ => {}
+        }
+
+        // Skip the rest of the section, such as the coefficients of the
+        // constraints, which the parameters do not need.
+        io::copy(&mut data, &mut io::sink())?;
+        if data.limit() != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "truncated zkey section",
+            ));
+        }
+    }
+
+    if protocol != Some(GROTH16) {
+        return Err(invalid_data("not a Groth16 key"));
+    }
+    let missing = |section: &str| invalid_data(&format!("missing the {} section", section));
+    let header = header.ok_or_else(|| missing("Groth16 header"))?;
+    let ic = ic.ok_or_else(|| missing("IC"))?;
+    let a = a.ok_or_else(|| missing("A"))?;
+    let b_g1 = b_g1.ok_or_else(|| missing("B1"))?;
+    let b_g2 = b_g2.ok_or_else(|| missing("B2"))?;
+    let c: Vec<G1Affine> = c.ok_or_else(|| missing("C"))?;
+    let h: Vec<G1Affine> = h.ok_or_else(|| missing("H"))?;
+
+    // The L query never contains points at infinity, see `Parameters`.
+    if c.iter().any(|p| p.is_zero()) {
+        return Err(invalid_data("point at infinity"));
+    }
+
+    let vk = VerifyingKey {
+        alpha_g1: header.alpha_g1,
+        beta_g1: header.beta_g1,
+        beta_g2: header.beta_g2,
+        gamma_g2: header.gamma_g2,
+        delta_g1: header.delta_g1,
+        delta_g2: header.delta_g2,
+        ic,
+        digest: None,
+        domain: QapDomain::Snarkjs,
+    };
+
+    Ok(Parameters::new(
+        vk,
+        Arc::new(h_from_zkey(&h)?),
+        Arc::new(c),
+        // Filter points at infinity away from A/B queries
+        Arc::new(non_zero(a)),
+        Arc::new(non_zero(b_g1)),
+        Arc::new(non_zero(b_g2)),
+    ))
+}
+
+fn read_header<R: Read>(reader: &mut R, checked: bool) -> io::Result<ZkeyHeader> {
+    read_modulus::<Fq, _>(reader, FQ_BYTES)?;
+    read_modulus::<Fr, _>(reader, FR_BYTES)?;
+
+    let num_vars = reader.read_u32::<LittleEndian>()? as usize;
+    let num_public = reader.read_u32::<LittleEndian>()? as usize;
+    let domain_size = reader.read_u32::<LittleEndian>()? as usize;
+    if num_public >= num_vars {
+        return Err(invalid_data("nPublic is not below nVars"));
+    }
+    if !domain_size.is_power_of_two() {
+        return Err(invalid_data("domainSize is not a power of two"));
+    }
+
+    Ok(ZkeyHeader {
+        num_vars,
+        num_public,
+        domain_size,
+        alpha_g1: read_point(reader, checked)?,
+        beta_g1: read_point(reader, checked)?,
+        beta_g2: read_point(reader, checked)?,
+        gamma_g2: read_point(reader, checked)?,
+        delta_g1: read_point(reader, checked)?,
+        delta_g2: read_point(reader, checked)?,
+    })
+}
+
+/// Checks that the key is over the field `F`, whose modulus is written with
+/// its size in bytes.
+fn read_modulus<F: PrimeField, R: Read>(reader: &mut R, len: usize) -> io::Result<()> {
+    if reader.read_u32::<LittleEndian>()? as usize != len {
+        return Err(invalid_data("the key is not over BLS12-381"));
+    }
+    let mut modulus = vec![0u8; len];
+    reader.read_exact(&mut modulus)?;
+    if modulus != modulus_bytes::<F>(len) {
+        return Err(invalid_data("the key is not over BLS12-381"));
+    }
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn write_zkey<W: Write>(
+    vk: &VerifyingKey<Bls12>,
+    h: &[G1Affine],
+    l: &[G1Affine],
+    a: &[G1Affine],
+    b_g1: &[G1Affine],
+    b_g2: &[G2Affine],
+    shape: &CircuitShape<Bls12>,
+    mut writer: W,
+) -> io::Result<()> {
+    if vk.domain() != QapDomain::Snarkjs {
+        return Err(invalid_input(
+            "the parameters are not for the roots of unity of snarkjs",
+        ));
+    }
+    if let Some(digest) = vk.digest() {
+        if digest != shape.digest() {
+            return Err(invalid_input(
+                "the parameters were generated for another circuit",
+            ));
+        }
+    }
+
+    let num_inputs = shape.num_inputs;
+    let num_vars = num_inputs + shape.num_aux;
+    let domain_size = shape.num_constraints().next_power_of_two();
+    if vk.ic.len() != num_inputs || l.len() != shape.num_aux || h.len() + 1 != domain_size {
+        return Err(invalid_input("the parameters do not match the circuit"));
+    }
+
+    // The coefficients of the A and B matrices, as (matrix, constraint,
+    // signal, coefficient), and which signals have points in the queries.
+    let mut coeffs = Vec::new();
+    let mut in_a = vec![false; num_vars];
+    let mut in_b = vec![false; num_vars];
+    for (matrix, lcs, in_query) in vec![(0u32, &shape.a, &mut in_a), (1, &shape.b, &mut in_b)] {
+        for (constraint, lc) in lcs.iter().enumerate() {
+            for (var, coeff) in lc.iter() {
+                if coeff.is_zero() {
+                    continue;
+                }
+                let signal = match var.get_unchecked() {
+                    Index::Input(i) => i,
+                    Index::Aux(i) => num_inputs + i,
+                };
+                in_query[signal] = true;
+                coeffs.push((matrix, constraint as u32, signal as u32, *coeff));
+            }
+        }
+    }
+    let count = |in_query: &[bool]| in_query.iter().filter(|&&used| used).count();
+    if count(&in_a) != a.len() || count(&in_b) != b_g1.len() || count(&in_b) != b_g2.len() {
+        return Err(invalid_input("the parameters do not match the circuit"));
+    }
+
+    let h = h_to_zkey(h)?;
+
+    writer.write_all(MAGIC)?;
+    writer.write_u32::<LittleEndian>(VERSION)?;
+    writer.write_u32::<LittleEndian>(NUM_SECTIONS)?;
+
+    write_section_header(&mut writer, HEADER, 4)?;
+    writer.write_u32::<LittleEndian>(GROTH16)?;
+
+    let header_size = 4 + FQ_BYTES + 4 + FR_BYTES + 3 * 4 + 3 * G1_BYTES + 3 * G2_BYTES;
+    write_section_header(&mut writer, GROTH16_HEADER, header_size)?;
+    writer.write_u32::<LittleEndian>(FQ_BYTES as u32)?;
+    writer.write_all(&modulus_bytes::<Fq>(FQ_BYTES))?;
+    writer.write_u32::<LittleEndian>(FR_BYTES as u32)?;
+    writer.write_all(&modulus_bytes::<Fr>(FR_BYTES))?;
+    writer.write_u32::<LittleEndian>(num_vars as u32)?;
+    writer.write_u32::<LittleEndian>(num_inputs as u32 - 1)?;
+    writer.write_u32::<LittleEndian>(domain_size as u32)?;
+    write_point(&mut writer, &vk.alpha_g1)?;
+    write_point(&mut writer, &vk.beta_g1)?;
+    write_point(&mut writer, &vk.beta_g2)?;
+    write_point(&mut writer, &vk.gamma_g2)?;
+    write_point(&mut writer, &vk.delta_g1)?;
+    write_point(&mut writer, &vk.delta_g2)?;
+
+    write_section_header(&mut writer, IC, vk.ic.len() * G1_BYTES)?;
+    write_points(&mut writer, &vk.ic)?;
+
+    write_section_header(&mut writer, COEFFS, 4 + coeffs.len() * (3 * 4 + FR_BYTES))?;
+    writer.write_u32::<LittleEndian>(coeffs.len() as u32)?;
+    for (matrix, constraint, signal, coeff) in coeffs {
+        writer.write_u32::<LittleEndian>(matrix)?;
+        writer.write_u32::<LittleEndian>(constraint)?;
+        writer.write_u32::<LittleEndian>(signal)?;
+        let mut coeff = coeff;
+        coeff.mul_assign(&FR_R2);
+        coeff.into_repr().write_le(&mut writer)?;
+    }
+
+    write_section_header(&mut writer, POINTS_A, num_vars * G1_BYTES)?;
+    write_query(&mut writer, &in_a, a)?;
+    write_section_header(&mut writer, POINTS_B1, num_vars * G1_BYTES)?;
+    write_query(&mut writer, &in_b, b_g1)?;
+    write_section_header(&mut writer, POINTS_B2, num_vars * G2_BYTES)?;
+    write_query(&mut writer, &in_b, b_g2)?;
+    write_section_header(&mut writer, POINTS_C, l.len() * G1_BYTES)?;
+    write_points(&mut writer, l)?;
+    write_section_header(&mut writer, POINTS_H, h.len() * G1_BYTES)?;
+    write_points(&mut writer, &h)?;
+
+    write_section_header(&mut writer, CONTRIBUTIONS, CS_HASH_BYTES + 4)?;
+    writer.write_all(&[0u8; CS_HASH_BYTES])?;
+    writer.write_u32::<LittleEndian>(0)?;
+
+    Ok(())
+}
+
+fn write_section_header<W: Write>(writer: &mut W, section: u32, size: usize) -> io::Result<()> {
+    writer.write_u32::<LittleEndian>(section)?;
+    writer.write_u64::<LittleEndian>(size as u64)
+}
+
+/// Writes a point for every signal, where those without a point in the query
+/// are at infinity.
+fn write_query<W: Write, G: CurveAffine>(
+    writer: &mut W,
+    in_query: &[bool],
+    points: &[G],
+) -> io::Result<()> {
+    let mut points = points.iter();
+    for &used in in_query {
+        if used {
+            write_point(writer, points.next().expect("the points were counted"))?;
+        } else {
+            write_point(writer, &G::zero())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The `2n`th root of unity of snarkjs `w`, whose odd powers are the roots
+/// of the Lagrange polynomials of the H query of a key of size `n`.
+fn h_root_of_unity(domain_size: usize) -> io::Result<Fr> {
+    let log = domain_size.trailing_zeros() + 1;
+    if log > Fr::S {
+        return Err(invalid_data("the domain of the key is too large"));
+    }
+
+    let mut root = QapDomain::Snarkjs.root_of_unity::<Fr>();
+    for _ in log..Fr::S {
+        root.square();
+    }
+
+    Ok(root)
+}
+
+/// Converts the H query of a key. With `w` from `h_root_of_unity` and
+/// `g = w^2`, `tau^k * t(tau) / delta = -2 * w^k * sum_i g^(ik) * H_i`, which
+/// is an FFT over the points.
+fn h_from_zkey(h: &[G1Affine]) -> io::Result<Vec<G1Affine>> {
+    let domain_size = h.len();
+    let root = h_root_of_unity(domain_size)?;
+    let worker = Worker::new();
+
+    let points = h.iter().map(|p| Point(p.into_projective())).collect();
+    let mut domain = EvaluationDomain::<Bls12, _>::from_coeffs_in(points, QapDomain::Snarkjs)
+        .map_err(synthesis_error)?;
+    domain.fft(&worker, &mut None).map_err(synthesis_error)?;
+    domain.distribute_powers(&worker, root);
+
+    let mut minus_two = Fr::one();
+    minus_two.double();
+    minus_two.negate();
+
+    // The parameters hold the powers of tau up to `n - 2`.
+    let mut points = domain.into_coeffs();
+    points.truncate(domain_size - 1);
+
+    Ok(scale(points, &minus_two))
+}
+
+/// Converts the H query into that of a key, inverting `h_from_zkey`:
+/// `H_i = -1/2n * sum_k g^(-ik) * w^(-k) * tau^k * t(tau) / delta`.
+///
+/// The power of tau `n - 1` is missing from the parameters, and is taken to
+/// be zero, as quotient polynomials have a degree of at most `n - 2`.
+fn h_to_zkey(h: &[G1Affine]) -> io::Result<Vec<G1Affine>> {
+    let domain_size = h.len() + 1;
+    let root = h_root_of_unity(domain_size)?;
+    let worker = Worker::new();
+
+    let mut points = h
+        .iter()
+        .map(|p| Point(p.into_projective()))
+        .collect::<Vec<_>>();
+    points.push(Point(G1::zero()));
+    let mut domain = EvaluationDomain::<Bls12, _>::from_coeffs_in(points, QapDomain::Snarkjs)
+        .map_err(synthesis_error)?;
+    domain.distribute_powers(
+        &worker,
+        root.inverse().expect("roots of unity are not zero"),
+    );
+    domain.ifft(&worker, &mut None).map_err(synthesis_error)?;
+
+    let mut minus_half = Fr::one();
+    minus_half.double();
+    let mut minus_half = minus_half.inverse().expect("2 is not zero");
+    minus_half.negate();
+
+    Ok(scale(domain.into_coeffs(), &minus_half))
+}
+
+fn scale(points: Vec<Point<G1>>, by: &Fr) -> Vec<G1Affine> {
+    points
+        .into_par_iter()
+        .map(|Point(mut p)| {
+            p.mul_assign(by.into_repr());
+            p.into_affine()
+        })
+        .collect()
+}
+
+fn read_points<R: Read, G: CurveAffine>(
+    reader: &mut R,
+    len: usize,
+    checked: bool,
+) -> io::Result<Vec<G>> {
+    (0..len).map(|_| read_point(reader, checked)).collect()
+}
+
+fn write_points<W: Write, G: CurveAffine>(writer: &mut W, points: &[G]) -> io::Result<()> {
+    for point in points {
+        write_point(writer, point)?;
+    }
+
+    Ok(())
+}
+
+/// Points are written as their coordinates in little-endian Montgomery form,
+/// and the point at infinity as zeros.
+fn read_point<R: Read, G: CurveAffine>(reader: &mut R, checked: bool) -> io::Result<G> {
+    let mut encoded = G::Uncompressed::empty();
+    let order = coordinate_order(encoded.as_ref().len());
+
+    let mut coordinates = [0u8; G2_BYTES];
+    let coordinates = &mut coordinates[..order.len() * FQ_BYTES];
+    reader.read_exact(coordinates)?;
+    if coordinates.iter().all(|&b| b == 0) {
+        return Ok(G::zero());
+    }
+
+    for (coordinate, &slot) in coordinates.chunks(FQ_BYTES).zip(order) {
+        let mut repr = FqRepr::default();
+        repr.read_le(coordinate)?;
+        let mut fq =
+            Fq::from_repr(repr).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fq.mul_assign(&FQ_R.1);
+        fq.into_repr()
+            .write_be(&mut encoded.as_mut()[slot * FQ_BYTES..(slot + 1) * FQ_BYTES])?;
+    }
+
+    let point = if checked {
+        encoded.into_affine()
+    } else {
+        encoded.into_affine_unchecked()
+    };
+    point.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_point<W: Write, G: CurveAffine>(writer: &mut W, point: &G) -> io::Result<()> {
+    let encoded = point.into_uncompressed();
+    let order = coordinate_order(encoded.as_ref().len());
+    if point.is_zero() {
+        return writer.write_all(&[0u8; G2_BYTES][..order.len() * FQ_BYTES]);
+    }
+
+    for &slot in order {
+        let mut repr = FqRepr::default();
+        repr.read_be(&encoded.as_ref()[slot * FQ_BYTES..(slot + 1) * FQ_BYTES])?;
+        let mut fq =
+            Fq::from_repr(repr).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fq.mul_assign(&FQ_R.0);
+        fq.into_repr().write_le(&mut *writer)?;
+    }
+
+    Ok(())
+}
+
+/// The slots of the encoding of a point which its coordinates are written
+/// from. The coordinates of points in G2 are written as `c0` followed by
+/// `c1`, while they are encoded as `c1` followed by `c0`.
+fn coordinate_order(encoded_len: usize) -> &'static [usize] {
+    if encoded_len == G1_BYTES {
+        &[0, 1]
+    } else {
+        &[1, 0, 3, 2]
+    }
+}
+
+/// `2^(8 * len)` in the field `F`.
+fn montgomery_factor<F: PrimeField>(len: usize) -> F {
+    let mut r = F::one();
+    for _ in 0..8 * len {
+        r.double();
+    }
+    r
+}
+
+fn modulus_bytes<F: PrimeField>(len: usize) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(len);
+    F::char()
+        .write_le(&mut bytes)
+        .expect("writing to a vector never fails");
+    bytes
+}
+
+fn non_zero<G: CurveAffine>(points: Vec<G>) -> Vec<G> {
+    points.into_iter().filter(|p| !p.is_zero()).collect()
+}
+
+fn synthesis_error(e: crate::SynthesisError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+fn invalid_data(error: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+fn invalid_input(error: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_points() {
+        let g1 = G1Affine::one();
+        let g2 = G2Affine::one();
+
+        let mut v = vec![];
+        write_point(&mut v, &g1).unwrap();
+        write_point(&mut v, &g2).unwrap();
+        write_point(&mut v, &G1Affine::zero()).unwrap();
+        assert_eq!(v.len(), 2 * G1_BYTES + G2_BYTES);
+        assert!(v[G1_BYTES + G2_BYTES..].iter().all(|&b| b == 0));
+
+        // The coordinates are written multiplied by R.
+        let mut x = FqRepr::default();
+        x.read_le(&v[..FQ_BYTES]).unwrap();
+        let mut x = Fq::from_repr(x).unwrap();
+        x.mul_assign(&FQ_R.1);
+        let mut expected = FqRepr::default();
+        expected
+            .read_be(&g1.into_uncompressed().as_ref()[..FQ_BYTES])
+            .unwrap();
+        assert_eq!(x.into_repr(), expected);
+
+        let mut reader = &v[..];
+        assert_eq!(read_point::<_, G1Affine>(&mut reader, true).unwrap(), g1);
+        assert_eq!(read_point::<_, G2Affine>(&mut reader, true).unwrap(), g2);
+        assert!(read_point::<_, G1Affine>(&mut reader, true)
+            .unwrap()
+            .is_zero());
+    }
+
+    #[test]
+    fn test_read_zkey_invalid() {
+        assert!(read_zkey(&b"zkey"[..], true).is_err());
+        assert!(read_zkey(&b"wtns\x01\0\0\0\0\0\0\0"[..], true).is_err());
+
+        // A key without sections.
+        let err = read_zkey(&b"zkey\x01\0\0\0\0\0\0\0"[..], true).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -139,6 +139,7 @@
 #[macro_use]
 extern crate hex_literal;
 
+pub mod digest;
 pub mod domain;
 pub mod gadgets;
 pub mod gpu;
@@ -146,11 +147,11 @@ pub mod gpu;
 pub mod groth16;
 pub mod multicore;
 pub mod multiexp;
+pub mod parallel;
 
 pub mod util_cs;
 use ff::{Field, ScalarEngine};
 
-use ahash::AHashMap as HashMap;
 use std::io;
 use std::marker::PhantomData;
 use std::ops::{Add, Sub};
@@ -167,7 +168,7 @@ pub trait Circuit<E: ScalarEngine> {
 }
 
 /// Represents a variable in our constraint system.
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Variable(Index);
 
 impl Variable {
@@ -185,8 +186,9 @@ impl Variable {
 }
 
 /// Represents the index of either an input variable or
-/// auxiliary variable.
-#[derive(Copy, Clone, PartialEq, Debug, Eq, Hash)]
+/// auxiliary variable. Inputs are ordered before auxiliary
+/// variables, and each kind is ordered by its index.
+#[derive(Copy, Clone, PartialEq, Debug, Eq, PartialOrd, Ord, Hash)]
 pub enum Index {
     Input(usize),
     Aux(usize),
@@ -194,8 +196,13 @@ pub enum Index {
 
 /// This represents a linear combination of some variables, with coefficients
 /// in the scalar field of a pairing-friendly elliptic curve group.
+///
+/// Terms are kept in a vector sorted by variable, with at most one term per
+/// variable. Most linear combinations built during synthesis only have a
+/// handful of terms, so this is considerably cheaper than hashing.
 #[derive(Clone)]
-pub struct LinearCombination<E: ScalarEngine>(HashMap<Variable, E::Fr>);
+pub struct LinearCombination<E: ScalarEngine>(Vec<(Variable, E::Fr)>);
+
 impl<E: ScalarEngine> Default for LinearCombination<E> {
     fn default() -> Self {
         Self::zero()
@@ -204,20 +211,101 @@ impl<E: ScalarEngine> Default for LinearCombination<E> {
 
 impl<E: ScalarEngine> LinearCombination<E> {
     pub fn zero() -> LinearCombination<E> {
-        LinearCombination(HashMap::new())
+        LinearCombination(Vec::new())
     }
 
     pub fn iter(&self) -> impl Iterator<Item = (&Variable, &E::Fr)> + '_ {
-        self.0.iter()
+        self.0.iter().map(|(var, coeff)| (var, coeff))
     }
 
-    pub fn add_unsimplified(mut self, (coeff, var): (E::Fr, Variable)) -> LinearCombination<E> {
-        self.0
-            .entry(var)
-            .or_insert(E::Fr::zero())
-            .add_assign(&coeff);
+    /// Returns the number of distinct variables in this linear combination.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
 
-        self
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn add_unsimplified(self, (coeff, var): (E::Fr, Variable)) -> LinearCombination<E> {
+        self + (coeff, var)
+    }
+
+    /// Adds `coeff` to the coefficient of `var`, inserting a new term in
+    /// sorted position if `var` is not present yet.
+    fn add_term(&mut self, var: Variable, coeff: &E::Fr) {
+        // Gadgets mostly build linear combinations in allocation order, so
+        // check the last term before falling back to a binary search.
+        match self.0.last_mut() {
+            None => {
+                self.0.push((var, *coeff));
+                return;
+            }
+            Some(last) if last.0 == var => {
+                last.1.add_assign(coeff);
+                return;
+            }
+            Some(last) if last.0 < var => {
+                self.0.push((var, *coeff));
+                return;
+            }
+            _ => {}
+        }
+
+        match self.0.binary_search_by(|(v, _)| v.cmp(&var)) {
+            Ok(i) => self.0[i].1.add_assign(coeff),
+            Err(i) => self.0.insert(i, (var, *coeff)),
+        }
+    }
+
+    /// Merges the terms of `other` into `self`, mapping each of its
+    /// coefficients through `f` first. Both sides are sorted, so this is a
+    /// single linear pass.
+    fn merge<F>(mut self, other: &LinearCombination<E>, f: F) -> LinearCombination<E>
+    where
+        F: Fn(&E::Fr) -> E::Fr,
+    {
+        if other.0.is_empty() {
+            return self;
+        }
+        if other.0.len() == 1 {
+            let (var, coeff) = &other.0[0];
+            self.add_term(*var, &f(coeff));
+            return self;
+        }
+
+        let mut merged = Vec::with_capacity(self.0.len() + other.0.len());
+        let mut ours = self.0.into_iter().peekable();
+        let mut theirs = other.0.iter().peekable();
+
+        loop {
+            let take_ours = match (ours.peek(), theirs.peek()) {
+                (Some((a, _)), Some((b, _))) => match a.cmp(b) {
+                    std::cmp::Ordering::Less => Some(true),
+                    std::cmp::Ordering::Greater => Some(false),
+                    std::cmp::Ordering::Equal => None,
+                },
+                (Some(_), None) => Some(true),
+                (None, Some(_)) => Some(false),
+                (None, None) => break,
+            };
+
+            match take_ours {
+                Some(true) => merged.push(ours.next().unwrap()),
+                Some(false) => {
+                    let (var, coeff) = theirs.next().unwrap();
+                    merged.push((*var, f(coeff)));
+                }
+                None => {
+                    let (var, mut coeff) = ours.next().unwrap();
+                    let (_, other_coeff) = theirs.next().unwrap();
+                    coeff.add_assign(&f(other_coeff));
+                    merged.push((var, coeff));
+                }
+            }
+        }
+
+        LinearCombination(merged)
     }
 }
 
@@ -225,10 +313,7 @@ impl<E: ScalarEngine> Add<(E::Fr, Variable)> for LinearCombination<E> {
     type Output = LinearCombination<E>;
 
     fn add(mut self, (coeff, var): (E::Fr, Variable)) -> LinearCombination<E> {
-        self.0
-            .entry(var)
-            .or_insert(E::Fr::zero())
-            .add_assign(&coeff);
+        self.add_term(var, &coeff);
 
         self
     }
@@ -264,52 +349,45 @@ impl<E: ScalarEngine> Sub<Variable> for LinearCombination<E> {
 impl<'a, E: ScalarEngine> Add<&'a LinearCombination<E>> for LinearCombination<E> {
     type Output = LinearCombination<E>;
 
-    fn add(mut self, other: &'a LinearCombination<E>) -> LinearCombination<E> {
-        for (var, val) in &other.0 {
-            self.0.entry(*var).or_insert(E::Fr::zero()).add_assign(val);
-        }
-
-        self
+    fn add(self, other: &'a LinearCombination<E>) -> LinearCombination<E> {
+        self.merge(other, |coeff| *coeff)
     }
 }
 
 impl<'a, E: ScalarEngine> Sub<&'a LinearCombination<E>> for LinearCombination<E> {
     type Output = LinearCombination<E>;
 
-    fn sub(mut self, other: &'a LinearCombination<E>) -> LinearCombination<E> {
-        for (var, val) in &other.0 {
-            self = self - (*val, *var);
-        }
-
-        self
+    fn sub(self, other: &'a LinearCombination<E>) -> LinearCombination<E> {
+        self.merge(other, |coeff| {
+            let mut tmp = *coeff;
+            tmp.negate();
+            tmp
+        })
     }
 }
 
 impl<'a, E: ScalarEngine> Add<(E::Fr, &'a LinearCombination<E>)> for LinearCombination<E> {
     type Output = LinearCombination<E>;
 
-    fn add(mut self, (coeff, other): (E::Fr, &'a LinearCombination<E>)) -> LinearCombination<E> {
-        for s in &other.0 {
-            let mut tmp = *s.1;
+    fn add(self, (coeff, other): (E::Fr, &'a LinearCombination<E>)) -> LinearCombination<E> {
+        self.merge(other, |c| {
+            let mut tmp = *c;
             tmp.mul_assign(&coeff);
-            self = self + (tmp, *s.0);
-        }
-
-        self
+            tmp
+        })
     }
 }
 
 impl<'a, E: ScalarEngine> Sub<(E::Fr, &'a LinearCombination<E>)> for LinearCombination<E> {
     type Output = LinearCombination<E>;
 
-    fn sub(mut self, (coeff, other): (E::Fr, &'a LinearCombination<E>)) -> LinearCombination<E> {
-        for s in &other.0 {
-            let mut tmp = *s.1;
+    fn sub(self, (coeff, other): (E::Fr, &'a LinearCombination<E>)) -> LinearCombination<E> {
+        self.merge(other, |c| {
+            let mut tmp = *c;
             tmp.mul_assign(&coeff);
-            self = self - (tmp, *s.0);
-        }
-
-        self
+            tmp.negate();
+            tmp
+        })
     }
 }
 
@@ -344,6 +422,75 @@ pub enum SynthesisError {
     /// During GPU multiexp/fft, some GPU related error happened
     #[error("encountered a GPU error: {0}")]
     GPUError(#[from] gpu::GPUError),
+    /// During proving, the synthesized witness did not fit the cached circuit shape
+    #[error("witness does not match the circuit shape")]
+    ShapeMismatch,
+    /// During proving, the circuit did not match the circuit the parameters were generated for
+    #[error("circuit digest {actual} does not match the parameters, which were generated for a circuit with digest {expected}")]
+    DigestMismatch {
+        expected: digest::CircuitDigest,
+        actual: digest::CircuitDigest,
+    },
+    /// During batch proving, the parameters did not fit the size of a group of circuits
+    #[error("the parameters are not for circuits with a domain of size {domain_size}, {num_inputs} inputs and {num_aux} auxiliary variables")]
+    ParameterSizeMismatch {
+        domain_size: usize,
+        num_inputs: usize,
+        num_aux: usize,
+    },
+    /// During aggregation, the number of proofs did not match the aggregation SRS
+    #[error("expected {expected} proofs to aggregate, got {actual}")]
+    AggregationSizeMismatch { expected: usize, actual: usize },
+    /// During aggregation, the number of proofs was not a power of two the SRS supports
+    #[error("cannot aggregate {size} proofs with an SRS for up to {max_size}, the number of proofs must be a power of two of at least two")]
+    UnsupportedAggregationSize { size: usize, max_size: usize },
+    /// During batch proving, the number of randomizers did not match the number of circuits
+    #[error("expected randomizers for {expected} circuits, got {actual}")]
+    BatchSizeMismatch { expected: usize, actual: usize },
+    /// During proving, the computation was cancelled or its deadline passed
+    #[error("the computation was cancelled")]
+    Cancelled,
+    /// During distributed proving, a result did not match the job it was given for
+    #[error("unexpected result for multiexp job {0}")]
+    UnexpectedJobResult(usize),
+    /// During distributed proving, jobs were requested without any exponents
+    #[error("multiexp jobs must have at least one exponent")]
+    EmptyMultiexpJobs,
+    /// During distributed proving, the result of a job had not been added
+    #[error("the result of multiexp job {0} is missing")]
+    MissingJobResult(usize),
+    /// The prover produced a proof which does not verify against the verifying key
+    #[error("the prover produced an invalid proof")]
+    InvalidProofProduced,
+    /// During synthesis, a witness did not hold one value per variable
+    #[error("expected a witness of {expected} values, got {actual}")]
+    WitnessLengthMismatch { expected: usize, actual: usize },
+    /// During synthesis through a `ContextCS`, an error occurred at the namespace path `path`
+    #[error("{source} at {path}")]
+    WithContext {
+        path: String,
+        source: Box<SynthesisError>,
+    },
+}
+
+impl SynthesisError {
+    /// The error without the context `ContextCS` attached to it, to match on
+    /// its variant.
+    pub fn root_cause(&self) -> &SynthesisError {
+        match self {
+            SynthesisError::WithContext { source, .. } => source.root_cause(),
+            e => e,
+        }
+    }
+
+    /// The namespace path and annotation at which the error occurred, if it
+    /// was returned through a `ContextCS`.
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            SynthesisError::WithContext { path, .. } => Some(path),
+            _ => None,
+        }
+    }
 }
 
 /// Represents a constraint system which can have new variables
@@ -442,7 +589,7 @@ pub trait ConstraintSystem<E: ScalarEngine>: Sized + Send {
 /// a namespace context) and, when dropped, pops out of the namespace context.
 pub struct Namespace<'a, E: ScalarEngine, CS: ConstraintSystem<E>>(&'a mut CS, SendMarker<E>);
 
-struct SendMarker<E: ScalarEngine>(PhantomData<E>);
+pub(crate) struct SendMarker<E: ScalarEngine>(PhantomData<E>);
 
 impl<E: ScalarEngine> Default for SendMarker<E> {
     fn default() -> Self {
@@ -605,4 +752,87 @@ mod tests {
             _ => panic!("unexpected variable type"),
         });
     }
+
+    #[test]
+    fn test_lc_sorted_merge() {
+        use ff::PrimeField;
+        use paired::bls12_381::{Bls12, Fr};
+        use rand_core::SeedableRng;
+        use rand_xorshift::XorShiftRng;
+
+        let mut rng = XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        let vars = [
+            Variable::new_unchecked(Index::Aux(3)),
+            Variable::new_unchecked(Index::Input(2)),
+            Variable::new_unchecked(Index::Aux(0)),
+            Variable::new_unchecked(Index::Input(0)),
+            Variable::new_unchecked(Index::Aux(7)),
+        ];
+        let coeffs = (0..vars.len())
+            .map(|_| Fr::random(&mut rng))
+            .collect::<Vec<_>>();
+
+        // Insert out of order, so both the fast path and the binary search are exercised.
+        let mut lc1 = LinearCombination::<Bls12>::zero();
+        for (var, coeff) in vars.iter().zip(coeffs.iter()).take(3) {
+            lc1 = lc1 + (*coeff, *var);
+        }
+        let mut lc2 = LinearCombination::<Bls12>::zero();
+        for (var, coeff) in vars.iter().zip(coeffs.iter()).skip(1) {
+            lc2 = lc2 + (*coeff, *var);
+        }
+
+        let sum = lc1.clone() + &lc2;
+        let diff = lc1.clone() - &lc2;
+        let two = Fr::from_str("2").unwrap();
+        let scaled = lc1 + (two, &lc2);
+
+        // Terms are sorted by variable, inputs before aux, without duplicates.
+        let sorted = sum.iter().map(|(var, _)| *var).collect::<Vec<_>>();
+        assert_eq!(
+            sorted,
+            vec![
+                Variable::new_unchecked(Index::Input(0)),
+                Variable::new_unchecked(Index::Input(2)),
+                Variable::new_unchecked(Index::Aux(0)),
+                Variable::new_unchecked(Index::Aux(3)),
+                Variable::new_unchecked(Index::Aux(7)),
+            ]
+        );
+        assert_eq!(sum.len(), 5);
+        assert_eq!(diff.len(), 5);
+        assert_eq!(scaled.len(), 5);
+
+        for (i, (var, coeff)) in vars.iter().zip(coeffs.iter()).enumerate() {
+            let in_lc1 = i < 3;
+            let in_lc2 = i >= 1;
+
+            let mut expected_sum = Fr::zero();
+            let mut expected_diff = Fr::zero();
+            let mut expected_scaled = Fr::zero();
+            if in_lc1 {
+                expected_sum.add_assign(coeff);
+                expected_diff.add_assign(coeff);
+                expected_scaled.add_assign(coeff);
+            }
+            if in_lc2 {
+                expected_sum.add_assign(coeff);
+                expected_diff.sub_assign(coeff);
+                let mut tmp = *coeff;
+                tmp.mul_assign(&two);
+                expected_scaled.add_assign(&tmp);
+            }
+
+            let find = |lc: &LinearCombination<Bls12>| {
+                *lc.iter().find(|(v, _)| *v == var).expect("missing term").1
+            };
+            assert_eq!(find(&sum), expected_sum);
+            assert_eq!(find(&diff), expected_diff);
+            assert_eq!(find(&scaled), expected_scaled);
+        }
+    }
 }
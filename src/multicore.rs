@@ -1,14 +1,25 @@
 //! An interface for dealing with the kinds of parallel computations involved in
-//! `bellperson`. It's currently just a thin wrapper around [`CpuPool`] and
-//! [`rayon`] but may be extended in the future to allow for various
-//! parallelism strategies.
+//! `bellperson`. It's currently just a thin wrapper around [`rayon`] but may be
+//! extended in the future to allow for various parallelism strategies.
 //!
-//! [`CpuPool`]: futures_cpupool::CpuPool
+//! Computations on a [`Worker`] can be aborted through the
+//! [`CancellationToken`] it was created with. Their results are
+//! [`WorkerFuture`]s, which are `std` futures, so they can be awaited without
+//! blocking the executor.
+
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::SynthesisError;
 
 #[cfg(feature = "multicore")]
 mod implementation {
-    use futures::{Future, IntoFuture, Poll};
-    use futures_cpupool::{CpuFuture, CpuPool};
     use lazy_static::lazy_static;
     use num_cpus;
     use std::env;
@@ -27,32 +38,45 @@ mod implementation {
             .num_threads(*NUM_CPUS)
             .build()
             .unwrap();
-        static ref CPU_POOL: CpuPool = CpuPool::new(*NUM_CPUS);
+        static ref CPU_POOL: rayon::ThreadPool = rayon::ThreadPoolBuilder::new()
+            .num_threads(*NUM_CPUS)
+            .build()
+            .unwrap();
     }
 
+    use super::{CancellationToken, WorkerFuture};
+
     #[derive(Clone)]
-    pub struct Worker {}
+    pub struct Worker {
+        cancel: CancellationToken,
+    }
 
     impl Worker {
         pub fn new() -> Worker {
-            Worker {}
+            Worker::with_cancellation(CancellationToken::new())
+        }
+
+        /// A worker whose computations stop early once `cancel` is cancelled.
+        pub fn with_cancellation(cancel: CancellationToken) -> Worker {
+            Worker { cancel }
+        }
+
+        pub fn cancellation(&self) -> &CancellationToken {
+            &self.cancel
         }
 
         pub fn log_num_cpus(&self) -> u32 {
             log2_floor(*NUM_CPUS)
         }
 
-        pub fn compute<F, R>(&self, f: F) -> WorkerFuture<R::Item, R::Error>
+        /// Runs `f` in the background, on a pool separate from the one of
+        /// `scope`, so that waiting for the result there cannot deadlock.
+        pub fn compute<F, R>(&self, f: F) -> WorkerFuture<R>
         where
             F: FnOnce() -> R + Send + 'static,
-            R: IntoFuture + 'static,
-            R::Future: Send + 'static,
-            R::Item: Send + 'static,
-            R::Error: Send + 'static,
+            R: Send + 'static,
         {
-            WorkerFuture {
-                future: CPU_POOL.spawn_fn(f),
-            }
+            WorkerFuture::spawn(f, |job| CPU_POOL.spawn(job))
         }
 
         pub fn scope<'a, F, R>(&self, elements: usize, f: F) -> R
@@ -70,19 +94,6 @@ mod implementation {
         }
     }
 
-    pub struct WorkerFuture<T, E> {
-        future: CpuFuture<T, E>,
-    }
-
-    impl<T: Send + 'static, E: Send + 'static> Future for WorkerFuture<T, E> {
-        type Item = T;
-        type Error = E;
-
-        fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-            self.future.poll()
-        }
-    }
-
     fn log2_floor(num: usize) -> u32 {
         assert!(num > 0);
 
@@ -110,29 +121,37 @@ mod implementation {
 
 #[cfg(not(feature = "multicore"))]
 mod implementation {
-    use futures::{future, Future, IntoFuture, Poll};
+    use super::{CancellationToken, WorkerFuture};
 
     #[derive(Clone)]
-    pub struct Worker;
+    pub struct Worker {
+        cancel: CancellationToken,
+    }
 
     impl Worker {
         pub fn new() -> Worker {
-            Worker
+            Worker::with_cancellation(CancellationToken::new())
+        }
+
+        /// A worker whose computations stop early once `cancel` is cancelled.
+        pub fn with_cancellation(cancel: CancellationToken) -> Worker {
+            Worker { cancel }
+        }
+
+        pub fn cancellation(&self) -> &CancellationToken {
+            &self.cancel
         }
 
         pub fn log_num_cpus(&self) -> u32 {
             0
         }
 
-        pub fn compute<F, R>(&self, f: F) -> R::Future
+        pub fn compute<F, R>(&self, f: F) -> WorkerFuture<R>
         where
             F: FnOnce() -> R + Send + 'static,
-            R: IntoFuture + 'static,
-            R::Future: Send + 'static,
-            R::Item: Send + 'static,
-            R::Error: Send + 'static,
+            R: Send + 'static,
         {
-            f().into_future()
+            WorkerFuture::ready(f())
         }
 
         pub fn scope<F, R>(&self, elements: usize, f: F) -> R
@@ -143,26 +162,225 @@ mod implementation {
         }
     }
 
-    pub struct WorkerFuture<T, E> {
-        future: future::FutureResult<T, E>,
+    pub struct DummyScope;
+
+    impl DummyScope {
+        pub fn spawn<F: FnOnce(&DummyScope)>(&self, f: F) {
+            f(self);
+        }
     }
+}
+
+pub use self::implementation::*;
 
-    impl<T: Send + 'static, E: Send + 'static> Future for WorkerFuture<T, E> {
-        type Item = T;
-        type Error = E;
+/// The result of a computation running in the background. It can be awaited
+/// as a `std` future, or waited for by blocking the current thread. If the
+/// computation panicked, the panic is resumed when the result is taken.
+///
+/// The future is not fused: polling it again, or waiting for it, once it
+/// returned its result panics.
+pub struct WorkerFuture<T> {
+    shared: Arc<Shared<T>>,
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    done: Condvar,
+}
 
-        fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-            self.future.poll()
+struct State<T> {
+    result: Option<thread::Result<T>>,
+    /// Whether the result has been taken.
+    taken: bool,
+    /// When the computation finished.
+    finished: Option<Instant>,
+    waker: Option<Waker>,
+}
+
+impl<T: Send + 'static> WorkerFuture<T> {
+    /// A future which is already resolved to `value`.
+    pub fn ready(value: T) -> Self {
+        WorkerFuture {
+            shared: Arc::new(Shared {
+                state: Mutex::new(State {
+                    result: Some(Ok(value)),
+                    taken: false,
+                    finished: Some(Instant::now()),
+                    waker: None,
+                }),
+                done: Condvar::new(),
+            }),
         }
     }
 
-    pub struct DummyScope;
+    /// Runs `f` through `spawn`, which must run the job it is given exactly
+    /// once, on any thread.
+    pub fn spawn<F, S>(f: F, spawn: S) -> Self
+    where
+        F: FnOnce() -> T + Send + 'static,
+        S: FnOnce(Box<dyn FnOnce() + Send>),
+    {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                result: None,
+                taken: false,
+                finished: None,
+                waker: None,
+            }),
+            done: Condvar::new(),
+        });
 
-    impl DummyScope {
-        pub fn spawn<F: FnOnce(&DummyScope)>(&self, f: F) {
-            f(self);
+        let job_shared = shared.clone();
+        spawn(Box::new(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(f));
+            let finished = Instant::now();
+
+            let mut state = job_shared.state.lock().unwrap();
+            state.result = Some(result);
+            state.finished = Some(finished);
+            let waker = state.waker.take();
+            drop(state);
+
+            job_shared.done.notify_all();
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }));
+
+        WorkerFuture { shared }
+    }
+
+    /// Blocks the current thread until the computation is done.
+    pub fn wait(self) -> T {
+        self.wait_timed().0
+    }
+
+    /// Like `wait`, but also returns when the computation finished, which may
+    /// be long before it was waited for.
+    pub(crate) fn wait_timed(self) -> (T, Instant) {
+        let mut state = self.shared.state.lock().unwrap();
+        loop {
+            if let Some(result) = state.result.take() {
+                let finished = state
+                    .finished
+                    .expect("a finished computation has a finish time");
+                return (unwrap_result(result), finished);
+            }
+            assert!(!state.taken, "WorkerFuture waited for after it completed");
+            state = self.shared.done.wait(state).unwrap();
         }
     }
 }
 
-pub use self::implementation::*;
+impl<T> Future for WorkerFuture<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<T> {
+        let mut state = self.shared.state.lock().unwrap();
+        match state.result.take() {
+            Some(result) => {
+                state.taken = true;
+                drop(state);
+                Poll::Ready(unwrap_result(result))
+            }
+            None => {
+                assert!(!state.taken, "WorkerFuture polled after it completed");
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+fn unwrap_result<T>(result: thread::Result<T>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => panic::resume_unwind(e),
+    }
+}
+
+/// A handle with which long-running computations can be aborted, either
+/// explicitly or once a deadline has passed. Clones share the cancellation
+/// state, so a clone can be handed to the computation and the original kept
+/// to cancel it.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A token which is cancelled at `deadline`, if not before.
+    pub fn with_deadline(deadline: Instant) -> Self {
+        CancellationToken {
+            cancelled: Arc::new(AtomicBool::new(false)),
+            deadline: Some(deadline),
+        }
+    }
+
+    /// A token which is cancelled once `timeout` has elapsed, if not before.
+    pub fn with_timeout(timeout: Duration) -> Self {
+        Self::with_deadline(Instant::now() + timeout)
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+            || self
+                .deadline
+                .map_or(false, |deadline| Instant::now() >= deadline)
+    }
+
+    /// Fails with `SynthesisError::Cancelled` if this token is cancelled.
+    pub fn check(&self) -> Result<(), SynthesisError> {
+        if self.is_cancelled() {
+            Err(SynthesisError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancellation_token() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert!(!clone.is_cancelled());
+        assert!(clone.check().is_ok());
+
+        token.cancel();
+        assert!(clone.is_cancelled());
+        match clone.check() {
+            Err(SynthesisError::Cancelled) => {}
+            _ => panic!("expected the token to be cancelled"),
+        }
+
+        assert!(CancellationToken::with_timeout(Duration::from_secs(0)).is_cancelled());
+        assert!(!CancellationToken::with_timeout(Duration::from_secs(3600)).is_cancelled());
+
+        let worker = Worker::with_cancellation(clone);
+        assert!(worker.cancellation().is_cancelled());
+        assert!(!Worker::new().cancellation().is_cancelled());
+    }
+
+    #[test]
+    fn test_worker_future() {
+        let worker = Worker::new();
+        assert_eq!(worker.compute(|| 6 * 7).wait(), 42);
+        assert_eq!(WorkerFuture::ready("done").wait(), "done");
+
+        let panicked = worker.compute(|| -> u32 { panic!("computation failed") });
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| panicked.wait())).is_err());
+    }
+}
@@ -1,13 +1,15 @@
 use bit_vec::{self, BitVec};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use ff::{Field, PrimeField, PrimeFieldRepr, ScalarEngine};
-use futures::Future;
-use groupy::{CurveAffine, CurveProjective};
+use groupy::{CurveAffine, CurveProjective, EncodedPoint};
 use log::{info, warn};
-use std::io;
+use rayon::prelude::*;
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
 use std::iter;
 use std::sync::Arc;
 
-use super::multicore::Worker;
+use super::multicore::{CancellationToken, Worker, WorkerFuture};
 use super::SynthesisError;
 use crate::gpu;
 
@@ -17,6 +19,12 @@ pub trait SourceBuilder<G: CurveAffine>: Send + Sync + 'static + Clone {
 
     fn new(self) -> Self::Source;
     fn get(self) -> (Arc<Vec<G>>, usize);
+
+    /// The fixed-base table of the bases and the position of the first base
+    /// of this source in it, if one was precomputed.
+    fn fixed_base_table(&self) -> Option<(Arc<FixedBaseTable<G>>, usize)> {
+        None
+    }
 }
 
 /// A source of bases, like an iterator.
@@ -82,6 +90,177 @@ impl<G: CurveAffine> Source<G> for (Arc<Vec<G>>, usize) {
     }
 }
 
+/// The bases of a query of the parameters from the `skip`th one on, with the
+/// fixed-base table of the whole query if one was precomputed.
+#[derive(Clone)]
+pub struct QuerySource<G: CurveAffine> {
+    pub bases: Arc<Vec<G>>,
+    pub skip: usize,
+    pub table: Option<Arc<FixedBaseTable<G>>>,
+}
+
+impl<G: CurveAffine> SourceBuilder<G> for QuerySource<G> {
+    type Source = (Arc<Vec<G>>, usize);
+
+    fn new(self) -> (Arc<Vec<G>>, usize) {
+        (self.bases, self.skip)
+    }
+
+    fn get(self) -> (Arc<Vec<G>>, usize) {
+        (self.bases, self.skip)
+    }
+
+    fn fixed_base_table(&self) -> Option<(Arc<FixedBaseTable<G>>, usize)> {
+        self.table.clone().map(|table| (table, self.skip))
+    }
+}
+
+/// The largest window of a `FixedBaseTable`. A multiexp with a table of
+/// windows of `c` bits sorts the bases into `2^c - 1` buckets.
+pub const MAX_FIXED_BASE_WINDOW: u32 = 20;
+
+/// The multiples of bases by `2^(window * j)` for each window `j` of the
+/// exponents. With them, a multiexp sorts all the windows of the exponents
+/// into a single set of buckets, which saves the bucket sums and doublings
+/// of the other windows, at the cost of storing a multiple of each base per
+/// window.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FixedBaseTable<G: CurveAffine> {
+    window: u32,
+    /// The multiples of the `i`th base, starting with the base itself, are at
+    /// `num_windows * i..num_windows * (i + 1)`.
+    points: Vec<G>,
+}
+
+impl<G: CurveAffine> FixedBaseTable<G> {
+    /// Builds the table of `bases` for windows of `window` bits, which must
+    /// be between 1 and `MAX_FIXED_BASE_WINDOW`.
+    pub fn new(bases: &[G], window: u32) -> Self {
+        assert!(
+            window > 0 && window <= MAX_FIXED_BASE_WINDOW,
+            "window must be between 1 and {} bits",
+            MAX_FIXED_BASE_WINDOW
+        );
+        let num_windows = num_windows::<G>(window);
+
+        let points = bases
+            .par_iter()
+            .flat_map(|base| {
+                let mut multiples = Vec::with_capacity(num_windows);
+                let mut multiple = base.into_projective();
+                for _ in 0..num_windows {
+                    multiples.push(multiple);
+                    for _ in 0..window {
+                        multiple.double();
+                    }
+                }
+                G::Projective::batch_normalization(&mut multiples[..]);
+
+                multiples
+                    .into_iter()
+                    .map(|multiple| multiple.into_affine())
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        FixedBaseTable { window, points }
+    }
+
+    pub fn window(&self) -> u32 {
+        self.window
+    }
+
+    /// The number of bases of the table.
+    pub fn len(&self) -> usize {
+        self.points.len() / num_windows::<G>(self.window)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// A quick check that this is the table of `bases`, which only compares
+    /// their number and the first and last bases, unlike `is_table_of`.
+    pub fn matches(&self, bases: &[G]) -> bool {
+        let num_windows = num_windows::<G>(self.window);
+        self.len() == bases.len()
+            && bases.first().map_or(true, |base| self.points[0] == *base)
+            && bases.last().map_or(true, |base| {
+                self.points[num_windows * (bases.len() - 1)] == *base
+            })
+    }
+
+    /// Whether this is the table of `bases`, which are the first multiples.
+    pub fn is_table_of(&self, bases: &[G]) -> bool {
+        let num_windows = num_windows::<G>(self.window);
+        self.len() == bases.len()
+            && bases
+                .iter()
+                .enumerate()
+                .all(|(i, base)| self.points[num_windows * i] == *base)
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let len = u32::try_from(self.len()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the table is too large to be serialized",
+            )
+        })?;
+        writer.write_u32::<BigEndian>(self.window)?;
+        writer.write_u32::<BigEndian>(len)?;
+        for point in &self.points {
+            writer.write_all(point.into_uncompressed().as_ref())?;
+        }
+
+        Ok(())
+    }
+
+    pub fn read<R: Read>(mut reader: R, checked: bool) -> io::Result<Self> {
+        let window = reader.read_u32::<BigEndian>()?;
+        if window == 0 || window > MAX_FIXED_BASE_WINDOW {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid fixed-base table window",
+            ));
+        }
+        let len = reader.read_u32::<BigEndian>()? as usize;
+        let num_points = len
+            .checked_mul(num_windows::<G>(window))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "table is too large"))?;
+
+        // The length is untrusted, so the points are only allocated as they
+        // are read, and a truncated table fails before it is exhausted.
+        let mut points = Vec::new();
+        for _ in 0..num_points {
+            let mut repr = G::Uncompressed::empty();
+            reader.read_exact(repr.as_mut())?;
+
+            let point = if checked {
+                repr.into_affine()
+            } else {
+                repr.into_affine_unchecked()
+            }
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            if point.is_zero() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "point at infinity",
+                ));
+            }
+            points.push(point);
+        }
+
+        Ok(FixedBaseTable { window, points })
+    }
+}
+
+/// The number of windows of `window` bits of the exponents of `G`.
+fn num_windows<G: CurveAffine>(window: u32) -> usize {
+    let num_bits = <G::Engine as ScalarEngine>::Fr::NUM_BITS;
+    ((num_bits + window - 1) / window) as usize
+}
+
 pub trait QueryDensity {
     /// Returns whether the base exists.
     type Iter: Iterator<Item = bool>;
@@ -111,7 +290,7 @@ impl<'a> QueryDensity for &'a FullDensity {
     }
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
 pub struct DensityTracker {
     pub bv: BitVec,
     pub total_density: usize,
@@ -192,69 +371,176 @@ impl DensityTracker {
     }
 }
 
+/// The number of exponents after which a region of the multiexp checks
+/// whether it was cancelled.
+const CANCELLATION_CHECK_INTERVAL: usize = 1 << 10;
+
+/// Computes the multiexp of the window of `c` bits of the exponents starting
+/// at bit `skip`.
+fn multiexp_region<Q, D, G, S>(
+    bases: &S,
+    density_map: &D,
+    exponents: &[<<G::Engine as ScalarEngine>::Fr as PrimeField>::Repr],
+    skip: u32,
+    c: u32,
+    handle_trivial: bool,
+    cancel: &CancellationToken,
+) -> Result<<G as CurveAffine>::Projective, SynthesisError>
+where
+    for<'a> &'a Q: QueryDensity,
+    D: Send + Sync + 'static + Clone + AsRef<Q>,
+    G: CurveAffine,
+    S: SourceBuilder<G>,
+{
+    // Accumulate the result
+    let mut acc = G::Projective::zero();
+
+    // Build a source for the bases
+    let mut bases = bases.clone().new();
+
+    // Create space for the buckets
+    let mut buckets = vec![<G as CurveAffine>::Projective::zero(); (1 << c) - 1];
+
+    let zero = <G::Engine as ScalarEngine>::Fr::zero().into_repr();
+    let one = <G::Engine as ScalarEngine>::Fr::one().into_repr();
+
+    // Sort the bases into buckets
+    for (i, (&exp, density)) in exponents
+        .iter()
+        .zip(density_map.as_ref().iter())
+        .enumerate()
+    {
+        if i % CANCELLATION_CHECK_INTERVAL == 0 {
+            cancel.check()?;
+        }
+
+        if density {
+            if exp == zero {
+                bases.skip(1)?;
+            } else if exp == one {
+                if handle_trivial {
+                    bases.add_assign_mixed(&mut acc)?;
+                } else {
+                    bases.skip(1)?;
+                }
+            } else {
+                let mut exp = exp;
+                exp.shr(skip);
+                let exp = exp.as_ref()[0] % (1 << c);
+
+                if exp != 0 {
+                    bases.add_assign_mixed(&mut buckets[(exp - 1) as usize])?;
+                } else {
+                    bases.skip(1)?;
+                }
+            }
+        }
+    }
+
+    // Summation by parts
+    // e.g. 3a + 2b + 1c = a +
+    //                    (a) + b +
+    //                    ((a) + b) + c
+    let mut running_sum = G::Projective::zero();
+    for exp in buckets.into_iter().rev() {
+        running_sum.add_assign(&exp);
+        acc.add_assign(&running_sum);
+    }
+
+    Ok(acc)
+}
+
 fn multiexp_inner<Q, D, G, S>(
-    pool: &Worker,
     bases: S,
     density_map: D,
     exponents: Arc<Vec<<<G::Engine as ScalarEngine>::Fr as PrimeField>::Repr>>,
-    mut skip: u32,
     c: u32,
-    handle_trivial: bool,
-) -> Box<dyn Future<Item = <G as CurveAffine>::Projective, Error = SynthesisError>>
+    cancel: &CancellationToken,
+) -> Result<<G as CurveAffine>::Projective, SynthesisError>
 where
     for<'a> &'a Q: QueryDensity,
     D: Send + Sync + 'static + Clone + AsRef<Q>,
     G: CurveAffine,
     S: SourceBuilder<G>,
 {
-    // Perform this region of the multiexp
-    let this = {
-        let bases = bases.clone();
-        let exponents = exponents.clone();
-        let density_map = density_map.clone();
-
-        pool.compute(move || {
-            // Accumulate the result
-            let mut acc = G::Projective::zero();
+    // Perform the regions of the multiexp in parallel. Only the least
+    // significant one handles the exponents equal to one.
+    let regions = (0..<G::Engine as ScalarEngine>::Fr::NUM_BITS)
+        .step_by(c as usize)
+        .collect::<Vec<_>>();
+    let regions = regions
+        .into_par_iter()
+        .map(|skip| multiexp_region(&bases, &density_map, &exponents, skip, c, skip == 0, cancel))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Join the regions, starting from the most significant one.
+    let mut acc = G::Projective::zero();
+    for region in regions.into_iter().rev() {
+        for _ in 0..c {
+            acc.double();
+        }
 
-            // Build a source for the bases
-            let mut bases = bases.new();
-
-            // Create space for the buckets
-            let mut buckets = vec![<G as CurveAffine>::Projective::zero(); (1 << c) - 1];
-
-            let zero = <G::Engine as ScalarEngine>::Fr::zero().into_repr();
-            let one = <G::Engine as ScalarEngine>::Fr::one().into_repr();
-
-            // Sort the bases into buckets
-            for (&exp, density) in exponents.iter().zip(density_map.as_ref().iter()) {
-                if density {
-                    if exp == zero {
-                        bases.skip(1)?;
-                    } else if exp == one {
-                        if handle_trivial {
-                            bases.add_assign_mixed(&mut acc)?;
-                        } else {
-                            bases.skip(1)?;
-                        }
-                    } else {
-                        let mut exp = exp;
-                        exp.shr(skip);
-                        let exp = exp.as_ref()[0] % (1 << c);
-
-                        if exp != 0 {
-                            bases.add_assign_mixed(&mut buckets[(exp - 1) as usize])?;
-                        } else {
-                            bases.skip(1)?;
-                        }
+        acc.add_assign(&region);
+    }
+
+    Ok(acc)
+}
+
+/// Computes the multiexp of the bases of `table` from the `skip`th one on, with
+/// all the windows of the exponents sorted into the same buckets.
+fn multiexp_fixed_base<Q, D, G>(
+    table: &FixedBaseTable<G>,
+    skip: usize,
+    density_map: &D,
+    exponents: &[<<G::Engine as ScalarEngine>::Fr as PrimeField>::Repr],
+    cancel: &CancellationToken,
+) -> Result<<G as CurveAffine>::Projective, SynthesisError>
+where
+    for<'a> &'a Q: QueryDensity,
+    D: AsRef<Q>,
+    G: CurveAffine,
+{
+    let exponents = exponents
+        .iter()
+        .zip(density_map.as_ref().iter())
+        .filter(|(_, density)| *density)
+        .map(|(exp, _)| exp)
+        .collect::<Vec<_>>();
+    if skip + exponents.len() > table.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "expected more bases from table",
+        )
+        .into());
+    }
+
+    let window = table.window;
+    let num_windows = num_windows::<G>(window);
+    let chunk_size = (exponents.len() / rayon::current_num_threads()).max(1);
+
+    let chunks = exponents
+        .par_chunks(chunk_size)
+        .enumerate()
+        .map(|(i, chunk)| -> Result<_, SynthesisError> {
+            let mut buckets = vec![<G as CurveAffine>::Projective::zero(); (1 << window) - 1];
+
+            for (j, exp) in chunk.iter().enumerate() {
+                if j % CANCELLATION_CHECK_INTERVAL == 0 {
+                    cancel.check()?;
+                }
+
+                let base = skip + i * chunk_size + j;
+                let multiples = &table.points[num_windows * base..num_windows * (base + 1)];
+                for (k, multiple) in multiples.iter().enumerate() {
+                    let digit = window_digit(exp.as_ref(), k as u32 * window, window);
+                    if digit != 0 {
+                        buckets[digit - 1].add_assign_mixed(multiple);
                     }
                 }
             }
 
-            // Summation by parts
-            // e.g. 3a + 2b + 1c = a +
-            //                    (a) + b +
-            //                    ((a) + b) + c
+            // Summation by parts, as in `multiexp_region`.
+            let mut acc = G::Projective::zero();
             let mut running_sum = G::Projective::zero();
             for exp in buckets.into_iter().rev() {
                 running_sum.add_assign(&exp);
@@ -263,57 +549,55 @@ where
 
             Ok(acc)
         })
-    };
+        .collect::<Result<Vec<_>, _>>()?;
 
-    skip += c;
+    let mut acc = G::Projective::zero();
+    for chunk in &chunks {
+        acc.add_assign(chunk);
+    }
 
-    if skip >= <G::Engine as ScalarEngine>::Fr::NUM_BITS {
-        // There isn't another region.
-        Box::new(this)
-    } else {
-        // There's another region more significant. Calculate and join it with
-        // this region recursively.
-        Box::new(
-            this.join(multiexp_inner(
-                pool,
-                bases,
-                density_map,
-                exponents,
-                skip,
-                c,
-                false,
-            ))
-            .map(move |(this, mut higher)| {
-                for _ in 0..c {
-                    higher.double();
-                }
+    Ok(acc)
+}
 
-                higher.add_assign(&this);
+/// The `c` bits of the little-endian `limbs` starting at bit `skip`.
+fn window_digit(limbs: &[u64], skip: u32, c: u32) -> usize {
+    let limb = (skip / 64) as usize;
+    let shift = skip % 64;
+    if limb >= limbs.len() {
+        return 0;
+    }
 
-                higher
-            }),
-        )
+    let mut digit = limbs[limb] >> shift;
+    if shift + c > 64 && limb + 1 < limbs.len() {
+        digit |= limbs[limb + 1] << (64 - shift);
     }
+
+    (digit & ((1 << c) - 1)) as usize
 }
 
 /// Perform multi-exponentiation. The caller is responsible for ensuring the
-/// query size is the same as the number of exponents.
+/// query size is the same as the number of exponents. Fails with
+/// `SynthesisError::Cancelled` if the worker's cancellation token is
+/// cancelled before or while it runs.
 pub fn multiexp<Q, D, G, S>(
     pool: &Worker,
     bases: S,
     density_map: D,
     exponents: Arc<Vec<<<G::Engine as ScalarEngine>::Fr as PrimeField>::Repr>>,
     kern: &mut Option<gpu::LockedMultiexpKernel<G::Engine>>,
-) -> Box<dyn Future<Item = <G as CurveAffine>::Projective, Error = SynthesisError>>
+) -> WorkerFuture<Result<<G as CurveAffine>::Projective, SynthesisError>>
 where
     for<'a> &'a Q: QueryDensity,
     D: Send + Sync + 'static + Clone + AsRef<Q>,
     G: CurveAffine,
-    G::Engine: paired::Engine,
     S: SourceBuilder<G>,
 {
+    if let Err(e) = pool.cancellation().check() {
+        return WorkerFuture::ready(Err(e));
+    }
+
     if let Some(ref mut kern) = kern {
-        if let Ok(p) = kern.with(|k: &mut gpu::MultiexpKernel<G::Engine>| {
+        if let Ok(p) = kern.with(|k: &mut dyn gpu::MultiexpKernelOps<G::Engine>| {
             let mut exps = vec![exponents[0]; exponents.len()];
             let mut n = 0;
             for (&e, d) in exponents.iter().zip(density_map.as_ref().iter()) {
@@ -324,9 +608,9 @@ where
             }
 
             let (bss, skip) = bases.clone().get();
-            k.multiexp(pool, bss, Arc::new(exps.clone()), skip, n)
+            gpu::kernel_multiexp(k, pool, bss, Arc::new(exps.clone()), skip, n)
         }) {
-            return Box::new(pool.compute(move || Ok(p)));
+            return WorkerFuture::ready(Ok(p));
         }
     }
 
@@ -342,14 +626,18 @@ where
         assert!(query_size == exponents.len());
     }
 
-    let future = multiexp_inner(pool, bases, density_map, exponents, 0, c, true);
+    let cancel = pool.cancellation().clone();
+    let future = match bases.fixed_base_table() {
+        Some((table, skip)) => pool
+            .compute(move || multiexp_fixed_base(&table, skip, &density_map, &exponents, &cancel)),
+        None => pool.compute(move || multiexp_inner(bases, density_map, exponents, c, &cancel)),
+    };
     #[cfg(feature = "gpu")]
     {
         // Do not give the control back to the caller till the
         // multiexp is done. We may want to reacquire the GPU again
         // between the multiexps.
-        let result = future.wait();
-        Box::new(pool.compute(move || result))
+        WorkerFuture::ready(future.wait())
     }
     #[cfg(not(feature = "gpu"))]
     future
@@ -399,6 +687,7 @@ fn test_with_bls12() {
     assert_eq!(naive, fast);
 }
 
+#[cfg(feature = "paired")]
 pub fn create_multiexp_kernel<E>(_log_d: usize, priority: bool) -> Option<gpu::MultiexpKernel<E>>
 where
     E: paired::Engine,
@@ -637,4 +926,82 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_window_digit() {
+        let limbs = [0xfedc_ba98_7654_3210, 0x0123_4567_89ab_cdef];
+        assert_eq!(window_digit(&limbs, 0, 8), 0x10);
+        assert_eq!(window_digit(&limbs, 4, 3), 0x1);
+        assert_eq!(window_digit(&limbs, 60, 8), 0xff);
+        assert_eq!(window_digit(&limbs, 120, 13), 0x01);
+        assert_eq!(window_digit(&limbs, 128, 8), 0);
+    }
+
+    #[cfg(feature = "groth16")]
+    #[test]
+    fn test_multiexp_fixed_base_table() {
+        use paired::bls12_381::{Fr, G1Affine, G1};
+
+        let mut rng = XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        let bases = Arc::new(
+            (0..40)
+                .map(|_| G1::random(&mut rng).into_affine())
+                .collect::<Vec<_>>(),
+        );
+        let exponents = Arc::new(
+            (0..30)
+                .map(|_| Fr::random(&mut rng).into_repr())
+                .collect::<Vec<_>>(),
+        );
+        let mut density = DensityTracker::new();
+        for i in 0..30 {
+            density.add_element();
+            if i % 3 != 0 {
+                density.inc(i);
+            }
+        }
+        let density = Arc::new(density);
+
+        let pool = Worker::new();
+        let skip = 5;
+        let expected = multiexp(
+            &pool,
+            (bases.clone(), skip),
+            density.clone(),
+            exponents.clone(),
+            &mut None,
+        )
+        .wait()
+        .unwrap();
+
+        for &window in &[1, 4, 13] {
+            let table = Arc::new(FixedBaseTable::new(&bases, window));
+            assert!(table.is_table_of(&bases));
+            assert!(!table.is_table_of(&bases[1..]));
+
+            let source = QuerySource {
+                bases: bases.clone(),
+                skip,
+                table: Some(table.clone()),
+            };
+            let actual = multiexp(&pool, source, density.clone(), exponents.clone(), &mut None)
+                .wait()
+                .unwrap();
+            assert_eq!(actual, expected);
+
+            let mut bytes = vec![];
+            table.write(&mut bytes).unwrap();
+            let read = FixedBaseTable::<G1Affine>::read(&bytes[..], true).unwrap();
+            assert_eq!(read, *table);
+            assert!(FixedBaseTable::<G1Affine>::read(&bytes[..bytes.len() - 1], true).is_err());
+
+            // A table claiming more points than it holds is rejected.
+            bytes[4..8].copy_from_slice(&u32::max_value().to_be_bytes());
+            assert!(FixedBaseTable::<G1Affine>::read(&bytes[..], true).is_err());
+        }
+    }
 }
@@ -0,0 +1,167 @@
+//! Parallel synthesis of circuits which decompose into independent
+//! sub-circuits.
+//!
+//! When the root constraint system is extensible, every sub-circuit is
+//! synthesized into its own constraint system on the global thread pool, and
+//! the results are merged into the root with `ConstraintSystem::extend`, in
+//! order. Otherwise the sub-circuits are simply synthesized one after the
+//! other, so the resulting constraint system is the same either way. This is
+//! the case when generating Groth parameters, whose constraint system is
+//! deliberately not extensible.
+
+use ff::{Field, ScalarEngine};
+
+use crate::{Circuit, ConstraintSystem, SynthesisError};
+
+/// A circuit made of independent sub-circuits, which are synthesized in
+/// parallel whenever the constraint system allows it.
+///
+/// The sub-circuits can not share variables, besides the "one" input.
+#[derive(Clone)]
+pub struct ParallelCircuit<C> {
+    circuits: Vec<C>,
+}
+
+impl<C> ParallelCircuit<C> {
+    pub fn new(circuits: Vec<C>) -> Self {
+        ParallelCircuit { circuits }
+    }
+}
+
+impl<E: ScalarEngine, C: Circuit<E> + Send> Circuit<E> for ParallelCircuit<C> {
+    fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        synthesize_parallel(cs, self.circuits)
+    }
+}
+
+/// Synthesizes `circuits` into `cs`, as if they had been synthesized one
+/// after the other. This can be called from within `Circuit::synthesize` for
+/// the independent parts of a larger circuit.
+pub fn synthesize_parallel<E, CS, C>(cs: &mut CS, circuits: Vec<C>) -> Result<(), SynthesisError>
+where
+    E: ScalarEngine,
+    CS: ConstraintSystem<E>,
+    C: Circuit<E> + Send,
+{
+    if !CS::Root::is_extensible() {
+        for circuit in circuits {
+            circuit.synthesize(cs)?;
+        }
+        return Ok(());
+    }
+
+    let children = synthesize_children::<E, CS::Root, C>(circuits)?;
+
+    let root = cs.get_root();
+    for child in children {
+        root.extend(child);
+    }
+
+    Ok(())
+}
+
+fn synthesize_child<E, CS, C>(circuit: C) -> Result<CS, SynthesisError>
+where
+    E: ScalarEngine,
+    CS: ConstraintSystem<E>,
+    C: Circuit<E>,
+{
+    let mut child = CS::new();
+
+    // Temporarily allocate the "one" input variable, which `extend` skips.
+    child.alloc_input(|| "one", || Ok(E::Fr::one()))?;
+
+    circuit.synthesize(&mut child)?;
+
+    Ok(child)
+}
+
+#[cfg(feature = "multicore")]
+fn synthesize_children<E, CS, C>(circuits: Vec<C>) -> Result<Vec<CS>, SynthesisError>
+where
+    E: ScalarEngine,
+    CS: ConstraintSystem<E>,
+    C: Circuit<E> + Send,
+{
+    use crate::multicore::THREAD_POOL;
+    use rayon::prelude::*;
+
+    THREAD_POOL.install(|| {
+        circuits
+            .into_par_iter()
+            .map(synthesize_child::<E, CS, C>)
+            .collect()
+    })
+}
+
+#[cfg(not(feature = "multicore"))]
+fn synthesize_children<E, CS, C>(circuits: Vec<C>) -> Result<Vec<CS>, SynthesisError>
+where
+    E: ScalarEngine,
+    CS: ConstraintSystem<E>,
+    C: Circuit<E> + Send,
+{
+    circuits
+        .into_iter()
+        .map(synthesize_child::<E, CS, C>)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::util_cs::metric_cs::MetricCS;
+    use ff::PrimeField;
+    use paired::bls12_381::{Bls12, Fr};
+
+    /// Proves knowledge of `x` with `x^2 = y`, where `y` is public.
+    #[derive(Clone)]
+    struct Square {
+        x: u64,
+    }
+
+    impl Circuit<Bls12> for Square {
+        fn synthesize<CS: ConstraintSystem<Bls12>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let x_val = Fr::from_str(&self.x.to_string()).unwrap();
+            let mut y_val = x_val;
+            y_val.square();
+
+            let mut cs = cs.namespace(|| format!("square {}", self.x));
+            let x = cs.alloc(|| "x", || Ok(x_val))?;
+            let y = cs.alloc_input(|| "y", || Ok(y_val))?;
+            cs.enforce(|| "x * x = y", |lc| lc + x, |lc| lc + x, |lc| lc + y);
+            cs.enforce(
+                || "x * 1 = x",
+                |lc| lc + x,
+                |lc| lc + CS::one(),
+                |lc| lc + x,
+            );
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_parallel_metric_cs() {
+        let circuits = (1..10).map(|x| Square { x }).collect::<Vec<_>>();
+
+        let mut sequential = MetricCS::<Bls12>::new();
+        {
+            let mut cs = sequential.namespace(|| "squares");
+            for circuit in circuits.clone() {
+                circuit.synthesize(&mut cs).unwrap();
+            }
+        }
+
+        let mut parallel = MetricCS::<Bls12>::new();
+        synthesize_parallel(&mut parallel.namespace(|| "squares"), circuits).unwrap();
+
+        assert_eq!(parallel.num_constraints(), 18);
+        assert_eq!(parallel.num_inputs(), 10);
+        assert_eq!(parallel.pretty_print(), sequential.pretty_print());
+    }
+}
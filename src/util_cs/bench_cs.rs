@@ -1,10 +1,10 @@
 use std::marker::PhantomData;
 
 use crate::{ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
-use paired::Engine;
+use ff::ScalarEngine;
 
 #[derive(Debug)]
-pub struct BenchCS<E: Engine> {
+pub struct BenchCS<E: ScalarEngine> {
     inputs: usize,
     aux: usize,
     a: usize,
@@ -13,7 +13,7 @@ pub struct BenchCS<E: Engine> {
     _e: PhantomData<E>,
 }
 
-impl<E: Engine> BenchCS<E> {
+impl<E: ScalarEngine> BenchCS<E> {
     pub fn new() -> Self {
         BenchCS::default()
     }
@@ -27,7 +27,7 @@ impl<E: Engine> BenchCS<E> {
     }
 }
 
-impl<E: Engine> Default for BenchCS<E> {
+impl<E: ScalarEngine> Default for BenchCS<E> {
     fn default() -> Self {
         BenchCS {
             inputs: 1,
@@ -40,10 +40,10 @@ impl<E: Engine> Default for BenchCS<E> {
     }
 }
 
-// Safety: Engine is static and this is only a marker
-unsafe impl<E: Engine> Send for BenchCS<E> {}
+// Safety: ScalarEngine is static and this is only a marker
+unsafe impl<E: ScalarEngine> Send for BenchCS<E> {}
 
-impl<E: Engine> ConstraintSystem<E> for BenchCS<E> {
+impl<E: ScalarEngine> ConstraintSystem<E> for BenchCS<E> {
     type Root = Self;
 
     fn new() -> Self {
@@ -0,0 +1,246 @@
+//! Proving circuits compiled with circom.
+//!
+//! A `CircomCircuit` replays the constraints of a `.r1cs` file, and
+//! optionally the assignment of a `.wtns` file produced by circom's witness
+//! calculator, through a `ConstraintSystem`. It can therefore be used with
+//! `generate_random_parameters` and `create_random_proof` like any other
+//! circuit.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use ff::{Field, PrimeField, PrimeFieldRepr, ScalarEngine};
+
+use super::r1cs::{
+    field_size, invalid_data, read_field, read_fr, read_sections, write_field, write_u32, R1CS,
+};
+use crate::{Circuit, ConstraintSystem, SynthesisError};
+
+const WTNS_MAGIC: &[u8; 4] = b"wtns";
+const WTNS_VERSION: u32 = 2;
+
+const WTNS_SECTION_HEADER: u32 = 1;
+const WTNS_SECTION_VALUES: u32 = 2;
+
+/// Reads a witness in the `.wtns` format. The witness holds one value per
+/// wire, starting with the constant one.
+pub fn read_witness<E: ScalarEngine, R: Read>(reader: R) -> io::Result<Vec<E::Fr>> {
+    let sections = read_sections(reader, WTNS_MAGIC, WTNS_VERSION)?;
+
+    let mut header = &sections
+        .get(&WTNS_SECTION_HEADER)
+        .ok_or_else(|| invalid_data("missing wtns header"))?[..];
+    read_field::<E, _>(&mut header)?;
+    let num_values = header.read_u32::<LittleEndian>()? as usize;
+
+    let mut data = &sections
+        .get(&WTNS_SECTION_VALUES)
+        .ok_or_else(|| invalid_data("missing wtns values"))?[..];
+    (0..num_values)
+        .map(|_| read_fr::<E, _>(&mut data))
+        .collect()
+}
+
+/// Writes a witness in the `.wtns` format.
+pub fn write_witness<E: ScalarEngine, W: Write>(
+    witness: &[E::Fr],
+    mut writer: W,
+) -> io::Result<()> {
+    let n8 = field_size::<E>();
+
+    writer.write_all(WTNS_MAGIC)?;
+    writer.write_u32::<LittleEndian>(WTNS_VERSION)?;
+    writer.write_u32::<LittleEndian>(2)?;
+
+    writer.write_u32::<LittleEndian>(WTNS_SECTION_HEADER)?;
+    writer.write_u64::<LittleEndian>(n8 as u64 + 8)?;
+    write_field::<E, _>(&mut writer)?;
+    write_u32(&mut writer, witness.len())?;
+
+    writer.write_u32::<LittleEndian>(WTNS_SECTION_VALUES)?;
+    writer.write_u64::<LittleEndian>((witness.len() * n8) as u64)?;
+    for value in witness {
+        value.into_repr().write_le(&mut writer)?;
+    }
+
+    Ok(())
+}
+
+/// A circuit defined by circom artifacts.
+#[derive(Clone)]
+pub struct CircomCircuit<E: ScalarEngine> {
+    r1cs: R1CS<E>,
+    witness: Option<Vec<E::Fr>>,
+}
+
+impl<E: ScalarEngine> CircomCircuit<E> {
+    /// A circuit without an assignment, suitable for parameter generation.
+    pub fn new(r1cs: R1CS<E>) -> Self {
+        CircomCircuit {
+            r1cs,
+            witness: None,
+        }
+    }
+
+    /// A circuit with an assignment, suitable for proving. The witness must
+    /// hold one value per wire of `r1cs`, starting with the constant one.
+    pub fn with_witness(r1cs: R1CS<E>, witness: Vec<E::Fr>) -> io::Result<Self> {
+        if witness.len() != r1cs.num_inputs() + r1cs.num_aux() {
+            return Err(invalid_data(
+                "witness length does not match the number of wires",
+            ));
+        }
+        if witness[0] != E::Fr::one() {
+            return Err(invalid_data("witness does not start with one"));
+        }
+
+        Ok(CircomCircuit {
+            r1cs,
+            witness: Some(witness),
+        })
+    }
+
+    /// Loads a `.r1cs` file and, if given, a `.wtns` file.
+    pub fn from_files<P: AsRef<Path>>(r1cs: P, witness: Option<P>) -> io::Result<Self> {
+        let r1cs = R1CS::read(BufReader::new(File::open(r1cs)?))?;
+
+        match witness {
+            Some(witness) => {
+                let witness = read_witness::<E, _>(BufReader::new(File::open(witness)?))?;
+                Self::with_witness(r1cs, witness)
+            }
+            None => Ok(Self::new(r1cs)),
+        }
+    }
+
+    pub fn r1cs(&self) -> &R1CS<E> {
+        &self.r1cs
+    }
+
+    /// The public inputs to verify a proof of this circuit with, excluding
+    /// the "one" input.
+    pub fn public_inputs(&self) -> Option<&[E::Fr]> {
+        self.witness.as_ref().map(|w| &w[1..self.r1cs.num_inputs()])
+    }
+
+    /// Writes the witness, if any, in the `.wtns` format.
+    pub fn write_witness<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let witness = self
+            .witness
+            .as_ref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "no witness"))?;
+        write_witness::<E, _>(witness, BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<E: ScalarEngine> Circuit<E> for CircomCircuit<E> {
+    fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        self.r1cs.replay(cs, self.witness.as_ref().map(|w| &w[..]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::groth16::{
+        create_random_proof, generate_random_parameters, prepare_verifying_key, verify_proof,
+    };
+    use crate::util_cs::test_cs::TestConstraintSystem;
+    use paired::bls12_381::{Bls12, Fr};
+    use rand_core::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    // out = x^3 + x + 5, with out public; the witness is ordered
+    // [one, out, x, x^2, x^3] as circom would.
+    fn cube_r1cs() -> R1CS<Bls12> {
+        let mut cs = R1CS::<Bls12>::new();
+        let out = cs.alloc_input(|| "out", || unreachable!()).unwrap();
+        let x = cs.alloc(|| "x", || unreachable!()).unwrap();
+        let x2 = cs.alloc(|| "x2", || unreachable!()).unwrap();
+        let x3 = cs.alloc(|| "x3", || unreachable!()).unwrap();
+        cs.enforce(|| "x2", |lc| lc + x, |lc| lc + x, |lc| lc + x2);
+        cs.enforce(|| "x3", |lc| lc + x2, |lc| lc + x, |lc| lc + x3);
+        cs.enforce(
+            || "out",
+            |lc| lc + x3 + x + (Fr::from_str("5").unwrap(), R1CS::<Bls12>::one()),
+            |lc| lc + R1CS::<Bls12>::one(),
+            |lc| lc + out,
+        );
+        cs
+    }
+
+    fn cube_witness(x: u64) -> Vec<Fr> {
+        let x = Fr::from_str(&x.to_string()).unwrap();
+        let mut x2 = x;
+        x2.square();
+        let mut x3 = x2;
+        x3.mul_assign(&x);
+        let mut out = x3;
+        out.add_assign(&x);
+        out.add_assign(&Fr::from_str("5").unwrap());
+
+        vec![Fr::one(), out, x, x2, x3]
+    }
+
+    #[test]
+    fn test_wtns_roundtrip() {
+        let witness = cube_witness(3);
+
+        let mut buf = vec![];
+        write_witness::<Bls12, _>(&witness, &mut buf).unwrap();
+        assert_eq!(&buf[..4], b"wtns");
+
+        assert_eq!(read_witness::<Bls12, _>(&buf[..]).unwrap(), witness);
+        assert!(read_witness::<Bls12, _>(&buf[..buf.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_circom_circuit() {
+        let mut r1cs_buf = vec![];
+        cube_r1cs().write(&mut r1cs_buf).unwrap();
+        let r1cs = R1CS::<Bls12>::read(&r1cs_buf[..]).unwrap();
+
+        let mut wtns_buf = vec![];
+        write_witness::<Bls12, _>(&cube_witness(3), &mut wtns_buf).unwrap();
+        let witness = read_witness::<Bls12, _>(&wtns_buf[..]).unwrap();
+
+        assert!(CircomCircuit::with_witness(r1cs.clone(), witness[1..].to_vec()).is_err());
+
+        let circuit = CircomCircuit::with_witness(r1cs.clone(), witness).unwrap();
+        assert_eq!(
+            circuit.public_inputs().unwrap(),
+            &[Fr::from_str("35").unwrap()][..]
+        );
+
+        let mut cs = TestConstraintSystem::<Bls12>::new();
+        circuit.clone().synthesize(&mut cs).unwrap();
+        assert!(cs.is_satisfied());
+        assert_eq!(cs.num_constraints(), 3);
+        assert_eq!(cs.num_inputs(), 2);
+
+        let mut cs = TestConstraintSystem::<Bls12>::new();
+        CircomCircuit::with_witness(r1cs.clone(), {
+            let mut witness = cube_witness(3);
+            witness[1] = Fr::from_str("36").unwrap();
+            witness
+        })
+        .unwrap()
+        .synthesize(&mut cs)
+        .unwrap();
+        assert!(!cs.is_satisfied());
+
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+        let params = generate_random_parameters(CircomCircuit::new(r1cs), rng).unwrap();
+        let pvk = prepare_verifying_key(&params.vk);
+
+        let proof = create_random_proof(circuit.clone(), &params, rng).unwrap();
+        assert!(verify_proof(&pvk, &proof, circuit.public_inputs().unwrap()).unwrap());
+        assert!(!verify_proof(&pvk, &proof, &[Fr::from_str("36").unwrap()]).unwrap());
+    }
+}
@@ -0,0 +1,247 @@
+use ff::ScalarEngine;
+
+use crate::{Circuit, ConstraintSystem, LinearCombination, SendMarker, SynthesisError, Variable};
+
+/// Wraps a constraint system and records the namespace path of every
+/// allocation and constraint, so that errors carry the place at which
+/// synthesis failed.
+///
+/// Errors of failed allocations are returned as `SynthesisError::WithContext`
+/// with the path of the allocation. Errors of the circuit itself are attached
+/// the path of the last allocation or constraint before them by
+/// `with_context`, which `WithContext` does for whole circuits. Matching on
+/// the variant of such errors goes through `SynthesisError::root_cause`.
+///
+/// Keeping track of the path requires evaluating every namespace name and
+/// annotation, even when the wrapped constraint system ignores them, so this
+/// is meant for debugging failing circuits.
+pub struct ContextCS<'a, E: ScalarEngine, CS: ConstraintSystem<E>> {
+    inner: &'a mut CS,
+    current_namespace: Vec<String>,
+    last_path: Option<String>,
+    _e: SendMarker<E>,
+}
+
+impl<'a, E: ScalarEngine, CS: ConstraintSystem<E>> ContextCS<'a, E, CS> {
+    pub fn new(inner: &'a mut CS) -> Self {
+        ContextCS {
+            inner,
+            current_namespace: vec![],
+            last_path: None,
+            _e: SendMarker::default(),
+        }
+    }
+
+    /// The path of the last allocation or constraint, relative to the
+    /// namespace of the wrapped constraint system.
+    pub fn last_path(&self) -> Option<&str> {
+        self.last_path.as_deref()
+    }
+
+    /// Attaches the path of the last allocation or constraint to `error`,
+    /// unless it already carries a path or nothing was synthesized yet.
+    pub fn with_context(&self, error: SynthesisError) -> SynthesisError {
+        match (&error, &self.last_path) {
+            (SynthesisError::WithContext { .. }, _) | (_, None) => error,
+            (_, Some(path)) => SynthesisError::WithContext {
+                path: path.clone(),
+                source: Box::new(error),
+            },
+        }
+    }
+
+    fn record(&mut self, annotation: &str) {
+        let mut path = self.current_namespace.join("/");
+        if !path.is_empty() {
+            path.push('/');
+        }
+        path.push_str(annotation);
+        self.last_path = Some(path);
+    }
+}
+
+impl<'a, E: ScalarEngine, CS: ConstraintSystem<E>> ConstraintSystem<E> for ContextCS<'a, E, CS> {
+    type Root = Self;
+
+    fn one() -> Variable {
+        CS::one()
+    }
+
+    fn alloc<F, A, AR>(&mut self, annotation: A, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<E::Fr, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        let annotation = annotation().into();
+        self.record(&annotation);
+        self.inner
+            .alloc(|| annotation, f)
+            .map_err(|e| self.with_context(e))
+    }
+
+    fn alloc_input<F, A, AR>(&mut self, annotation: A, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<E::Fr, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        let annotation = annotation().into();
+        self.record(&annotation);
+        self.inner
+            .alloc_input(|| annotation, f)
+            .map_err(|e| self.with_context(e))
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(&mut self, annotation: A, a: LA, b: LB, c: LC)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+        LA: FnOnce(LinearCombination<E>) -> LinearCombination<E>,
+        LB: FnOnce(LinearCombination<E>) -> LinearCombination<E>,
+        LC: FnOnce(LinearCombination<E>) -> LinearCombination<E>,
+    {
+        let annotation = annotation().into();
+        self.record(&annotation);
+        self.inner.enforce(|| annotation, a, b, c)
+    }
+
+    fn push_namespace<NR, N>(&mut self, name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        let name = name_fn().into();
+        self.inner.get_root().push_namespace(|| name.clone());
+        self.current_namespace.push(name);
+    }
+
+    fn pop_namespace(&mut self) {
+        self.inner.get_root().pop_namespace();
+        assert!(self.current_namespace.pop().is_some());
+    }
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+}
+
+/// Synthesizes the wrapped circuit through a `ContextCS`, so that its errors
+/// carry the path at which synthesis failed.
+#[derive(Clone)]
+pub struct WithContext<C>(pub C);
+
+impl<E: ScalarEngine, C: Circuit<E>> Circuit<E> for WithContext<C> {
+    fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let mut cs = ContextCS::new(cs);
+        self.0.synthesize(&mut cs).map_err(|e| cs.with_context(e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::util_cs::test_cs::TestConstraintSystem;
+    use ff::Field;
+    use paired::bls12_381::{Bls12, Fr};
+
+    struct MissingValue;
+
+    impl Circuit<Bls12> for MissingValue {
+        fn synthesize<CS: ConstraintSystem<Bls12>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let mut cs = cs.namespace(|| "outer");
+            cs.alloc(|| "present", || Ok(Fr::one()))?;
+
+            let mut cs = cs.namespace(|| "inner");
+            cs.alloc(|| "missing", || Err(SynthesisError::AssignmentMissing))?;
+
+            Ok(())
+        }
+    }
+
+    struct Unsatisfiable;
+
+    impl Circuit<Bls12> for Unsatisfiable {
+        fn synthesize<CS: ConstraintSystem<Bls12>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let mut cs = cs.namespace(|| "gadget");
+            let x = cs.alloc(|| "x", || Ok(Fr::one()))?;
+            cs.enforce(|| "x = x", |lc| lc + x, |lc| lc + CS::one(), |lc| lc + x);
+
+            Err(SynthesisError::Unsatisfiable)
+        }
+    }
+
+    #[test]
+    fn test_error_context() {
+        let mut cs = TestConstraintSystem::<Bls12>::new();
+        let mut context = ContextCS::new(&mut cs);
+        assert_eq!(context.last_path(), None);
+        let err = MissingValue.synthesize(&mut context).unwrap_err();
+        match err.root_cause() {
+            SynthesisError::AssignmentMissing => {}
+            _ => panic!("expected AssignmentMissing"),
+        }
+        assert_eq!(err.path(), Some("outer/inner/missing"));
+        assert_eq!(
+            err.to_string(),
+            "an assignment for a variable could not be computed at outer/inner/missing"
+        );
+        assert_eq!(context.last_path(), Some("outer/inner/missing"));
+
+        // Errors which already carry a path keep it.
+        let err = context.with_context(err);
+        assert_eq!(err.path(), Some("outer/inner/missing"));
+        match err {
+            SynthesisError::WithContext { source, .. } => match *source {
+                SynthesisError::AssignmentMissing => {}
+                _ => panic!("expected a single context"),
+            },
+            _ => panic!("expected a context"),
+        }
+
+        // Errors of the circuit itself are attributed to its last operation.
+        let mut cs = TestConstraintSystem::<Bls12>::new();
+        let mut namespace = cs.namespace(|| "circuit");
+        let mut context = ContextCS::new(&mut namespace);
+        let err = Unsatisfiable.synthesize(&mut context).unwrap_err();
+        match err {
+            SynthesisError::Unsatisfiable => {}
+            _ => panic!("the circuit's own errors are returned unchanged"),
+        }
+        let err = context.with_context(err);
+        match err.root_cause() {
+            SynthesisError::Unsatisfiable => {}
+            _ => panic!("expected Unsatisfiable"),
+        }
+        assert_eq!(err.path(), Some("gadget/x = x"));
+        drop(namespace);
+
+        // The namespaces and annotations were forwarded to the wrapped constraint system.
+        assert_eq!(cs.get("circuit/gadget/x"), Fr::one());
+        assert!(cs.is_satisfied());
+
+        let mut cs = TestConstraintSystem::<Bls12>::new();
+        let err = WithContext(Unsatisfiable).synthesize(&mut cs).unwrap_err();
+        match err.root_cause() {
+            SynthesisError::Unsatisfiable => {}
+            _ => panic!("expected Unsatisfiable"),
+        }
+        assert_eq!(err.path(), Some("gadget/x = x"));
+
+        // Errors are only wrapped through a `ContextCS`.
+        let mut cs = TestConstraintSystem::<Bls12>::new();
+        let err = MissingValue.synthesize(&mut cs).unwrap_err();
+        assert_eq!(err.path(), None);
+        match err.root_cause() {
+            SynthesisError::AssignmentMissing => {}
+            _ => panic!("expected AssignmentMissing"),
+        }
+    }
+}
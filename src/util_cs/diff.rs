@@ -0,0 +1,300 @@
+//! Structural diffs between two constraint systems.
+//!
+//! `TestConstraintSystem::hash` tells whether a circuit changed shape; a
+//! `CsDiff` tells how. Constraints are matched by their namespace path, and
+//! compared with every variable replaced by its path, so allocating an extra
+//! variable somewhere does not make all later constraints look modified.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use ff::{Field, ScalarEngine};
+
+use super::test_cs::TestConstraintSystem;
+use crate::{Circuit, Index, LinearCombination, SynthesisError};
+
+/// The first constraint at which two constraint systems differ, either in
+/// path or in content.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// The index of the constraint in both constraint systems.
+    pub index: usize,
+    /// The path of the constraint on the left, if it has that many.
+    pub left: Option<String>,
+    /// The path of the constraint on the right, if it has that many.
+    pub right: Option<String>,
+}
+
+/// The structural differences between a left and a right constraint system.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CsDiff {
+    /// The number of inputs on the left and on the right, including "one".
+    pub num_inputs: (usize, usize),
+    pub num_aux: (usize, usize),
+    pub num_constraints: (usize, usize),
+    /// Paths of constraints which only exist on the right, in order.
+    pub added: Vec<String>,
+    /// Paths of constraints which only exist on the left, in order.
+    pub removed: Vec<String>,
+    /// Paths of constraints which exist on both sides with different linear
+    /// combinations, in the order of the left.
+    pub modified: Vec<String>,
+    pub first_divergence: Option<Divergence>,
+}
+
+impl CsDiff {
+    /// Whether both constraint systems have the same structure.
+    pub fn is_empty(&self) -> bool {
+        self.num_inputs.0 == self.num_inputs.1
+            && self.num_aux.0 == self.num_aux.1
+            && self.first_divergence.is_none()
+    }
+}
+
+impl fmt::Display for CsDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "inputs: {} -> {}", self.num_inputs.0, self.num_inputs.1)?;
+        writeln!(f, "aux: {} -> {}", self.num_aux.0, self.num_aux.1)?;
+        writeln!(
+            f,
+            "constraints: {} -> {}",
+            self.num_constraints.0, self.num_constraints.1
+        )?;
+
+        if let Some(divergence) = &self.first_divergence {
+            let path = |p: &Option<String>| p.as_ref().map_or("<none>", String::as_str).to_string();
+            writeln!(
+                f,
+                "first divergence at constraint {}: {} -> {}",
+                divergence.index,
+                path(&divergence.left),
+                path(&divergence.right)
+            )?;
+        }
+
+        for path in &self.removed {
+            writeln!(f, "- {}", path)?;
+        }
+        for path in &self.added {
+            writeln!(f, "+ {}", path)?;
+        }
+        for path in &self.modified {
+            writeln!(f, "~ {}", path)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A linear combination with its variables replaced by their paths, and
+/// without zero terms.
+type NamedLc<E> = BTreeMap<String, <E as ScalarEngine>::Fr>;
+
+fn named_lc<E: ScalarEngine>(
+    lc: &LinearCombination<E>,
+    cs: &TestConstraintSystem<E>,
+) -> NamedLc<E> {
+    let mut named = BTreeMap::new();
+    for (var, coeff) in lc.iter() {
+        if coeff.is_zero() {
+            continue;
+        }
+        let path = match var.get_unchecked() {
+            Index::Input(i) => &cs.get_inputs()[i].1,
+            Index::Aux(i) => &cs.aux()[i].1,
+        };
+        named.insert(path.clone(), *coeff);
+    }
+    named
+}
+
+type NamedConstraint<E> = (NamedLc<E>, NamedLc<E>, NamedLc<E>);
+
+fn named_constraints<E: ScalarEngine>(
+    cs: &TestConstraintSystem<E>,
+) -> Vec<(&str, NamedConstraint<E>)> {
+    cs.constraints()
+        .iter()
+        .map(|(a, b, c, path)| {
+            (
+                path.as_str(),
+                (named_lc(a, cs), named_lc(b, cs), named_lc(c, cs)),
+            )
+        })
+        .collect()
+}
+
+/// Compares two constraint systems.
+pub fn diff<E: ScalarEngine>(
+    left: &TestConstraintSystem<E>,
+    right: &TestConstraintSystem<E>,
+) -> CsDiff {
+    let left_constraints = named_constraints(left);
+    let right_constraints = named_constraints(right);
+
+    let len = left_constraints.len().max(right_constraints.len());
+    let first_divergence = (0..len)
+        .find(|&i| left_constraints.get(i) != right_constraints.get(i))
+        .map(|index| Divergence {
+            index,
+            left: left_constraints.get(index).map(|(p, _)| p.to_string()),
+            right: right_constraints.get(index).map(|(p, _)| p.to_string()),
+        });
+
+    let left_by_path = left_constraints
+        .iter()
+        .map(|(path, constraint)| (*path, constraint))
+        .collect::<HashMap<_, _>>();
+    let right_by_path = right_constraints
+        .iter()
+        .map(|(path, constraint)| (*path, constraint))
+        .collect::<HashMap<_, _>>();
+
+    let mut removed = vec![];
+    let mut modified = vec![];
+    for (path, constraint) in &left_constraints {
+        match right_by_path.get(path) {
+            None => removed.push(path.to_string()),
+            Some(other) if *other != constraint => modified.push(path.to_string()),
+            Some(_) => {}
+        }
+    }
+    let added = right_constraints
+        .iter()
+        .filter(|(path, _)| !left_by_path.contains_key(path))
+        .map(|(path, _)| path.to_string())
+        .collect();
+
+    CsDiff {
+        num_inputs: (left.num_inputs(), right.num_inputs()),
+        num_aux: (left.aux().len(), right.aux().len()),
+        num_constraints: (left.num_constraints(), right.num_constraints()),
+        added,
+        removed,
+        modified,
+        first_divergence,
+    }
+}
+
+/// Synthesizes both circuits into `TestConstraintSystem`s and compares them.
+/// Since `TestConstraintSystem` computes every assignment, both circuits
+/// need a witness.
+pub fn diff_circuits<E, L, R>(left: L, right: R) -> Result<CsDiff, SynthesisError>
+where
+    E: ScalarEngine,
+    L: Circuit<E>,
+    R: Circuit<E>,
+{
+    let mut left_cs = TestConstraintSystem::new();
+    left.synthesize(&mut left_cs)?;
+    let mut right_cs = TestConstraintSystem::new();
+    right.synthesize(&mut right_cs)?;
+
+    Ok(diff(&left_cs, &right_cs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::ConstraintSystem;
+    use ff::PrimeField;
+    use paired::bls12_381::{Bls12, Fr};
+
+    /// Computes `x^3` in version 1, and `x^3 + x` with an extra variable in
+    /// version 2.
+    struct Cube {
+        version: u8,
+    }
+
+    impl Circuit<Bls12> for Cube {
+        fn synthesize<CS: ConstraintSystem<Bls12>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let x_val = Fr::from_str("3").unwrap();
+            let mut x2_val = x_val;
+            x2_val.square();
+            let mut x3_val = x2_val;
+            x3_val.mul_assign(&x_val);
+
+            let x = cs.alloc(|| "x", || Ok(x_val))?;
+            if self.version > 1 {
+                cs.alloc(|| "unused", || Ok(Fr::zero()))?;
+            }
+            let x2 = cs.alloc(|| "x2", || Ok(x2_val))?;
+            let x3 = cs.alloc(|| "x3", || Ok(x3_val))?;
+            let out = cs.alloc_input(|| "out", || Ok(x3_val))?;
+
+            let mut cs = cs.namespace(|| "cube");
+            cs.enforce(|| "x2", |lc| lc + x, |lc| lc + x, |lc| lc + x2);
+            cs.enforce(|| "x3", |lc| lc + x2, |lc| lc + x, |lc| lc + x3);
+            if self.version > 1 {
+                cs.enforce(|| "check", |lc| lc + x, |lc| lc + x, |lc| lc + x2);
+                cs.enforce(
+                    || "out",
+                    |lc| lc + x3 + x - x,
+                    |lc| lc + CS::one(),
+                    |lc| lc + out,
+                );
+            } else {
+                cs.enforce(|| "out", |lc| lc + x3, |lc| lc + CS::one(), |lc| lc + out);
+                cs.enforce(|| "old", |lc| lc + x, |lc| lc, |lc| lc);
+            }
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let same = diff_circuits(Cube { version: 1 }, Cube { version: 1 }).unwrap();
+        assert!(same.is_empty());
+        assert_eq!(same.first_divergence, None);
+
+        // Zero terms and shifted variable indices don't count as changes.
+        let diff = diff_circuits(Cube { version: 1 }, Cube { version: 2 }).unwrap();
+        assert!(!diff.is_empty());
+        assert_eq!(diff.num_inputs, (2, 2));
+        assert_eq!(diff.num_aux, (3, 4));
+        assert_eq!(diff.num_constraints, (4, 4));
+        assert_eq!(diff.added, vec!["cube/check".to_string()]);
+        assert_eq!(diff.removed, vec!["cube/old".to_string()]);
+        assert!(diff.modified.is_empty());
+        assert_eq!(
+            diff.first_divergence,
+            Some(Divergence {
+                index: 2,
+                left: Some("cube/out".to_string()),
+                right: Some("cube/check".to_string()),
+            })
+        );
+        assert_eq!(
+            diff.to_string(),
+            "inputs: 2 -> 2\n\
+             aux: 3 -> 4\n\
+             constraints: 4 -> 4\n\
+             first divergence at constraint 2: cube/out -> cube/check\n\
+             - cube/old\n\
+             + cube/check\n"
+        );
+
+        let mut left = TestConstraintSystem::<Bls12>::new();
+        Cube { version: 1 }.synthesize(&mut left).unwrap();
+        let mut right = TestConstraintSystem::<Bls12>::new();
+        Cube { version: 1 }.synthesize(&mut right).unwrap();
+        let one = TestConstraintSystem::<Bls12>::one();
+        right.enforce(|| "extra", |lc| lc + one, |lc| lc + one, |lc| lc + one);
+
+        let diff = super::diff(&left, &right);
+        assert_eq!(diff.added, vec!["extra".to_string()]);
+        assert_eq!(
+            diff.first_divergence,
+            Some(Divergence {
+                index: 4,
+                left: None,
+                right: Some("extra".to_string()),
+            })
+        );
+    }
+}
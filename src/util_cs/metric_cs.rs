@@ -1,6 +1,5 @@
 use crate::{ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
 use ff::{Field, PrimeField, ScalarEngine};
-use paired::Engine;
 use std::cmp::Ordering;
 use std::collections::{BTreeMap, HashMap};
 
@@ -40,7 +39,7 @@ impl Ord for OrderedVariable {
     }
 }
 
-pub struct MetricCS<E: Engine> {
+pub struct MetricCS<E: ScalarEngine> {
     named_objects: HashMap<String, NamedObject>,
     current_namespace: Vec<String>,
     #[allow(clippy::type_complexity)]
@@ -77,7 +76,9 @@ fn proc_lc<E: ScalarEngine>(terms: &LinearCombination<E>) -> BTreeMap<OrderedVar
     map
 }
 
-impl<E: Engine> MetricCS<E> {
+impl<E: ScalarEngine> MetricCS<E> {
+    /// Creates a constraint system with the "one" input allocated, which is
+    /// the same as `ConstraintSystem::new` followed by allocating it.
     pub fn new() -> Self {
         MetricCS::default()
     }
@@ -178,6 +179,28 @@ impl<E: Engine> MetricCS<E> {
         s
     }
 
+    /// Aggregates the cost of the circuit per namespace.
+    pub fn cost_report(&self) -> CostReport {
+        let mut root = CostNode::default();
+
+        for path in &self.inputs {
+            root.get(path).own.inputs += 1;
+        }
+        for path in &self.aux {
+            root.get(path).own.aux += 1;
+        }
+        for (a, b, c, path) in &self.constraints {
+            let cost = &mut root.get(path).own;
+            cost.constraints += 1;
+            cost.terms += [a, b, c]
+                .iter()
+                .map(|lc| lc.iter().filter(|(_, coeff)| !coeff.is_zero()).count())
+                .sum::<usize>();
+        }
+
+        root.into_report(ROOT_NAME.to_string())
+    }
+
     fn set_named_obj(&mut self, path: String, to: NamedObject) {
         if self.named_objects.contains_key(&path) {
             panic!("tried to create object at existing path: {}", path);
@@ -187,22 +210,30 @@ impl<E: Engine> MetricCS<E> {
     }
 }
 
-impl<E: Engine> Default for MetricCS<E> {
+impl<E: ScalarEngine> Default for MetricCS<E> {
     fn default() -> Self {
-        let mut map = HashMap::new();
-        map.insert("ONE".into(), NamedObject::Var(MetricCS::<E>::one()));
+        let mut cs = <MetricCS<E> as ConstraintSystem<E>>::new();
+        cs.inputs.push(String::from("ONE"));
+        cs.set_named_obj("ONE".into(), NamedObject::Var(MetricCS::<E>::one()));
+        cs
+    }
+}
+
+impl<E: ScalarEngine> ConstraintSystem<E> for MetricCS<E> {
+    type Root = Self;
+
+    /// Creates an empty constraint system. As with the other extensible
+    /// constraint systems, the caller allocates the "one" input, which
+    /// `MetricCS::new` does.
+    fn new() -> Self {
         MetricCS {
-            named_objects: map,
+            named_objects: HashMap::new(),
             current_namespace: vec![],
             constraints: vec![],
-            inputs: vec![String::from("ONE")],
+            inputs: vec![],
             aux: vec![],
         }
     }
-}
-
-impl<E: Engine> ConstraintSystem<E> for MetricCS<E> {
-    type Root = Self;
 
     fn alloc<F, A, AR>(&mut self, annotation: A, _f: F) -> Result<Variable, SynthesisError>
     where
@@ -265,6 +296,274 @@ impl<E: Engine> ConstraintSystem<E> for MetricCS<E> {
     fn get_root(&mut self) -> &mut Self::Root {
         self
     }
+
+    fn is_extensible() -> bool {
+        true
+    }
+
+    fn extend(&mut self, other: Self) {
+        // Paths of `other` are relative to our current namespace.
+        let prefix = |path: String| {
+            if self.current_namespace.is_empty() {
+                path
+            } else {
+                format!("{}/{}", self.current_namespace.join("/"), path)
+            }
+        };
+
+        // Skip first input, which must have been a temporarily allocated one variable.
+        let input_offset = self.inputs.len() - 1;
+        let aux_offset = self.aux.len();
+        let constraint_offset = self.constraints.len();
+
+        let inputs = other
+            .inputs
+            .into_iter()
+            .skip(1)
+            .map(&prefix)
+            .collect::<Vec<_>>();
+        let aux = other.aux.into_iter().map(&prefix).collect::<Vec<_>>();
+
+        // Our one variable takes the place of theirs, and all other variables
+        // are shifted past our own.
+        let remap = |var: Variable| match var.get_unchecked() {
+            Index::Input(0) => var,
+            Index::Input(i) => Variable::new_unchecked(Index::Input(input_offset + i)),
+            Index::Aux(i) => Variable::new_unchecked(Index::Aux(aux_offset + i)),
+        };
+
+        let mut named_objects = other
+            .named_objects
+            .into_iter()
+            .filter_map(|(path, obj)| {
+                let obj = match obj {
+                    NamedObject::Constraint(i) => NamedObject::Constraint(constraint_offset + i),
+                    // The "one" variable of `other` is not carried over.
+                    NamedObject::Var(var) if var == Self::one() => return None,
+                    NamedObject::Var(var) => NamedObject::Var(remap(var)),
+                    NamedObject::Namespace => NamedObject::Namespace,
+                };
+                Some((prefix(path), obj))
+            })
+            .collect::<Vec<_>>();
+
+        let mut constraints = other
+            .constraints
+            .into_iter()
+            .map(|(mut a, mut b, mut c, path)| {
+                // The mapping preserves the order of variables, so the terms stay sorted.
+                for lc in &mut [&mut a, &mut b, &mut c] {
+                    for (var, _) in lc.0.iter_mut() {
+                        *var = remap(*var);
+                    }
+                }
+                (a, b, c, prefix(path))
+            })
+            .collect::<Vec<_>>();
+
+        // Insert in a deterministic order, so duplicate paths panic reproducibly.
+        named_objects.sort_by(|a, b| a.0.cmp(&b.0));
+        for (path, obj) in named_objects {
+            self.set_named_obj(path, obj);
+        }
+
+        self.inputs.extend(inputs);
+        self.aux.extend(aux);
+        self.constraints.append(&mut constraints);
+    }
+}
+
+/// The costs attributed to a namespace.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NamespaceCost {
+    pub constraints: usize,
+    pub aux: usize,
+    pub inputs: usize,
+    /// The number of non-zero terms in the linear combinations of the
+    /// constraints.
+    pub terms: usize,
+}
+
+impl NamespaceCost {
+    fn add(&mut self, other: &NamespaceCost) {
+        self.constraints += other.constraints;
+        self.aux += other.aux;
+        self.inputs += other.inputs;
+        self.terms += other.terms;
+    }
+
+    fn to_json(&self) -> String {
+        format!(
+            "{{\"constraints\":{},\"aux\":{},\"inputs\":{},\"terms\":{}}}",
+            self.constraints, self.aux, self.inputs, self.terms
+        )
+    }
+}
+
+const ROOT_NAME: &str = "<root>";
+
+/// The cost of a circuit, aggregated per namespace as a tree.
+///
+/// Every variable and constraint is attributed to the namespace it was
+/// created in. Children are ordered by their total number of constraints,
+/// most expensive first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CostReport {
+    name: String,
+    own: NamespaceCost,
+    total: NamespaceCost,
+    children: Vec<CostReport>,
+}
+
+impl CostReport {
+    /// The name of this namespace, without the names of its parents.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The cost of the objects created directly in this namespace.
+    pub fn own(&self) -> &NamespaceCost {
+        &self.own
+    }
+
+    /// The cost of this namespace, including all of its children.
+    pub fn total(&self) -> &NamespaceCost {
+        &self.total
+    }
+
+    pub fn children(&self) -> &[CostReport] {
+        &self.children
+    }
+
+    /// Looks up the report of a namespace by its path relative to this one.
+    pub fn get(&self, path: &str) -> Option<&CostReport> {
+        path.split('/')
+            .filter(|name| !name.is_empty())
+            .try_fold(self, |report, name| {
+                report.children.iter().find(|child| child.name == name)
+            })
+    }
+
+    /// Renders the tree as indented text, one namespace per line, with
+    /// the total costs of each namespace.
+    pub fn to_text(&self) -> String {
+        fn render(report: &CostReport, depth: usize, s: &mut String) {
+            s.push_str(&format!(
+                "{:>12} {:>12} {:>8} {:>12}  {}{}\n",
+                report.total.constraints,
+                report.total.aux,
+                report.total.inputs,
+                report.total.terms,
+                "  ".repeat(depth),
+                report.name
+            ));
+            for child in &report.children {
+                render(child, depth + 1, s);
+            }
+        }
+
+        let mut s = format!(
+            "{:>12} {:>12} {:>8} {:>12}  {}\n",
+            "constraints", "aux", "inputs", "terms", "namespace"
+        );
+        render(self, 0, &mut s);
+        s
+    }
+
+    /// Renders the tree as JSON. Every namespace is an object with its
+    /// `name`, its `total` and `own` costs, and its `children`.
+    pub fn to_json(&self) -> String {
+        let children = self
+            .children
+            .iter()
+            .map(CostReport::to_json)
+            .collect::<Vec<_>>()
+            .join(",");
+        format!(
+            "{{\"name\":{},\"total\":{},\"own\":{},\"children\":[{}]}}",
+            json_string(&self.name),
+            self.total.to_json(),
+            self.own.to_json(),
+            children
+        )
+    }
+
+    /// Renders the number of constraints per namespace as folded stacks,
+    /// the input format of flamegraph tools such as `flamegraph.pl` and
+    /// `inferno-flamegraph`.
+    pub fn to_folded(&self) -> String {
+        fn render(report: &CostReport, stack: &mut Vec<String>, s: &mut String) {
+            stack.push(report.name.replace(';', ":"));
+            if report.own.constraints > 0 {
+                s.push_str(&format!("{} {}\n", stack.join(";"), report.own.constraints));
+            }
+            for child in &report.children {
+                render(child, stack, s);
+            }
+            stack.pop();
+        }
+
+        let mut s = String::new();
+        render(self, &mut vec![], &mut s);
+        s
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// A namespace while the report is being aggregated.
+#[derive(Default)]
+struct CostNode {
+    own: NamespaceCost,
+    children: BTreeMap<String, CostNode>,
+}
+
+impl CostNode {
+    /// Returns the node of the namespace containing the object at `path`.
+    fn get(&mut self, path: &str) -> &mut CostNode {
+        let mut names = path.split('/').collect::<Vec<_>>();
+        // The last component names the object itself.
+        names.pop();
+        names.into_iter().fold(self, |node, name| {
+            node.children.entry(name.to_string()).or_default()
+        })
+    }
+
+    fn into_report(self, name: String) -> CostReport {
+        let mut children = self
+            .children
+            .into_iter()
+            .map(|(name, node)| node.into_report(name))
+            .collect::<Vec<_>>();
+        // The sort is stable, so ties stay in alphabetical order.
+        children.sort_by(|a, b| b.total.constraints.cmp(&a.total.constraints));
+
+        let mut total = self.own;
+        for child in &children {
+            total.add(&child.total);
+        }
+
+        CostReport {
+            name,
+            own: self.own,
+            total,
+            children,
+        }
+    }
 }
 
 fn compute_path(ns: &[String], this: &str) -> String {
@@ -286,3 +585,120 @@ fn compute_path(ns: &[String], this: &str) -> String {
 
     name
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use paired::bls12_381::{Bls12, Fr};
+
+    fn square<CS: ConstraintSystem<Bls12>>(mut cs: CS) {
+        let x = cs.alloc(|| "x", || Ok(Fr::one())).unwrap();
+        let y = cs.alloc(|| "y", || Ok(Fr::one())).unwrap();
+        cs.enforce(|| "x * x = y", |lc| lc + x, |lc| lc + x, |lc| lc + y);
+    }
+
+    #[test]
+    fn test_extend() {
+        let mut sequential = MetricCS::<Bls12>::new();
+        sequential.alloc_input(|| "in", || Ok(Fr::one())).unwrap();
+        square(sequential.namespace(|| "first"));
+        square(sequential.namespace(|| "second"));
+
+        // A child created by `ConstraintSystem::new` and one created by
+        // `MetricCS::new` are merged the same way.
+        let mut first = <MetricCS<Bls12> as ConstraintSystem<Bls12>>::new();
+        first.alloc_input(|| "one", || Ok(Fr::one())).unwrap();
+        square(first.namespace(|| "first"));
+        let mut second = MetricCS::<Bls12>::new();
+        square(second.namespace(|| "second"));
+
+        let mut extended = MetricCS::<Bls12>::new();
+        extended.alloc_input(|| "in", || Ok(Fr::one())).unwrap();
+        extended.extend(first);
+        extended.extend(second);
+
+        assert_eq!(extended.pretty_print(), sequential.pretty_print());
+        assert_eq!(extended.num_inputs(), 2);
+        match extended.named_objects.get("second/x * x = y") {
+            Some(NamedObject::Constraint(1)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_cost_report() {
+        let mut cs = MetricCS::<Bls12>::new();
+        let out = cs.alloc_input(|| "out", || Ok(Fr::one())).unwrap();
+        {
+            let mut hash = cs.namespace(|| "hash");
+            square(hash.namespace(|| "round 0"));
+            square(hash.namespace(|| "round 1"));
+            square(hash.namespace(|| "round;2"));
+        }
+        square(cs.namespace(|| "merkle"));
+        let one = MetricCS::<Bls12>::one();
+        cs.enforce(
+            || "out",
+            |lc| lc + one,
+            |lc| lc + one,
+            |lc| lc + out - one + one,
+        );
+
+        let report = cs.cost_report();
+        assert_eq!(report.name(), "<root>");
+        assert_eq!(
+            *report.total(),
+            NamespaceCost {
+                constraints: 5,
+                aux: 8,
+                inputs: 2,
+                terms: 15,
+            }
+        );
+        assert_eq!(
+            *report.own(),
+            NamespaceCost {
+                constraints: 1,
+                aux: 0,
+                inputs: 2,
+                terms: 3,
+            }
+        );
+
+        let names = report
+            .children()
+            .iter()
+            .map(CostReport::name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["hash", "merkle"]);
+        assert_eq!(report.get("hash").unwrap().total().constraints, 3);
+        assert_eq!(report.get("hash").unwrap().own().constraints, 0);
+        assert_eq!(report.get("hash/round 1").unwrap().total().aux, 2);
+        assert!(report.get("hash/round 3").is_none());
+
+        assert_eq!(
+            report.to_folded(),
+            "<root> 1\n\
+             <root>;hash;round 0 1\n\
+             <root>;hash;round 1 1\n\
+             <root>;hash;round:2 1\n\
+             <root>;merkle 1\n"
+        );
+
+        let text = report.to_text();
+        assert_eq!(text.lines().count(), 7);
+        assert!(text.lines().nth(2).unwrap().ends_with("  hash"));
+        assert!(text.lines().nth(3).unwrap().ends_with("    round 0"));
+
+        let json = report.get("merkle").unwrap().to_json();
+        assert_eq!(
+            json,
+            "{\"name\":\"merkle\",\
+             \"total\":{\"constraints\":1,\"aux\":2,\"inputs\":0,\"terms\":3},\
+             \"own\":{\"constraints\":1,\"aux\":2,\"inputs\":0,\"terms\":3},\
+             \"children\":[]}"
+        );
+        assert_eq!(json_string("a\"b\\c\n"), "\"a\\\"b\\\\c\\n\"");
+    }
+}
@@ -1,3 +1,10 @@
 pub mod bench_cs;
+pub mod circom;
+pub mod context_cs;
+pub mod diff;
 pub mod metric_cs;
+pub mod optimize;
+pub mod r1cs;
 pub mod test_cs;
+pub mod underconstrained_cs;
+pub mod witness_cs;
@@ -0,0 +1,454 @@
+//! Elimination of linear constraints.
+//!
+//! Gadgets often enforce constraints in which `a` or `b` is a constant, such
+//! as `(x + y) * 1 = z` for additions or bit packing. Such a constraint is
+//! linear, and defines one of its auxiliary variables in terms of the others.
+//! That variable can be substituted everywhere else by its definition, after
+//! which the constraint is redundant. Fewer constraints mean a smaller FFT
+//! domain and smaller parameters.
+//!
+//! Since the eliminated variables disappear from the constraint system, the
+//! witness of a circuit must be translated with the accompanying
+//! `WitnessMap` before proving. `OptimizedCircuit` does this transparently.
+
+use std::collections::HashMap;
+
+use ff::{Field, ScalarEngine};
+
+use super::r1cs::R1CS;
+use super::witness_cs::WitnessCS;
+use crate::{Circuit, ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
+
+/// Relates the auxiliary variables of a constraint system to those of its
+/// optimized version. Inputs are never eliminated, so they stay the same.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WitnessMap {
+    num_aux: usize,
+    // The original index of every remaining auxiliary variable.
+    kept_aux: Vec<usize>,
+}
+
+impl WitnessMap {
+    /// The number of auxiliary variables before optimization.
+    pub fn num_aux(&self) -> usize {
+        self.num_aux
+    }
+
+    /// The number of auxiliary variables which were eliminated.
+    pub fn num_eliminated(&self) -> usize {
+        self.num_aux - self.kept_aux.len()
+    }
+
+    /// Maps an auxiliary assignment of the original constraint system to an
+    /// auxiliary assignment of the optimized one.
+    pub fn map_aux<T: Copy>(&self, aux: &[T]) -> Result<Vec<T>, SynthesisError> {
+        if aux.len() != self.num_aux {
+            return Err(SynthesisError::ShapeMismatch);
+        }
+
+        Ok(self.kept_aux.iter().map(|&i| aux[i]).collect())
+    }
+}
+
+/// Eliminates the linear constraints of `r1cs`, together with the auxiliary
+/// variables they define.
+///
+/// Constraints are visited in order. Every constraint where `a` or `b` is a
+/// constant is turned into an equation `l = 0`, in which the most recently
+/// allocated auxiliary variable is solved for; this is usually the variable
+/// the constraint was written to define. Linear constraints which only
+/// involve inputs are kept, and those which vanish entirely are dropped.
+pub fn eliminate_linear_constraints<E: ScalarEngine>(r1cs: &R1CS<E>) -> (R1CS<E>, WitnessMap) {
+    // Definitions of the eliminated variables, in terms of remaining
+    // variables only.
+    let mut defs: HashMap<usize, LinearCombination<E>> = HashMap::new();
+    // For every auxiliary variable, the eliminated variables whose
+    // definitions might refer to it.
+    let mut users: HashMap<usize, Vec<usize>> = HashMap::new();
+    let mut kept = vec![];
+
+    for constraint in r1cs.constraints() {
+        let (a, b, c) = constraint;
+        let ab = match (constant(a), constant(b)) {
+            (Some(k), _) => scale(b, &k),
+            (_, Some(k)) => scale(a, &k),
+            _ => {
+                kept.push(constraint);
+                continue;
+            }
+        };
+        let equation = substitute(&(ab - c), &defs);
+
+        let pivot = equation
+            .0
+            .iter()
+            .rev()
+            .find_map(|(var, coeff)| match var.get_unchecked() {
+                Index::Aux(i) => Some((i, *coeff)),
+                Index::Input(_) => None,
+            });
+
+        match pivot {
+            Some((v, coeff)) => {
+                // coeff * v + rest = 0, so v = rest * (-1 / coeff).
+                let mut factor = coeff.inverse().expect("zero terms are pruned");
+                factor.negate();
+                let def = LinearCombination(
+                    equation
+                        .0
+                        .into_iter()
+                        .filter(|(var, _)| *var != Variable(Index::Aux(v)))
+                        .map(|(var, mut coeff)| {
+                            coeff.mul_assign(&factor);
+                            (var, coeff)
+                        })
+                        .collect(),
+                );
+
+                // Keep all definitions in terms of remaining variables.
+                for u in users.remove(&v).unwrap_or_default() {
+                    let updated = substitute_one(&defs[&u], v, &def);
+                    for w in aux_indices(&def) {
+                        users.entry(w).or_default().push(u);
+                    }
+                    defs.insert(u, updated);
+                }
+                for w in aux_indices(&def) {
+                    users.entry(w).or_default().push(v);
+                }
+                defs.insert(v, def);
+            }
+            // Trivially satisfied.
+            None if equation.is_empty() => {}
+            None => kept.push(constraint),
+        }
+    }
+
+    let kept_aux = (0..r1cs.num_aux())
+        .filter(|i| !defs.contains_key(i))
+        .collect::<Vec<_>>();
+    let mut new_index = vec![0; r1cs.num_aux()];
+    for (new, &old) in kept_aux.iter().enumerate() {
+        new_index[old] = new;
+    }
+    let rewrite = |lc: &LinearCombination<E>| {
+        let lc = substitute(lc, &defs);
+        // Renumbering preserves the order of the remaining variables, so the
+        // terms stay sorted.
+        LinearCombination(
+            lc.0.into_iter()
+                .map(|(var, coeff)| match var.get_unchecked() {
+                    Index::Aux(i) => (Variable(Index::Aux(new_index[i])), coeff),
+                    Index::Input(_) => (var, coeff),
+                })
+                .collect(),
+        )
+    };
+
+    let constraints = kept
+        .into_iter()
+        .map(|(a, b, c)| (rewrite(a), rewrite(b), rewrite(c)))
+        .collect();
+    let optimized = R1CS::from_parts(r1cs.num_inputs(), kept_aux.len(), constraints);
+
+    let map = WitnessMap {
+        num_aux: r1cs.num_aux(),
+        kept_aux,
+    };
+
+    (optimized, map)
+}
+
+/// Returns the value of `lc` if it only refers to the "one" input.
+fn constant<E: ScalarEngine>(lc: &LinearCombination<E>) -> Option<E::Fr> {
+    let mut value = E::Fr::zero();
+    for (var, coeff) in lc.iter() {
+        match var.get_unchecked() {
+            Index::Input(0) => value.add_assign(coeff),
+            _ => return None,
+        }
+    }
+    Some(value)
+}
+
+fn scale<E: ScalarEngine>(lc: &LinearCombination<E>, k: &E::Fr) -> LinearCombination<E> {
+    LinearCombination::zero() + (*k, lc)
+}
+
+fn aux_indices<E: ScalarEngine>(lc: &LinearCombination<E>) -> Vec<usize> {
+    lc.iter()
+        .filter_map(|(var, _)| match var.get_unchecked() {
+            Index::Aux(i) => Some(i),
+            Index::Input(_) => None,
+        })
+        .collect()
+}
+
+/// Drops the terms whose coefficient cancelled out.
+fn prune<E: ScalarEngine>(lc: LinearCombination<E>) -> LinearCombination<E> {
+    LinearCombination(
+        lc.0.into_iter()
+            .filter(|(_, coeff)| !coeff.is_zero())
+            .collect(),
+    )
+}
+
+/// Replaces every eliminated variable in `lc` by its definition.
+fn substitute<E: ScalarEngine>(
+    lc: &LinearCombination<E>,
+    defs: &HashMap<usize, LinearCombination<E>>,
+) -> LinearCombination<E> {
+    let mut res = LinearCombination::zero();
+    for (var, coeff) in lc.iter() {
+        res = match var.get_unchecked() {
+            Index::Aux(i) if defs.contains_key(&i) => res + (*coeff, &defs[&i]),
+            _ => res + (*coeff, *var),
+        };
+    }
+    prune(res)
+}
+
+/// Replaces the auxiliary variable `v` in `lc` by `def`.
+fn substitute_one<E: ScalarEngine>(
+    lc: &LinearCombination<E>,
+    v: usize,
+    def: &LinearCombination<E>,
+) -> LinearCombination<E> {
+    let var = Variable(Index::Aux(v));
+    match lc.0.binary_search_by(|(other, _)| other.cmp(&var)) {
+        Ok(pos) => {
+            let mut rest = lc.clone();
+            let (_, coeff) = rest.0.remove(pos);
+            prune(rest + (coeff, def))
+        }
+        Err(_) => lc.clone(),
+    }
+}
+
+/// Synthesizes an optimized constraint system, with the witness of the
+/// original circuit translated through its `WitnessMap`.
+///
+/// The original circuit is only synthesized for its witness. If that fails
+/// with `SynthesisError::AssignmentMissing`, the constraints are synthesized
+/// without an assignment, which is what parameter generation needs; proving
+/// then fails with the same error.
+pub struct OptimizedCircuit<'a, E: ScalarEngine, C> {
+    r1cs: &'a R1CS<E>,
+    map: &'a WitnessMap,
+    circuit: C,
+}
+
+impl<'a, E: ScalarEngine, C: Circuit<E>> OptimizedCircuit<'a, E, C> {
+    /// `r1cs` and `map` must have been obtained by optimizing the constraint
+    /// system of `circuit`.
+    pub fn new(r1cs: &'a R1CS<E>, map: &'a WitnessMap, circuit: C) -> Self {
+        OptimizedCircuit { r1cs, map, circuit }
+    }
+}
+
+impl<'a, E: ScalarEngine, C: Circuit<E>> Circuit<E> for OptimizedCircuit<'a, E, C> {
+    fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let mut witness = WitnessCS::new();
+        witness.alloc_input(|| "", || Ok(E::Fr::one()))?;
+
+        let assignment = match self.circuit.synthesize(&mut witness) {
+            Ok(()) => {
+                let (mut assignment, aux) = witness.into_assignment();
+                if assignment.len() != self.r1cs.num_inputs() {
+                    return Err(SynthesisError::ShapeMismatch);
+                }
+                assignment.extend(self.map.map_aux(&aux)?);
+                Some(assignment)
+            }
+            Err(SynthesisError::AssignmentMissing) => None,
+            Err(e) => return Err(e),
+        };
+
+        self.r1cs.replay(cs, assignment.as_ref().map(|w| &w[..]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::groth16::{
+        create_random_proof, generate_random_parameters, prepare_verifying_key, verify_proof,
+    };
+    use crate::util_cs::test_cs::TestConstraintSystem;
+    use ff::PrimeField;
+    use paired::bls12_381::{Bls12, Fr};
+    use rand_core::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    /// Computes `out = (y^2 + x) * x` with `y = 3x + 2`, through a mix of
+    /// linear and non-linear constraints.
+    #[derive(Clone)]
+    struct Mixed {
+        x: Option<Fr>,
+    }
+
+    impl Mixed {
+        fn values(x: Fr) -> (Fr, Fr, Fr, Fr) {
+            let mut y = x;
+            y.mul_assign(&Fr::from_str("3").unwrap());
+            y.add_assign(&Fr::from_str("2").unwrap());
+            let mut z = y;
+            z.square();
+            let mut w = z;
+            w.add_assign(&x);
+            let mut out = w;
+            out.mul_assign(&x);
+            (y, z, w, out)
+        }
+    }
+
+    impl Circuit<Bls12> for Mixed {
+        fn synthesize<CS: ConstraintSystem<Bls12>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let values = self.x.map(|x| (x, Mixed::values(x)));
+            let get = |f: fn(&(Fr, (Fr, Fr, Fr, Fr))) -> Fr| {
+                values
+                    .as_ref()
+                    .map(f)
+                    .ok_or(SynthesisError::AssignmentMissing)
+            };
+
+            let x = cs.alloc(|| "x", || get(|v| v.0))?;
+            let y = cs.alloc(|| "y", || get(|v| (v.1).0))?;
+            let z = cs.alloc(|| "z", || get(|v| (v.1).1))?;
+            let w = cs.alloc(|| "w", || get(|v| (v.1).2))?;
+            let out = cs.alloc_input(|| "out", || get(|v| (v.1).3))?;
+
+            let three = Fr::from_str("3").unwrap();
+            let two = Fr::from_str("2").unwrap();
+            cs.enforce(
+                || "y = 3x + 2",
+                |lc| lc + (three, x) + (two, CS::one()),
+                |lc| lc + CS::one(),
+                |lc| lc + y,
+            );
+            cs.enforce(|| "z = y^2", |lc| lc + y, |lc| lc + y, |lc| lc + z);
+            cs.enforce(
+                || "w = z + x",
+                |lc| lc + CS::one(),
+                |lc| lc + z + x,
+                |lc| lc + w,
+            );
+            cs.enforce(|| "x = x", |lc| lc + x, |lc| lc + CS::one(), |lc| lc + x);
+            cs.enforce(|| "out = w * x", |lc| lc + w, |lc| lc + x, |lc| lc + out);
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_eliminate_linear_constraints() {
+        let mut r1cs = R1CS::<Bls12>::new();
+        Mixed { x: None }.synthesize(&mut r1cs).unwrap();
+        assert_eq!(r1cs.num_constraints(), 5);
+
+        let (optimized, map) = eliminate_linear_constraints(&r1cs);
+        assert_eq!(optimized.num_constraints(), 2);
+        assert_eq!(optimized.num_inputs(), 2);
+        assert_eq!(optimized.num_aux(), 2);
+        assert_eq!(map.num_aux(), 4);
+        assert_eq!(map.num_eliminated(), 2);
+
+        let x = Fr::from_str("7").unwrap();
+        let (y, z, w, out) = Mixed::values(x);
+        let inputs = [Fr::one(), out];
+        let aux = map.map_aux(&[x, y, z, w]).unwrap();
+        assert_eq!(aux, vec![x, z]);
+        assert!(optimized.is_satisfied(&inputs, &aux));
+        assert!(!optimized.is_satisfied(&inputs, &[x, w]));
+        assert!(map.map_aux(&[x, y, z]).is_err());
+
+        let mut cs = TestConstraintSystem::<Bls12>::new();
+        OptimizedCircuit::new(&optimized, &map, Mixed { x: Some(x) })
+            .synthesize(&mut cs)
+            .unwrap();
+        assert!(cs.is_satisfied());
+        assert_eq!(cs.num_constraints(), 2);
+
+        // An already optimized constraint system is left alone.
+        let (again, map) = eliminate_linear_constraints(&optimized);
+        assert_eq!(again.num_constraints(), 2);
+        assert_eq!(map.num_eliminated(), 0);
+    }
+
+    #[test]
+    fn test_chained_definitions() {
+        // a = b + c is solved for a first; then c = 2b is solved for c, which
+        // must also be substituted in the definition of a.
+        let mut r1cs = R1CS::<Bls12>::new();
+        let out = r1cs.alloc_input(|| "out", || unreachable!()).unwrap();
+        let b = r1cs.alloc(|| "b", || unreachable!()).unwrap();
+        let c = r1cs.alloc(|| "c", || unreachable!()).unwrap();
+        let a = r1cs.alloc(|| "a", || unreachable!()).unwrap();
+        let one = R1CS::<Bls12>::one();
+        r1cs.enforce(|| "a", |lc| lc + b + c, |lc| lc + one, |lc| lc + a);
+        r1cs.enforce(
+            || "c",
+            |lc| lc + (Fr::from_str("2").unwrap(), b),
+            |lc| lc + one,
+            |lc| lc + c,
+        );
+        r1cs.enforce(|| "out", |lc| lc + a, |lc| lc + a, |lc| lc + out);
+
+        let (optimized, map) = eliminate_linear_constraints(&r1cs);
+        assert_eq!(optimized.num_constraints(), 1);
+        assert_eq!(map.num_eliminated(), 2);
+
+        // b = 5, c = 10, a = 15
+        let values = ["5", "10", "15"]
+            .iter()
+            .map(|v| Fr::from_str(v).unwrap())
+            .collect::<Vec<_>>();
+        let inputs = [Fr::one(), Fr::from_str("225").unwrap()];
+        assert!(r1cs.is_satisfied(&inputs, &values));
+        assert!(optimized.is_satisfied(&inputs, &map.map_aux(&values).unwrap()));
+        assert!(!optimized.is_satisfied(&[Fr::one(), Fr::from_str("100").unwrap()], &values[..1]));
+    }
+
+    #[test]
+    fn test_optimized_proof() {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        let mut r1cs = R1CS::<Bls12>::new();
+        Mixed { x: None }.synthesize(&mut r1cs).unwrap();
+        let (optimized, map) = eliminate_linear_constraints(&r1cs);
+
+        let params = generate_random_parameters(
+            OptimizedCircuit::new(&optimized, &map, Mixed { x: None }),
+            rng,
+        )
+        .unwrap();
+        let pvk = prepare_verifying_key(&params.vk);
+
+        let x = Fr::from_str("7").unwrap();
+        let (_, _, _, out) = Mixed::values(x);
+        let proof = create_random_proof(
+            OptimizedCircuit::new(&optimized, &map, Mixed { x: Some(x) }),
+            &params,
+            rng,
+        )
+        .unwrap();
+        assert!(verify_proof(&pvk, &proof, &[out]).unwrap());
+        assert!(!verify_proof(&pvk, &proof, &[x]).unwrap());
+
+        match create_random_proof(
+            OptimizedCircuit::new(&optimized, &map, Mixed { x: None }),
+            &params,
+            rng,
+        ) {
+            Err(SynthesisError::AssignmentMissing) => {}
+            _ => panic!("expected AssignmentMissing"),
+        }
+    }
+}
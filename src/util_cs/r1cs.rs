@@ -0,0 +1,584 @@
+//! A plain rank-1 constraint system, with import and export in the binary
+//! `.r1cs` format used by the iden3/circom toolchain.
+//!
+//! The format is described in
+//! <https://github.com/iden3/r1csfile/blob/master/doc/r1cs_bin_format.md>.
+//! Circom numbers its wires starting with the constant one, followed by the
+//! public outputs, the public inputs and finally all private wires. Since
+//! bellperson orders inputs before auxiliary variables as well, the wire of
+//! `Index::Input(i)` is `i`, and the wire of `Index::Aux(i)` is
+//! `num_inputs + i`. All of our inputs besides "one" are exported as public
+//! inputs; private inputs cannot be told apart from other auxiliary variables.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use ff::{Field, PrimeField, PrimeFieldRepr, ScalarEngine};
+
+use crate::{ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
+
+const MAGIC: &[u8; 4] = b"r1cs";
+const VERSION: u32 = 1;
+
+const SECTION_HEADER: u32 = 1;
+const SECTION_CONSTRAINTS: u32 = 2;
+const SECTION_WIRE_TO_LABEL: u32 = 3;
+
+/// The constraint matrices of a circuit, without any assignment.
+///
+/// `R1CS` is itself a `ConstraintSystem`, so it can be collected by
+/// synthesizing a circuit into it. Namespaces and annotations are dropped.
+#[derive(Clone)]
+pub struct R1CS<E: ScalarEngine> {
+    num_inputs: usize,
+    num_aux: usize,
+    #[allow(clippy::type_complexity)]
+    constraints: Vec<(
+        LinearCombination<E>,
+        LinearCombination<E>,
+        LinearCombination<E>,
+    )>,
+}
+
+impl<E: ScalarEngine> Default for R1CS<E> {
+    fn default() -> Self {
+        R1CS {
+            // The "one" input is always allocated.
+            num_inputs: 1,
+            num_aux: 0,
+            constraints: vec![],
+        }
+    }
+}
+
+impl<E: ScalarEngine> R1CS<E> {
+    pub fn new() -> Self {
+        R1CS::default()
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub(super) fn from_parts(
+        num_inputs: usize,
+        num_aux: usize,
+        constraints: Vec<(
+            LinearCombination<E>,
+            LinearCombination<E>,
+            LinearCombination<E>,
+        )>,
+    ) -> Self {
+        R1CS {
+            num_inputs,
+            num_aux,
+            constraints,
+        }
+    }
+
+    /// The number of public inputs, including the "one" input.
+    pub fn num_inputs(&self) -> usize {
+        self.num_inputs
+    }
+
+    pub fn num_aux(&self) -> usize {
+        self.num_aux
+    }
+
+    pub fn num_constraints(&self) -> usize {
+        self.constraints.len()
+    }
+
+    /// The constraints, as `(a, b, c)` triples enforcing `a * b = c`.
+    pub fn constraints(
+        &self,
+    ) -> &[(
+        LinearCombination<E>,
+        LinearCombination<E>,
+        LinearCombination<E>,
+    )] {
+        &self.constraints
+    }
+
+    /// Checks whether the given assignment satisfies every constraint.
+    /// `inputs` must include the "one" input.
+    pub fn is_satisfied(&self, inputs: &[E::Fr], aux: &[E::Fr]) -> bool {
+        if inputs.len() != self.num_inputs || aux.len() != self.num_aux {
+            return false;
+        }
+
+        let eval = |lc: &LinearCombination<E>| {
+            let mut acc = E::Fr::zero();
+            for (var, coeff) in lc.iter() {
+                let mut tmp = match var.get_unchecked() {
+                    Index::Input(i) => inputs[i],
+                    Index::Aux(i) => aux[i],
+                };
+                tmp.mul_assign(coeff);
+                acc.add_assign(&tmp);
+            }
+            acc
+        };
+
+        self.constraints.iter().all(|(a, b, c)| {
+            let mut ab = eval(a);
+            ab.mul_assign(&eval(b));
+            ab == eval(c)
+        })
+    }
+
+    /// Allocates every variable of this constraint system in `cs` and
+    /// enforces all of its constraints there. The witness, if any, holds one
+    /// value per wire, starting with the constant one.
+    pub fn replay<CS: ConstraintSystem<E>>(
+        &self,
+        cs: &mut CS,
+        witness: Option<&[E::Fr]>,
+    ) -> Result<(), SynthesisError> {
+        let num_inputs = self.num_inputs;
+        let num_wires = self.num_wires();
+        if let Some(witness) = witness {
+            if witness.len() != num_wires {
+                return Err(SynthesisError::WitnessLengthMismatch {
+                    expected: num_wires,
+                    actual: witness.len(),
+                });
+            }
+        }
+        let value = |wire: usize| {
+            witness
+                .map(|w| w[wire])
+                .ok_or(SynthesisError::AssignmentMissing)
+        };
+
+        // Variables of `cs`, by wire.
+        let mut vars = Vec::with_capacity(num_wires);
+        vars.push(CS::one());
+        for wire in 1..num_inputs {
+            vars.push(cs.alloc_input(|| format!("public {}", wire), || value(wire))?);
+        }
+        for wire in num_inputs..num_wires {
+            vars.push(cs.alloc(|| format!("wire {}", wire), || value(wire))?);
+        }
+
+        let replay = |lc: LinearCombination<E>, terms: &LinearCombination<E>| {
+            terms.iter().fold(lc, |lc, (var, coeff)| {
+                let wire = match var.get_unchecked() {
+                    Index::Input(i) => i,
+                    Index::Aux(i) => num_inputs + i,
+                };
+                lc + (*coeff, vars[wire])
+            })
+        };
+
+        for (i, (a, b, c)) in self.constraints.iter().enumerate() {
+            cs.enforce(
+                || format!("constraint {}", i),
+                |lc| replay(lc, a),
+                |lc| replay(lc, b),
+                |lc| replay(lc, c),
+            );
+        }
+
+        Ok(())
+    }
+
+    fn wire(&self, var: Variable) -> usize {
+        match var.get_unchecked() {
+            Index::Input(i) => i,
+            Index::Aux(i) => self.num_inputs + i,
+        }
+    }
+
+    fn num_wires(&self) -> usize {
+        self.num_inputs + self.num_aux
+    }
+
+    /// Serializes the constraint system in the circom `.r1cs` format.
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let n8 = field_size::<E>();
+
+        writer.write_all(MAGIC)?;
+        writer.write_u32::<LittleEndian>(VERSION)?;
+        writer.write_u32::<LittleEndian>(3)?;
+
+        // Header
+        writer.write_u32::<LittleEndian>(SECTION_HEADER)?;
+        writer.write_u64::<LittleEndian>(n8 as u64 + 32)?;
+        write_field::<E, _>(&mut writer)?;
+        write_u32(&mut writer, self.num_wires())?;
+        // Public outputs
+        writer.write_u32::<LittleEndian>(0)?;
+        // Public inputs, excluding "one"
+        write_u32(&mut writer, self.num_inputs - 1)?;
+        // Private inputs
+        writer.write_u32::<LittleEndian>(0)?;
+        writer.write_u64::<LittleEndian>(self.num_wires() as u64)?;
+        write_u32(&mut writer, self.constraints.len())?;
+
+        // Constraints
+        let size = self
+            .constraints
+            .iter()
+            .map(|(a, b, c)| 12 + (a.len() + b.len() + c.len()) * (4 + n8))
+            .sum::<usize>();
+        writer.write_u32::<LittleEndian>(SECTION_CONSTRAINTS)?;
+        writer.write_u64::<LittleEndian>(size as u64)?;
+        for (a, b, c) in &self.constraints {
+            for lc in &[a, b, c] {
+                write_u32(&mut writer, lc.len())?;
+                for (var, coeff) in lc.iter() {
+                    write_u32(&mut writer, self.wire(*var))?;
+                    coeff.into_repr().write_le(&mut writer)?;
+                }
+            }
+        }
+
+        // Wire to label map; we have no labels besides the wires themselves.
+        writer.write_u32::<LittleEndian>(SECTION_WIRE_TO_LABEL)?;
+        writer.write_u64::<LittleEndian>(self.num_wires() as u64 * 8)?;
+        for wire in 0..self.num_wires() {
+            writer.write_u64::<LittleEndian>(wire as u64)?;
+        }
+
+        Ok(())
+    }
+
+    /// Deserializes a constraint system in the circom `.r1cs` format. The
+    /// file must be defined over the scalar field of `E`.
+    pub fn read<R: Read>(reader: R) -> io::Result<Self> {
+        let sections = read_sections(reader, MAGIC, VERSION)?;
+
+        let mut header = &sections
+            .get(&SECTION_HEADER)
+            .ok_or_else(|| invalid_data("missing r1cs header"))?[..];
+        read_field::<E, _>(&mut header)?;
+        let num_wires = header.read_u32::<LittleEndian>()? as usize;
+        let num_pub_out = header.read_u32::<LittleEndian>()? as usize;
+        let num_pub_in = header.read_u32::<LittleEndian>()? as usize;
+        let _num_prv_in = header.read_u32::<LittleEndian>()?;
+        let _num_labels = header.read_u64::<LittleEndian>()?;
+        let num_constraints = header.read_u32::<LittleEndian>()? as usize;
+
+        let num_inputs = 1 + num_pub_out + num_pub_in;
+        if num_wires < num_inputs {
+            return Err(invalid_data("r1cs has fewer wires than public signals"));
+        }
+
+        let mut data = &sections
+            .get(&SECTION_CONSTRAINTS)
+            .ok_or_else(|| invalid_data("missing r1cs constraints"))?[..];
+
+        // The number of constraints comes from the file, so the capacity is
+        // bounded by the size of the section: every constraint takes at least
+        // the three term counts.
+        let mut r1cs = R1CS {
+            num_inputs,
+            num_aux: num_wires - num_inputs,
+            constraints: Vec::with_capacity(num_constraints.min(data.len() / 12)),
+        };
+        let mut read_lc = || -> io::Result<LinearCombination<E>> {
+            let num_terms = data.read_u32::<LittleEndian>()?;
+            let mut lc = LinearCombination::zero();
+            for _ in 0..num_terms {
+                let wire = data.read_u32::<LittleEndian>()? as usize;
+                let var = if wire < num_inputs {
+                    Variable(Index::Input(wire))
+                } else if wire < num_wires {
+                    Variable(Index::Aux(wire - num_inputs))
+                } else {
+                    return Err(invalid_data("r1cs wire out of range"));
+                };
+
+                let coeff = read_fr::<E, _>(&mut data)?;
+
+                lc = lc + (coeff, var);
+            }
+            Ok(lc)
+        };
+        for _ in 0..num_constraints {
+            let a = read_lc()?;
+            let b = read_lc()?;
+            let c = read_lc()?;
+            r1cs.constraints.push((a, b, c));
+        }
+
+        Ok(r1cs)
+    }
+}
+
+/// Reads a file in the binary container format shared by the iden3 tools,
+/// returning the contents of each section by section type. Sections may
+/// appear in any order, so they are all collected before being parsed.
+pub(crate) fn read_sections<R: Read>(
+    mut reader: R,
+    magic: &[u8; 4],
+    max_version: u32,
+) -> io::Result<HashMap<u32, Vec<u8>>> {
+    let mut file_magic = [0u8; 4];
+    reader.read_exact(&mut file_magic)?;
+    if &file_magic != magic {
+        return Err(invalid_data(format!(
+            "not a {} file",
+            String::from_utf8_lossy(magic)
+        )));
+    }
+
+    let version = reader.read_u32::<LittleEndian>()?;
+    if version == 0 || version > max_version {
+        return Err(invalid_data(format!(
+            "unsupported {} version {}",
+            String::from_utf8_lossy(magic),
+            version
+        )));
+    }
+
+    let num_sections = reader.read_u32::<LittleEndian>()?;
+    let mut sections = HashMap::new();
+    for _ in 0..num_sections {
+        let ty = reader.read_u32::<LittleEndian>()?;
+        let size = reader.read_u64::<LittleEndian>()?;
+        let mut data = vec![];
+        (&mut reader).take(size).read_to_end(&mut data)?;
+        if data.len() as u64 != size {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "truncated section",
+            ));
+        }
+        if sections.insert(ty, data).is_some() {
+            return Err(invalid_data(format!("duplicate section {}", ty)));
+        }
+    }
+
+    Ok(sections)
+}
+
+/// Writes a count or a wire, which the format stores as a `u32`.
+pub(crate) fn write_u32<W: Write>(writer: &mut W, value: usize) -> io::Result<()> {
+    let value = u32::try_from(value).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the count or wire is too large to be serialized",
+        )
+    })?;
+    writer.write_u32::<LittleEndian>(value)
+}
+
+/// Writes the size of a field element followed by the field modulus.
+pub(crate) fn write_field<E: ScalarEngine, W: Write>(mut writer: W) -> io::Result<()> {
+    writer.write_u32::<LittleEndian>(field_size::<E>() as u32)?;
+    E::Fr::char().write_le(&mut writer)
+}
+
+/// Reads the size of a field element and the field modulus, and checks that
+/// they describe the scalar field of `E`.
+pub(crate) fn read_field<E: ScalarEngine, R: Read>(mut reader: R) -> io::Result<()> {
+    let n8 = reader.read_u32::<LittleEndian>()? as usize;
+    if n8 != field_size::<E>() {
+        return Err(invalid_data("field size mismatch"));
+    }
+    let mut prime = <E::Fr as PrimeField>::Repr::default();
+    prime.read_le(&mut reader)?;
+    if prime != E::Fr::char() {
+        return Err(invalid_data("prime does not match the scalar field"));
+    }
+
+    Ok(())
+}
+
+/// Reads a little-endian field element in canonical (non-Montgomery) form.
+pub(crate) fn read_fr<E: ScalarEngine, R: Read>(reader: R) -> io::Result<E::Fr> {
+    let mut repr = <E::Fr as PrimeField>::Repr::default();
+    repr.read_le(reader)?;
+    E::Fr::from_repr(repr).map_err(invalid_data)
+}
+
+/// The number of bytes of a serialized field element.
+pub(crate) fn field_size<E: ScalarEngine>() -> usize {
+    <E::Fr as PrimeField>::Repr::default().as_ref().len() * 8
+}
+
+pub(crate) fn invalid_data<T: Into<Box<dyn std::error::Error + Send + Sync>>>(e: T) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+impl<E: ScalarEngine> ConstraintSystem<E> for R1CS<E> {
+    type Root = Self;
+
+    fn new() -> Self {
+        R1CS::default()
+    }
+
+    fn alloc<F, A, AR>(&mut self, _: A, _: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<E::Fr, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        // There is no assignment, so we don't even invoke the
+        // function for obtaining one.
+        self.num_aux += 1;
+
+        Ok(Variable(Index::Aux(self.num_aux - 1)))
+    }
+
+    fn alloc_input<F, A, AR>(&mut self, _: A, _: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<E::Fr, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        // There is no assignment, so we don't even invoke the
+        // function for obtaining one.
+        self.num_inputs += 1;
+
+        Ok(Variable(Index::Input(self.num_inputs - 1)))
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(&mut self, _: A, a: LA, b: LB, c: LC)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+        LA: FnOnce(LinearCombination<E>) -> LinearCombination<E>,
+        LB: FnOnce(LinearCombination<E>) -> LinearCombination<E>,
+        LC: FnOnce(LinearCombination<E>) -> LinearCombination<E>,
+    {
+        let a = a(LinearCombination::zero());
+        let b = b(LinearCombination::zero());
+        let c = c(LinearCombination::zero());
+
+        self.constraints.push((a, b, c));
+    }
+
+    fn push_namespace<NR, N>(&mut self, _: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        // Do nothing; we don't care about namespaces in this context.
+    }
+
+    fn pop_namespace(&mut self) {
+        // Do nothing; we don't care about namespaces in this context.
+    }
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use paired::bls12_381::{Bls12, Fr};
+
+    fn terms(lc: &LinearCombination<Bls12>) -> Vec<(Variable, Fr)> {
+        lc.iter().map(|(&var, &coeff)| (var, coeff)).collect()
+    }
+
+    #[test]
+    fn test_r1cs_roundtrip() {
+        let mut cs = R1CS::<Bls12>::new();
+        let a = cs.alloc(|| "a", || Ok(Fr::from_str("3").unwrap())).unwrap();
+        let b = cs.alloc(|| "b", || Ok(Fr::from_str("4").unwrap())).unwrap();
+        let c = cs
+            .alloc_input(|| "c", || Ok(Fr::from_str("12").unwrap()))
+            .unwrap();
+        cs.enforce(|| "a * b = c", |lc| lc + a, |lc| lc + b, |lc| lc + c);
+        cs.enforce(
+            || "(2a + b) * 1 = 2a + b",
+            |lc| lc + (Fr::from_str("2").unwrap(), a) + b,
+            |lc| lc + R1CS::<Bls12>::one(),
+            |lc| lc + b + (Fr::from_str("2").unwrap(), a),
+        );
+
+        assert_eq!(cs.num_inputs(), 2);
+        assert_eq!(cs.num_aux(), 2);
+        assert_eq!(cs.num_constraints(), 2);
+
+        let inputs = [Fr::one(), Fr::from_str("12").unwrap()];
+        let aux = [Fr::from_str("3").unwrap(), Fr::from_str("4").unwrap()];
+        assert!(cs.is_satisfied(&inputs, &aux));
+        let bad_aux = [Fr::from_str("3").unwrap(), Fr::from_str("5").unwrap()];
+        assert!(!cs.is_satisfied(&inputs, &bad_aux));
+
+        let mut buf = vec![];
+        cs.write(&mut buf).unwrap();
+
+        // magic, version, number of sections
+        assert_eq!(&buf[..4], b"r1cs");
+        assert_eq!(&buf[4..12], &[1, 0, 0, 0, 3, 0, 0, 0]);
+        // header section of 64 bytes
+        assert_eq!(&buf[12..24], &[1, 0, 0, 0, 64, 0, 0, 0, 0, 0, 0, 0]);
+        // field size, followed by the little-endian BLS12-381 scalar modulus
+        assert_eq!(&buf[24..28], &[32, 0, 0, 0]);
+        assert_eq!(buf[28], 0x01);
+        assert_eq!(buf[59], 0x73);
+
+        let read = R1CS::<Bls12>::read(&buf[..]).unwrap();
+        assert_eq!(read.num_inputs(), cs.num_inputs());
+        assert_eq!(read.num_aux(), cs.num_aux());
+        assert_eq!(read.num_constraints(), cs.num_constraints());
+        for (expected, actual) in cs.constraints().iter().zip(read.constraints()) {
+            assert_eq!(terms(&expected.0), terms(&actual.0));
+            assert_eq!(terms(&expected.1), terms(&actual.1));
+            assert_eq!(terms(&expected.2), terms(&actual.2));
+        }
+        assert!(read.is_satisfied(&inputs, &aux));
+
+        let mut rewritten = vec![];
+        read.write(&mut rewritten).unwrap();
+        assert_eq!(buf, rewritten);
+    }
+
+    #[test]
+    fn test_r1cs_read_invalid() {
+        let mut buf = vec![];
+        R1CS::<Bls12>::new().write(&mut buf).unwrap();
+
+        let mut bad_magic = buf.clone();
+        bad_magic[0] = b'x';
+        assert!(R1CS::<Bls12>::read(&bad_magic[..]).is_err());
+
+        let mut bad_prime = buf.clone();
+        bad_prime[28] ^= 1;
+        assert!(R1CS::<Bls12>::read(&bad_prime[..]).is_err());
+
+        assert!(R1CS::<Bls12>::read(&buf[..buf.len() - 1]).is_err());
+
+        // A header claiming more constraints than the file holds.
+        let mut bad_count = buf.clone();
+        bad_count[84..88].copy_from_slice(&[0xff; 4]);
+        assert!(R1CS::<Bls12>::read(&bad_count[..]).is_err());
+    }
+
+    #[test]
+    fn test_r1cs_write_too_large() {
+        let mut cs = R1CS::<Bls12>::new();
+        cs.num_aux = u32::max_value() as usize;
+
+        let err = cs.write(&mut vec![]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_r1cs_replay_witness_length() {
+        let mut cs = R1CS::<Bls12>::new();
+        let a = cs.alloc(|| "a", || Ok(Fr::one())).unwrap();
+        cs.enforce(|| "a * a = a", |lc| lc + a, |lc| lc + a, |lc| lc + a);
+
+        let mut replayed = R1CS::<Bls12>::new();
+        match cs.replay(&mut replayed, Some(&[Fr::one()])) {
+            Err(SynthesisError::WitnessLengthMismatch { expected, actual }) => {
+                assert_eq!((expected, actual), (2, 1));
+            }
+            _ => panic!("expected the witness to be rejected"),
+        }
+
+        let mut replayed = R1CS::<Bls12>::new();
+        cs.replay(&mut replayed, Some(&[Fr::one(), Fr::one()]))
+            .unwrap();
+        assert_eq!(replayed.num_constraints(), 1);
+    }
+}
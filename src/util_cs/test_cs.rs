@@ -1,12 +1,12 @@
 use std::cmp::Ordering;
 use std::collections::BTreeMap;
 use std::collections::HashMap;
+use std::fmt::Write;
 
 use crate::{ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
 use blake2s_simd::State as Blake2s;
 use byteorder::{BigEndian, ByteOrder};
-use ff::{Field, PrimeField, PrimeFieldRepr};
-use paired::Engine;
+use ff::{Field, PrimeField, PrimeFieldRepr, ScalarEngine};
 
 #[derive(Debug)]
 enum NamedObject {
@@ -16,7 +16,7 @@ enum NamedObject {
 }
 
 /// Constraint system for testing purposes.
-pub struct TestConstraintSystem<E: Engine> {
+pub struct TestConstraintSystem<E: ScalarEngine> {
     named_objects: HashMap<String, NamedObject>,
     current_namespace: Vec<String>,
     #[allow(clippy::type_complexity)]
@@ -59,7 +59,7 @@ impl Ord for OrderedVariable {
     }
 }
 
-fn proc_lc<E: Engine>(terms: &LinearCombination<E>) -> BTreeMap<OrderedVariable, E::Fr> {
+fn proc_lc<E: ScalarEngine>(terms: &LinearCombination<E>) -> BTreeMap<OrderedVariable, E::Fr> {
     let mut map = BTreeMap::new();
     for (&var, &coeff) in terms.iter() {
         map.entry(OrderedVariable(var))
@@ -82,7 +82,7 @@ fn proc_lc<E: Engine>(terms: &LinearCombination<E>) -> BTreeMap<OrderedVariable,
     map
 }
 
-fn hash_lc<E: Engine>(terms: &LinearCombination<E>, h: &mut Blake2s) {
+fn hash_lc<E: ScalarEngine>(terms: &LinearCombination<E>, h: &mut Blake2s) {
     let map = proc_lc::<E>(terms);
 
     let mut buf = [0u8; 9 + 32];
@@ -110,7 +110,11 @@ fn hash_lc<E: Engine>(terms: &LinearCombination<E>, h: &mut Blake2s) {
     }
 }
 
-fn _eval_lc2<E: Engine>(terms: &LinearCombination<E>, inputs: &[E::Fr], aux: &[E::Fr]) -> E::Fr {
+fn _eval_lc2<E: ScalarEngine>(
+    terms: &LinearCombination<E>,
+    inputs: &[E::Fr],
+    aux: &[E::Fr],
+) -> E::Fr {
     let mut acc = E::Fr::zero();
 
     for (&var, coeff) in terms.iter() {
@@ -126,7 +130,7 @@ fn _eval_lc2<E: Engine>(terms: &LinearCombination<E>, inputs: &[E::Fr], aux: &[E
     acc
 }
 
-fn eval_lc<E: Engine>(
+fn eval_lc<E: ScalarEngine>(
     terms: &LinearCombination<E>,
     inputs: &[(E::Fr, String)],
     aux: &[(E::Fr, String)],
@@ -146,7 +150,7 @@ fn eval_lc<E: Engine>(
     acc
 }
 
-impl<E: Engine> Default for TestConstraintSystem<E> {
+impl<E: ScalarEngine> Default for TestConstraintSystem<E> {
     fn default() -> Self {
         let mut map = HashMap::new();
         map.insert(
@@ -164,7 +168,7 @@ impl<E: Engine> Default for TestConstraintSystem<E> {
     }
 }
 
-impl<E: Engine> TestConstraintSystem<E> {
+impl<E: ScalarEngine> TestConstraintSystem<E> {
     pub fn new() -> Self {
         Default::default()
     }
@@ -192,6 +196,75 @@ impl<E: Engine> TestConstraintSystem<E> {
         res.join("\n")
     }
 
+    /// Prints every constraint as `name: (a) * (b) = (c)`, with the variables
+    /// of the linear combinations given by their paths.
+    pub fn pretty_print_constraints(&self) -> String {
+        let mut s = String::new();
+
+        let negone = {
+            let mut tmp = E::Fr::one();
+            tmp.negate();
+            tmp
+        };
+
+        let powers_of_two = (0..E::Fr::NUM_BITS)
+            .map(|i| E::Fr::from_str("2").unwrap().pow(&[u64::from(i)]))
+            .collect::<Vec<_>>();
+
+        let pp = |s: &mut String, lc: &LinearCombination<E>| {
+            write!(s, "(").unwrap();
+            let mut is_first = true;
+            for (var, coeff) in proc_lc::<E>(&lc) {
+                if coeff == negone {
+                    write!(s, " - ").unwrap();
+                } else if !is_first {
+                    write!(s, " + ").unwrap();
+                }
+                is_first = false;
+
+                if coeff != E::Fr::one() && coeff != negone {
+                    for (i, x) in powers_of_two.iter().enumerate() {
+                        if x == &coeff {
+                            write!(s, "2^{} . ", i).unwrap();
+                            break;
+                        }
+                    }
+
+                    write!(s, "{} . ", coeff).unwrap();
+                }
+
+                match var.0.get_unchecked() {
+                    Index::Input(i) => {
+                        write!(s, "`{}`", &self.inputs[i].1).unwrap();
+                    }
+                    Index::Aux(i) => {
+                        write!(s, "`{}`", &self.aux[i].1).unwrap();
+                    }
+                }
+            }
+            if is_first {
+                // Nothing was visited, print 0.
+                write!(s, "0").unwrap();
+            }
+            write!(s, ")").unwrap();
+        };
+
+        for &(ref a, ref b, ref c, ref name) in &self.constraints {
+            writeln!(&mut s).unwrap();
+
+            write!(&mut s, "{}: ", name).unwrap();
+            pp(&mut s, a);
+            write!(&mut s, " * ").unwrap();
+            pp(&mut s, b);
+            write!(&mut s, " = ").unwrap();
+            pp(&mut s, c);
+        }
+
+        writeln!(&mut s).unwrap();
+
+        s
+    }
+
     pub fn hash(&self) -> String {
         let mut h = Blake2s::new();
         {
@@ -289,6 +362,22 @@ impl<E: Engine> TestConstraintSystem<E> {
         &self.inputs[..]
     }
 
+    pub(super) fn aux(&self) -> &[(E::Fr, String)] {
+        &self.aux[..]
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub(super) fn constraints(
+        &self,
+    ) -> &[(
+        LinearCombination<E>,
+        LinearCombination<E>,
+        LinearCombination<E>,
+        String,
+    )] {
+        &self.constraints[..]
+    }
+
     pub fn get(&mut self, path: &str) -> E::Fr {
         match self.named_objects.get(path) {
             Some(&NamedObject::Var(ref v)) => match v.get_unchecked() {
@@ -326,7 +415,7 @@ fn compute_path(ns: &[String], this: &str) -> String {
     format!("{}/{}", name, this)
 }
 
-impl<E: Engine> ConstraintSystem<E> for TestConstraintSystem<E> {
+impl<E: ScalarEngine> ConstraintSystem<E> for TestConstraintSystem<E> {
     type Root = Self;
 
     fn alloc<F, A, AR>(&mut self, annotation: A, f: F) -> Result<Variable, SynthesisError>
@@ -0,0 +1,485 @@
+//! Detection of under-constrained auxiliary variables.
+//!
+//! Parameter generation fails with `UnconstrainedVariable` for aux variables
+//! which appear in no constraint, but a variable can also appear in
+//! constraints which fail to determine it, letting a malicious prover pick
+//! its value. `UnderconstrainedCS` records a circuit along with its witness
+//! and looks for such variables in two steps:
+//!
+//! 1. The constraints are linearized around the witness. Every aux variable
+//!    which is not determined by the linearized system, i.e. which has a
+//!    non-zero entry in a vector of its kernel, is suspected.
+//! 2. The witness is perturbed along random kernel vectors. A perturbed
+//!    witness which still satisfies every constraint confirms that the
+//!    suspects it changes can take other values. Suspects whose other
+//!    values lie on a curve rather than a line through the witness are not
+//!    confirmed this way.
+//!
+//! The public inputs stay fixed, as they are known to the verifier. The
+//! analysis is local to the witness, so variables with only finitely many
+//! alternative values, such as the square root of an input, are not
+//! reported.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use ff::{Field, ScalarEngine};
+use rand_core::RngCore;
+
+use crate::{ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
+
+/// The number of random combinations of kernel vectors tried per suspect,
+/// after trying each kernel vector on its own.
+const PERTURBATION_ROUNDS: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Finding {
+    /// The variable appears in no constraint.
+    Unconstrained,
+    /// Another witness satisfying every constraint assigns the variable a
+    /// different value.
+    Confirmed,
+    /// The linearized constraints don't determine the variable, but no other
+    /// satisfying witness was found.
+    Suspected,
+}
+
+/// An aux variable which is not uniquely determined by the constraints.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Underconstrained {
+    pub path: String,
+    pub finding: Finding,
+}
+
+/// Constraint system for finding under-constrained variables. Synthesis
+/// requires a witness.
+pub struct UnderconstrainedCS<E: ScalarEngine> {
+    current_namespace: Vec<String>,
+    #[allow(clippy::type_complexity)]
+    constraints: Vec<(
+        LinearCombination<E>,
+        LinearCombination<E>,
+        LinearCombination<E>,
+        String,
+    )>,
+    inputs: Vec<E::Fr>,
+    aux: Vec<(E::Fr, String)>,
+}
+
+impl<E: ScalarEngine> Default for UnderconstrainedCS<E> {
+    fn default() -> Self {
+        UnderconstrainedCS {
+            current_namespace: vec![],
+            constraints: vec![],
+            inputs: vec![E::Fr::one()],
+            aux: vec![],
+        }
+    }
+}
+
+impl<E: ScalarEngine> UnderconstrainedCS<E> {
+    pub fn new() -> Self {
+        UnderconstrainedCS::default()
+    }
+
+    pub fn num_constraints(&self) -> usize {
+        self.constraints.len()
+    }
+
+    /// Finds the aux variables whose value can be changed while every
+    /// constraint stays satisfied, ordered by allocation. Fails with
+    /// `Unsatisfiable` if the witness does not satisfy the constraints.
+    pub fn analyze<R: RngCore>(
+        &self,
+        rng: &mut R,
+    ) -> Result<Vec<Underconstrained>, SynthesisError> {
+        let witness = self.aux.iter().map(|(value, _)| *value).collect::<Vec<_>>();
+        if !self.is_satisfied_by(&witness) {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+
+        // The derivative of A * B - C at the witness is b * A + a * B - C,
+        // where a and b are the evaluations of A and B.
+        let mut constrained = vec![false; self.aux.len()];
+        let mut echelon = Echelon::default();
+        let mut neg_one = E::Fr::one();
+        neg_one.negate();
+        for (a, b, c, _) in &self.constraints {
+            let a_value = self.eval(a, &witness);
+            let b_value = self.eval(b, &witness);
+
+            let mut row = BTreeMap::new();
+            add_terms(&mut row, &mut constrained, a, &b_value);
+            add_terms(&mut row, &mut constrained, b, &a_value);
+            add_terms(&mut row, &mut constrained, c, &neg_one);
+            echelon.insert(row);
+        }
+
+        // Each free column spans one kernel vector, with a one at the free
+        // column and the negated entries of that column in the pivot rows.
+        let mut kernel = HashMap::new();
+        for i in (0..self.aux.len()).filter(|i| !echelon.rows.contains_key(i)) {
+            kernel.insert(i, vec![(i, E::Fr::one())]);
+        }
+        for (&pivot, row) in &echelon.rows {
+            for (&column, coeff) in row {
+                if column == pivot {
+                    continue;
+                }
+                let mut coeff = *coeff;
+                coeff.negate();
+                kernel
+                    .get_mut(&column)
+                    .expect("pivot rows only contain free columns")
+                    .push((pivot, coeff));
+            }
+        }
+
+        let mut confirmed = HashSet::new();
+        let mut findings = vec![];
+        for (i, (_, path)) in self.aux.iter().enumerate() {
+            let free_columns = match echelon.rows.get(&i) {
+                Some(row) => row.keys().cloned().filter(|&column| column != i).collect(),
+                None => vec![i],
+            };
+            if free_columns.is_empty() {
+                continue;
+            }
+
+            let finding = if !constrained[i] {
+                Finding::Unconstrained
+            } else if confirmed.contains(&i)
+                || self.perturb(rng, &witness, i, &free_columns, &kernel, &mut confirmed)
+            {
+                Finding::Confirmed
+            } else {
+                Finding::Suspected
+            };
+
+            findings.push(Underconstrained {
+                path: path.clone(),
+                finding,
+            });
+        }
+
+        Ok(findings)
+    }
+
+    /// Tries to change the value of `target` by moving the witness along the
+    /// kernel vectors of `free_columns`. On success, every variable the
+    /// perturbation changed is added to `confirmed`.
+    fn perturb<R: RngCore>(
+        &self,
+        rng: &mut R,
+        witness: &[E::Fr],
+        target: usize,
+        free_columns: &[usize],
+        kernel: &HashMap<usize, Vec<(usize, E::Fr)>>,
+        confirmed: &mut HashSet<usize>,
+    ) -> bool {
+        let singles = free_columns.iter().map(|&column| vec![column]);
+        let combinations = (0..PERTURBATION_ROUNDS).map(|_| free_columns.to_vec());
+
+        for columns in singles.chain(combinations) {
+            let mut perturbed = witness.to_vec();
+            for column in columns {
+                let scale = E::Fr::random(rng);
+                for (i, coeff) in &kernel[&column] {
+                    let mut delta = *coeff;
+                    delta.mul_assign(&scale);
+                    perturbed[*i].add_assign(&delta);
+                }
+            }
+
+            if perturbed[target] != witness[target] && self.is_satisfied_by(&perturbed) {
+                confirmed.extend((0..witness.len()).filter(|&i| perturbed[i] != witness[i]));
+                return true;
+            }
+        }
+
+        false
+    }
+
+    fn eval(&self, lc: &LinearCombination<E>, aux: &[E::Fr]) -> E::Fr {
+        let mut acc = E::Fr::zero();
+
+        for (var, coeff) in lc.iter() {
+            let mut tmp = match var.get_unchecked() {
+                Index::Input(index) => self.inputs[index],
+                Index::Aux(index) => aux[index],
+            };
+
+            tmp.mul_assign(&coeff);
+            acc.add_assign(&tmp);
+        }
+
+        acc
+    }
+
+    fn is_satisfied_by(&self, aux: &[E::Fr]) -> bool {
+        self.constraints.iter().all(|(a, b, c, _)| {
+            let mut a = self.eval(a, aux);
+            a.mul_assign(&self.eval(b, aux));
+            a == self.eval(c, aux)
+        })
+    }
+}
+
+/// Adds `factor` times the aux terms of `lc` to `row`, and marks the aux
+/// variables with non-zero coefficients as constrained.
+fn add_terms<E: ScalarEngine>(
+    row: &mut BTreeMap<usize, E::Fr>,
+    constrained: &mut [bool],
+    lc: &LinearCombination<E>,
+    factor: &E::Fr,
+) {
+    for (var, coeff) in lc.iter() {
+        if let Index::Aux(i) = var.get_unchecked() {
+            if coeff.is_zero() {
+                continue;
+            }
+            constrained[i] = true;
+
+            let mut term = *coeff;
+            term.mul_assign(factor);
+            row.entry(i).or_insert_with(E::Fr::zero).add_assign(&term);
+        }
+    }
+}
+
+/// A basis of the row space of a sparse matrix in reduced row echelon form,
+/// keyed by pivot column. Pivot rows are normalized to a one at the pivot,
+/// and don't contain any other pivot column.
+struct Echelon<F: Field> {
+    rows: BTreeMap<usize, BTreeMap<usize, F>>,
+}
+
+impl<F: Field> Default for Echelon<F> {
+    fn default() -> Self {
+        Echelon {
+            rows: BTreeMap::new(),
+        }
+    }
+}
+
+impl<F: Field> Echelon<F> {
+    fn insert(&mut self, row: BTreeMap<usize, F>) {
+        let mut row = row
+            .into_iter()
+            .filter(|(_, coeff)| !coeff.is_zero())
+            .collect::<BTreeMap<_, _>>();
+
+        // Pivot rows don't contain other pivots, so subtracting one leaves
+        // the coefficients of the other pivots unchanged.
+        let pivots = row
+            .keys()
+            .cloned()
+            .filter(|column| self.rows.contains_key(column))
+            .collect::<Vec<_>>();
+        for pivot in pivots {
+            let factor = row[&pivot];
+            sub_scaled(&mut row, &self.rows[&pivot], &factor);
+        }
+
+        let pivot = match row.keys().next() {
+            Some(&pivot) => pivot,
+            None => return,
+        };
+        let inverse = row[&pivot].inverse().expect("pivot is non-zero");
+        for coeff in row.values_mut() {
+            coeff.mul_assign(&inverse);
+        }
+
+        for other in self.rows.values_mut() {
+            if let Some(factor) = other.get(&pivot).cloned() {
+                sub_scaled(other, &row, &factor);
+            }
+        }
+        self.rows.insert(pivot, row);
+    }
+}
+
+/// Subtracts `factor` times `row` from `target`, dropping zero entries.
+fn sub_scaled<F: Field>(target: &mut BTreeMap<usize, F>, row: &BTreeMap<usize, F>, factor: &F) {
+    for (&column, coeff) in row {
+        let mut term = *coeff;
+        term.mul_assign(factor);
+
+        let entry = target.entry(column).or_insert_with(F::zero);
+        entry.sub_assign(&term);
+        if entry.is_zero() {
+            target.remove(&column);
+        }
+    }
+}
+
+fn compute_path(ns: &[String], this: &str) -> String {
+    assert!(
+        !this.chars().any(|a| a == '/'),
+        "'/' is not allowed in names"
+    );
+
+    if ns.is_empty() {
+        return this.to_string();
+    }
+
+    let name = ns.join("/");
+    format!("{}/{}", name, this)
+}
+
+impl<E: ScalarEngine> ConstraintSystem<E> for UnderconstrainedCS<E> {
+    type Root = Self;
+
+    fn alloc<F, A, AR>(&mut self, annotation: A, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<E::Fr, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        let path = compute_path(&self.current_namespace, &annotation().into());
+        self.aux.push((f()?, path));
+
+        Ok(Variable::new_unchecked(Index::Aux(self.aux.len() - 1)))
+    }
+
+    fn alloc_input<F, A, AR>(&mut self, _annotation: A, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<E::Fr, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.inputs.push(f()?);
+
+        Ok(Variable::new_unchecked(Index::Input(self.inputs.len() - 1)))
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(&mut self, annotation: A, a: LA, b: LB, c: LC)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+        LA: FnOnce(LinearCombination<E>) -> LinearCombination<E>,
+        LB: FnOnce(LinearCombination<E>) -> LinearCombination<E>,
+        LC: FnOnce(LinearCombination<E>) -> LinearCombination<E>,
+    {
+        let path = compute_path(&self.current_namespace, &annotation().into());
+        let a = a(LinearCombination::zero());
+        let b = b(LinearCombination::zero());
+        let c = c(LinearCombination::zero());
+
+        self.constraints.push((a, b, c, path));
+    }
+
+    fn push_namespace<NR, N>(&mut self, name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        self.current_namespace.push(name_fn().into());
+    }
+
+    fn pop_namespace(&mut self) {
+        assert!(self.current_namespace.pop().is_some());
+    }
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ff::PrimeField;
+    use paired::bls12_381::{Bls12, Fr};
+    use rand_core::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    fn rng() -> XorShiftRng {
+        XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ])
+    }
+
+    /// Enforces `x^3 = out`, optionally replacing the check of `x^2` with
+    /// one that always holds.
+    fn cube<CS: ConstraintSystem<Bls12>>(cs: &mut CS, x_value: u64, broken: bool) {
+        let x_value = Fr::from_str(&x_value.to_string()).unwrap();
+        let mut x2_value = x_value;
+        x2_value.square();
+        let mut x3_value = x2_value;
+        x3_value.mul_assign(&x_value);
+
+        let out = cs.alloc_input(|| "out", || Ok(x3_value)).unwrap();
+        let x = cs.alloc(|| "x", || Ok(x_value)).unwrap();
+        let x2 = cs.alloc(|| "x2", || Ok(x2_value)).unwrap();
+        let x3 = cs.alloc(|| "x3", || Ok(x3_value)).unwrap();
+
+        let mut cs = cs.namespace(|| "cube");
+        if broken {
+            cs.enforce(|| "x2", |lc| lc + x2, |lc| lc + x - x, |lc| lc);
+        } else {
+            cs.enforce(|| "x2", |lc| lc + x, |lc| lc + x, |lc| lc + x2);
+        }
+        cs.enforce(|| "x3", |lc| lc + x2, |lc| lc + x, |lc| lc + x3);
+        cs.enforce(|| "out", |lc| lc + x3, |lc| lc + CS::one(), |lc| lc + out);
+    }
+
+    #[test]
+    fn test_sound_circuit() {
+        let mut cs = UnderconstrainedCS::<Bls12>::new();
+        cube(&mut cs, 3, false);
+        assert_eq!(cs.num_constraints(), 3);
+        assert!(cs.analyze(&mut rng()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_underconstrained() {
+        let mut cs = UnderconstrainedCS::<Bls12>::new();
+        cube(&mut cs, 3, true);
+
+        let two = Fr::from_str("2").unwrap();
+        let a = cs.alloc(|| "a", || Ok(Fr::one())).unwrap();
+        let b = cs.alloc(|| "b", || Ok(Fr::one())).unwrap();
+        let y = cs.alloc(|| "y", || Ok(two)).unwrap();
+        let zero = cs.alloc(|| "zero", || Ok(Fr::zero())).unwrap();
+        cs.alloc(|| "unused", || Ok(Fr::one())).unwrap();
+        cs.enforce(
+            || "a + b = 2",
+            |lc| lc + a + b,
+            |lc| lc + UnderconstrainedCS::<Bls12>::one(),
+            |lc| lc + (two, UnderconstrainedCS::<Bls12>::one()),
+        );
+        cs.enforce(|| "y * 0 = 0", |lc| lc + y, |lc| lc, |lc| lc);
+        cs.enforce(|| "zero^2 = 0", |lc| lc + zero, |lc| lc + zero, |lc| lc);
+
+        let findings = cs.analyze(&mut rng()).unwrap();
+        let expected = vec![
+            // x2 = 27 / x, which no perturbation along a line satisfies.
+            ("x", Finding::Suspected),
+            ("x2", Finding::Suspected),
+            ("a", Finding::Confirmed),
+            ("b", Finding::Confirmed),
+            ("y", Finding::Confirmed),
+            ("zero", Finding::Suspected),
+            ("unused", Finding::Unconstrained),
+        ];
+        assert_eq!(
+            findings,
+            expected
+                .into_iter()
+                .map(|(path, finding)| Underconstrained {
+                    path: path.to_string(),
+                    finding,
+                })
+                .collect::<Vec<_>>()
+        );
+
+        // Changing the witness is caught before the analysis.
+        cs.aux[0].0 = Fr::one();
+        assert!(match cs.analyze(&mut rng()) {
+            Err(SynthesisError::Unsatisfiable) => true,
+            _ => false,
+        });
+    }
+}
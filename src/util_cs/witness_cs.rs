@@ -0,0 +1,97 @@
+use ff::ScalarEngine;
+
+use crate::{ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
+
+/// Constraint system which only records variable assignments. Constraints
+/// are ignored entirely, which makes this the cheapest way to compute the
+/// witness of a circuit whose constraints are already known.
+pub struct WitnessCS<E: ScalarEngine> {
+    input_assignment: Vec<E::Fr>,
+    aux_assignment: Vec<E::Fr>,
+}
+
+impl<E: ScalarEngine> WitnessCS<E> {
+    pub fn input_assignment(&self) -> &[E::Fr] {
+        &self.input_assignment
+    }
+
+    pub fn aux_assignment(&self) -> &[E::Fr] {
+        &self.aux_assignment
+    }
+
+    /// Returns the input and auxiliary assignments.
+    pub fn into_assignment(self) -> (Vec<E::Fr>, Vec<E::Fr>) {
+        (self.input_assignment, self.aux_assignment)
+    }
+}
+
+impl<E: ScalarEngine> ConstraintSystem<E> for WitnessCS<E> {
+    type Root = Self;
+
+    fn new() -> Self {
+        WitnessCS {
+            input_assignment: vec![],
+            aux_assignment: vec![],
+        }
+    }
+
+    fn alloc<F, A, AR>(&mut self, _: A, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<E::Fr, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.aux_assignment.push(f()?);
+
+        Ok(Variable(Index::Aux(self.aux_assignment.len() - 1)))
+    }
+
+    fn alloc_input<F, A, AR>(&mut self, _: A, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<E::Fr, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.input_assignment.push(f()?);
+
+        Ok(Variable(Index::Input(self.input_assignment.len() - 1)))
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(&mut self, _: A, _: LA, _: LB, _: LC)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+        LA: FnOnce(LinearCombination<E>) -> LinearCombination<E>,
+        LB: FnOnce(LinearCombination<E>) -> LinearCombination<E>,
+        LC: FnOnce(LinearCombination<E>) -> LinearCombination<E>,
+    {
+        // Do nothing; the constraints are known from elsewhere.
+    }
+
+    fn push_namespace<NR, N>(&mut self, _: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        // Do nothing; we don't care about namespaces in this context.
+    }
+
+    fn pop_namespace(&mut self) {
+        // Do nothing; we don't care about namespaces in this context.
+    }
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+
+    fn is_extensible() -> bool {
+        true
+    }
+
+    fn extend(&mut self, other: Self) {
+        self.input_assignment
+            // Skip first input, which must have been a temporarily allocated one variable.
+            .extend(&other.input_assignment[1..]);
+        self.aux_assignment.extend(other.aux_assignment);
+    }
+}